@@ -1,73 +1,204 @@
-use parser::Expression;
-use parser::Type;
+use parser::{Expression, ExpressionKind, Span, Type};
 
 pub fn desugar_program(exprs: Vec<Expression>) -> Vec<Expression> {
     exprs.into_iter().map(desugar_expr).collect()
 }
 
 fn desugar_expr(expr: Expression) -> Expression {
-    match expr {
-        Expression::ForStmt(var, start, end, step, body) => {
-            let init = Expression::LetStmt(
-                var.clone(),
-                Type::i32,
-                Box::new(Expression::Number(start)),
-            );
+    // Nodes synthesized by desugaring point back at the expression they replace
+    let span = expr.span;
+    let boxed = |expr: Box<Expression>| Box::new(desugar_expr(*expr));
+    let all = |exprs: Vec<Expression>| exprs.into_iter().map(desugar_expr).collect();
+    let kind = match expr.kind {
+        ExpressionKind::ForEachStmt(var, iterable, body) => desugar_for_each(
+            var,
+            desugar_expr(*iterable),
+            desugar_expr(*body),
+            None,
+            span,
+        ),
+        ExpressionKind::LabeledStmt(label, stmt) => match stmt.kind {
+            // The label moves onto the while loop the for-each is lowered to
+            ExpressionKind::ForEachStmt(var, iterable, body) => desugar_for_each(
+                var,
+                desugar_expr(*iterable),
+                desugar_expr(*body),
+                Some(label),
+                span,
+            ),
+            _ => ExpressionKind::LabeledStmt(label, boxed(stmt)),
+        },
+        ExpressionKind::ForStmt(init, cond, step, body) => {
+            ExpressionKind::ForStmt(boxed(init), boxed(cond), boxed(step), boxed(body))
+        }
+        ExpressionKind::List(values) => ExpressionKind::List(all(values)),
+        ExpressionKind::Map(entries) => ExpressionKind::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (desugar_expr(key), desugar_expr(value)))
+                .collect(),
+        ),
+        ExpressionKind::ListIndex(list, index) => {
+            ExpressionKind::ListIndex(boxed(list), boxed(index))
+        }
+        ExpressionKind::ListAssign(name, index, value) => {
+            ExpressionKind::ListAssign(name, boxed(index), boxed(value))
+        }
+        ExpressionKind::ListSlice(list, start, end) => {
+            ExpressionKind::ListSlice(boxed(list), start.map(boxed), end.map(boxed))
+        }
+        ExpressionKind::StructLiteral(name, fields) => ExpressionKind::StructLiteral(
+            name,
+            fields
+                .into_iter()
+                .map(|(field, value)| (field, desugar_expr(value)))
+                .collect(),
+        ),
+        ExpressionKind::FieldAccess(value, field) => {
+            ExpressionKind::FieldAccess(boxed(value), field)
+        }
+        ExpressionKind::FieldAssign(target, field, value) => {
+            ExpressionKind::FieldAssign(boxed(target), field, boxed(value))
+        }
+        ExpressionKind::EnumValue(name, variant, values) => {
+            ExpressionKind::EnumValue(name, variant, all(values))
+        }
+        ExpressionKind::MatchStmt(value, arms) => {
+            ExpressionKind::MatchStmt(boxed(value), all(arms))
+        }
+        ExpressionKind::MatchArm(pattern, body) => ExpressionKind::MatchArm(pattern, boxed(body)),
+        ExpressionKind::MethodCall(value, method, args) => {
+            ExpressionKind::MethodCall(boxed(value), method, all(args))
+        }
+        ExpressionKind::ImplStmt(self_type, methods) => {
+            ExpressionKind::ImplStmt(self_type, all(methods))
+        }
+        ExpressionKind::Lambda(params, return_type, body) => {
+            ExpressionKind::Lambda(params, return_type, boxed(body))
+        }
+        ExpressionKind::Binary(left, op, right) => {
+            ExpressionKind::Binary(boxed(left), op, boxed(right))
+        }
+        ExpressionKind::Unary(op, operand) => ExpressionKind::Unary(op, boxed(operand)),
+        ExpressionKind::Grouping(inner) => ExpressionKind::Grouping(boxed(inner)),
+        ExpressionKind::LetStmt(name, var_type, value) => {
+            ExpressionKind::LetStmt(name, var_type, boxed(value))
+        }
+        ExpressionKind::DeclareStmt(name, var_type, value) => {
+            ExpressionKind::DeclareStmt(name, var_type, boxed(value))
+        }
+        ExpressionKind::BlockStmt(stmts) => ExpressionKind::BlockStmt(all(stmts)),
+        ExpressionKind::FuncStmt(name, args, return_type, body) => {
+            ExpressionKind::FuncStmt(name, args, return_type, boxed(body))
+        }
+        ExpressionKind::PubStmt(func) => ExpressionKind::PubStmt(boxed(func)),
+        ExpressionKind::CallStmt(name, args) => ExpressionKind::CallStmt(name, all(args)),
+        ExpressionKind::IfStmt(cond, then_branch, else_branch) => ExpressionKind::IfStmt(
+            boxed(cond),
+            boxed(then_branch),
+            Box::new(else_branch.map(desugar_expr)),
+        ),
+        ExpressionKind::WhileStmt(cond, body) => {
+            ExpressionKind::WhileStmt(boxed(cond), boxed(body))
+        }
+        ExpressionKind::ReturnStmt(value) => ExpressionKind::ReturnStmt(boxed(value)),
+        ExpressionKind::IterItems(value) => ExpressionKind::IterItems(boxed(value)),
+        ExpressionKind::Print(value) => ExpressionKind::Print(boxed(value)),
+        ExpressionKind::Len(value) => ExpressionKind::Len(boxed(value)),
+        // Nodes without child expressions are left as they are
+        leaf @ (ExpressionKind::Number(_)
+        | ExpressionKind::Number64(_)
+        | ExpressionKind::Float(_)
+        | ExpressionKind::String(_)
+        | ExpressionKind::Bool(_)
+        | ExpressionKind::Nil
+        | ExpressionKind::StructStmt(..)
+        | ExpressionKind::EnumStmt(..)
+        | ExpressionKind::Variable(_)
+        | ExpressionKind::FuncArg(..)
+        | ExpressionKind::ExternFuncStmt(..)
+        | ExpressionKind::ExternModule(_)
+        | ExpressionKind::Import(_)
+        | ExpressionKind::BreakStmt(_)
+        | ExpressionKind::ContinueStmt(_)) => leaf,
+    };
+    Expression::new(kind, span)
+}
 
-            let cond = Expression::Binary(
-                Box::new(Expression::Variable(var.clone())),
-                if step >= 0 { "<".to_string() } else { ">".to_string() },
-                Box::new(Expression::Number(end)),
-            );
+/// Lowers `for (x in xs) { body }` to
+///
+/// ```text
+/// {
+///     let $items = <xs as a list>;
+///     let $index = 0;
+///     while ($index < len($items)) {
+///         let x = $items[$index];
+///         $index = $index + 1;
+///         { body }
+///     }
+/// }
+/// ```
+///
+/// The hidden variables are named after the loop's position so nested loops
+/// don't clash, and use `$` so they can't collide with a user's variable. `x`
+/// is declared in the loop body, so it shadows an outer variable of the same
+/// name rather than assigning to it. The index is advanced before the body
+/// runs so `continue` can't skip it, and a label on the for-each is carried
+/// over to the while loop.
+fn desugar_for_each(
+    var: String,
+    iterable: Expression,
+    body: Expression,
+    label: Option<String>,
+    span: Span,
+) -> ExpressionKind {
+    let items = format!("$items{}", span.start);
+    let index = format!("$index{}", span.start);
+    let node = |kind| Expression::new(kind, span);
+    let variable = |name: &str| Box::new(node(ExpressionKind::Variable(name.to_string())));
 
-            let incr = Expression::LetStmt(
-                var.clone(),
-                Type::None,
-                Box::new(Expression::Binary(
-                    Box::new(Expression::Variable(var.clone())),
-                    if step >= 0 { "+".to_string() } else { "-".to_string() },
-                    Box::new(Expression::Number(step.abs())),
-                )),
-            );
+    let init_items = node(ExpressionKind::LetStmt(
+        items.clone(),
+        Type::None,
+        Box::new(node(ExpressionKind::IterItems(Box::new(iterable)))),
+    ));
+    let init_index = node(ExpressionKind::LetStmt(
+        index.clone(),
+        Type::i32,
+        Box::new(node(ExpressionKind::Number(0))),
+    ));
 
-            let body = match *body {
-                Expression::BlockStmt(mut stmts) => {
-                    stmts.push(incr);
-                    Expression::BlockStmt(stmts)
-                }
-                other => Expression::BlockStmt(vec![other, incr]),
-            };
+    let cond = node(ExpressionKind::Binary(
+        variable(&index),
+        "<".to_string(),
+        Box::new(node(ExpressionKind::Len(variable(&items)))),
+    ));
 
-            Expression::BlockStmt(vec![
-                init,
-                Expression::WhileStmt(Box::new(cond), Box::new(body)),
-            ])
-        }
-        Expression::BlockStmt(stmts) => {
-            Expression::BlockStmt(stmts.into_iter().map(desugar_expr).collect())
-        }
-        Expression::IfStmt(cond, then_branch, else_branch) => {
-            let else_branch = *else_branch;
-            Expression::IfStmt(
-                Box::new(desugar_expr(*cond)),
-                Box::new(desugar_expr(*then_branch)),
-                Box::new(else_branch.map(desugar_expr)),
-            )
-        }
-        Expression::WhileStmt(cond, body) => {
-            Expression::WhileStmt(Box::new(desugar_expr(*cond)), Box::new(desugar_expr(*body)))
-        }
-        Expression::BreakStmt => Expression::BreakStmt,
-        Expression::FuncStmt(name, args, return_type, body) => Expression::FuncStmt(
-            name,
-            args,
-            return_type,
-            Box::new(desugar_expr(*body)),
-        ),
-        Expression::ExternFuncStmt(name, args, return_type) => {
-            Expression::ExternFuncStmt(name, args, return_type)
-        }
-        Expression::ExternModule(path) => Expression::ExternModule(path),
-        other => other,
+    let load_item = node(ExpressionKind::DeclareStmt(
+        var,
+        Type::None,
+        Box::new(node(ExpressionKind::ListIndex(
+            variable(&items),
+            variable(&index),
+        ))),
+    ));
+    let incr = node(ExpressionKind::LetStmt(
+        index.clone(),
+        Type::None,
+        Box::new(node(ExpressionKind::Binary(
+            variable(&index),
+            "+".to_string(),
+            Box::new(node(ExpressionKind::Number(1))),
+        ))),
+    ));
+
+    let loop_body = node(ExpressionKind::BlockStmt(vec![load_item, incr, body]));
+    let mut while_loop = node(ExpressionKind::WhileStmt(
+        Box::new(cond),
+        Box::new(loop_body),
+    ));
+    if let Some(label) = label {
+        while_loop = node(ExpressionKind::LabeledStmt(label, Box::new(while_loop)));
     }
+    ExpressionKind::BlockStmt(vec![init_items, init_index, while_loop])
 }
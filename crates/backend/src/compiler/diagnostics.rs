@@ -0,0 +1,129 @@
+use parser::{SourceFile, Span};
+use std::fmt;
+
+/// A compile error tied to the region of source that caused it.
+///
+/// Diagnostics travel through `anyhow::Error` like any other error; the
+/// driver downcasts to this type to render the offending source line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Option<Span>,
+    pub label: Option<String>,
+    /// Name of the imported file `span` indexes, None for the file being compiled
+    pub file: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span: Some(span),
+            label: None,
+            file: None,
+        }
+    }
+
+    /// Creates a diagnostic that applies to the whole program rather than a
+    /// single expression, e.g. a missing `main` function.
+    pub fn without_span(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            span: None,
+            label: None,
+            file: None,
+        }
+    }
+
+    /// Sets the text printed next to the caret underline.
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Points the span into an imported file rather than the one being compiled.
+    pub fn in_file(mut self, file: Option<String>) -> Self {
+        self.file = file;
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Every diagnostic reported during a compile, in the order they were found.
+///
+/// The type resolver and semantic analyzer keep going after an error so the
+/// user sees all of them at once; the driver renders each in turn, against
+/// the imported file it came from when there is one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+    // Every imported file, so spans into them can be rendered
+    sources: Vec<SourceFile>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn extend(&mut self, other: Diagnostics) {
+        self.diagnostics.extend(other.diagnostics);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    pub fn with_sources(mut self, sources: Vec<SourceFile>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// Returns the imported file a diagnostic points into, if any.
+    pub fn source(&self, diagnostic: &Diagnostic) -> Option<&SourceFile> {
+        let file = diagnostic.file.as_ref()?;
+        self.sources.iter().find(|source| source.name == *file)
+    }
+}
+
+impl From<Vec<Diagnostic>> for Diagnostics {
+    fn from(diagnostics: Vec<Diagnostic>) -> Self {
+        Self {
+            diagnostics,
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
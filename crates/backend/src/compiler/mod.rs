@@ -1,3 +1,5 @@
+mod diagnostics;
+mod module_loader;
 mod semantic_analyzer;
 mod type_resolver;
 mod validation_rules;
@@ -8,36 +10,67 @@ use crate::compiler::type_resolver::TypeResolver;
 use anyhow::Result;
 use codegen::builder::LLVMCodegenBuilder;
 use codegen::code_generator::CodeGenerator;
-use parser::Expression;
+use parser::{Expression, ExpressionKind};
 use std::path::Path;
 use std::process::Command;
 use tempfile::NamedTempFile;
 
 pub use codegen::CompileOptions;
 pub use desugar::desugar_program;
+pub use diagnostics::{Diagnostic, Diagnostics};
 
 pub fn compile(exprs: Vec<Expression>, options: Option<CompileOptions>) -> Result<String> {
-    let (extern_modules, exprs) = extract_extern_modules(exprs);
-    let exprs = desugar::desugar_program(exprs);
+    let source = options.as_ref().and_then(|options| options.source.as_ref());
+    let modules = module_loader::load_program(exprs, source)?;
+    let mut extern_modules = Vec::new();
     let mut type_resolver = TypeResolver::new();
     let mut typed_exprs = Vec::new();
-    for expr in exprs {
-        let (typed_expr, ty) = type_resolver.resolve_expression(&expr)?;
-        typed_exprs.push((typed_expr, ty));
+    // The imported file each typed expression came from, None for the program
+    let mut files = Vec::new();
+    let mut sources = Vec::new();
+    // Imported modules come first, so their functions are declared before use
+    for module in modules {
+        let (module_externs, exprs) = extract_extern_modules(module.exprs);
+        extern_modules.extend(module_externs);
+        let file = module.source.as_ref().map(|source| source.name.clone());
+        type_resolver.enter_module(module.name, module.imports, file.clone());
+        for expr in desugar::desugar_program(exprs) {
+            let (typed_expr, ty) = type_resolver.resolve_expression(&expr);
+            typed_exprs.push((typed_expr, ty));
+            files.push(file.clone());
+        }
+        sources.extend(module.source);
     }
 
     let mut analyzer = SemanticAnalyzer::new();
 
-    analyzer.validate_program(&typed_exprs)?;
+    analyzer.validate_program(&typed_exprs, &files);
     for (typed_expr, _) in &typed_exprs {
         analyzer.analyze(typed_expr)?;
     }
 
+    // Report every error found so far before attempting codegen
+    let mut diagnostics = type_resolver.into_diagnostics();
+    diagnostics.extend(analyzer.into_diagnostics());
+    if !diagnostics.is_empty() {
+        return Err(diagnostics.with_sources(sources).into());
+    }
+
+    let root_source = source.cloned();
     let mut codegen_builder = LLVMCodegenBuilder::init(options)?;
     let _extern_bc_files = link_extern_modules(&mut codegen_builder, &extern_modules)?;
     let mut generator = CodeGenerator::new(&mut codegen_builder);
 
-    for (typed_expr, _ty) in typed_exprs {
+    // Runtime panics point into the file the code being generated came from
+    let mut current_file = None;
+    for ((typed_expr, _ty), file) in typed_exprs.into_iter().zip(files) {
+        if file != current_file {
+            generator.set_source(match &file {
+                Some(file) => sources.iter().find(|source| source.name == *file).cloned(),
+                None => root_source.clone(),
+            });
+            current_file = file;
+        }
         generator.generate_expression(&typed_expr)?;
     }
 
@@ -48,9 +81,9 @@ fn extract_extern_modules(exprs: Vec<Expression>) -> (Vec<String>, Vec<Expressio
     let mut modules = Vec::new();
     let mut filtered = Vec::new();
     for expr in exprs {
-        match expr {
-            Expression::ExternModule(path) => modules.push(path),
-            other => filtered.push(other),
+        match expr.kind {
+            ExpressionKind::ExternModule(path) => modules.push(path),
+            _ => filtered.push(expr),
         }
     }
     (modules, filtered)
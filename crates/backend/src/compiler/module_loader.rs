@@ -0,0 +1,165 @@
+use crate::compiler::diagnostics::Diagnostic;
+use anyhow::{Context, Result};
+use parser::{parse_cyclo_program, Expression, ExpressionKind, SourceFile, Span};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A parsed source file with its imports taken out.
+pub struct Module {
+    /// Name other files call its functions by, None for the file being compiled
+    pub name: Option<String>,
+    /// Names of the modules this one imports
+    pub imports: Vec<String>,
+    pub exprs: Vec<Expression>,
+    /// File the module was loaded from, None for the file being compiled
+    pub source: Option<SourceFile>,
+}
+
+/// Follows `import` statements from the file being compiled, parsing each
+/// imported file once. Imports are resolved relative to the importing file.
+#[derive(Default)]
+struct ModuleLoader {
+    // Canonical path of every module loaded so far -> its name
+    loaded: HashMap<PathBuf, String>,
+    // Files whose imports are being loaded, each imported by the one before it
+    loading: Vec<PathBuf>,
+    modules: Vec<Module>,
+}
+
+/// Loads every module the program imports, directly or not. Modules come
+/// before the ones importing them, with the program itself last.
+pub fn load_program(exprs: Vec<Expression>, source: Option<&SourceFile>) -> Result<Vec<Module>> {
+    let mut loader = ModuleLoader::default();
+    let root = source.map(|source| PathBuf::from(&source.name));
+    let dir = root
+        .as_deref()
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    if let Some(root) = root.and_then(|root| root.canonicalize().ok()) {
+        loader.loading.push(root);
+    }
+    let (imports, exprs) = loader.load_imports(exprs, &dir, None)?;
+    loader.modules.push(Module {
+        name: None,
+        imports,
+        exprs,
+        source: None,
+    });
+    Ok(loader.modules)
+}
+
+impl ModuleLoader {
+    /// Loads the modules imported by `exprs`, returning their names and the
+    /// remaining expressions. `importer` is None for the file being compiled.
+    fn load_imports(
+        &mut self,
+        exprs: Vec<Expression>,
+        dir: &Path,
+        importer: Option<&SourceFile>,
+    ) -> Result<(Vec<String>, Vec<Expression>)> {
+        let mut imports = Vec::new();
+        let mut rest = Vec::new();
+        for expr in exprs {
+            match &expr.kind {
+                ExpressionKind::Import(path) => {
+                    let name = self.load(dir, path, expr.span, importer)?;
+                    imports.push(name);
+                }
+                _ => rest.push(expr),
+            }
+        }
+        Ok((imports, rest))
+    }
+
+    fn load(
+        &mut self,
+        dir: &Path,
+        import: &str,
+        span: Span,
+        importer: Option<&SourceFile>,
+    ) -> Result<String> {
+        let path = dir.join(import);
+        let Ok(canonical) = path.canonicalize() else {
+            return Err(import_error(
+                format!("Cannot find module '{}'", import),
+                "no such file",
+                span,
+                importer,
+            ));
+        };
+        if let Some(name) = self.loaded.get(&canonical) {
+            return Ok(name.clone());
+        }
+        if let Some(start) = self.loading.iter().position(|p| *p == canonical) {
+            let cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| display_name(p))
+                .collect();
+            return Err(import_error(
+                format!("Import cycle: {}", cycle.join(" -> ")),
+                "imported here",
+                span,
+                importer,
+            ));
+        }
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if self.loaded.values().any(|loaded| *loaded == name) {
+            return Err(import_error(
+                format!("Module name '{}' is already taken by another import", name),
+                "modules are named after their file",
+                span,
+                importer,
+            ));
+        }
+
+        let contents = fs::read_to_string(&canonical)
+            .with_context(|| format!("failed to read module {}", path.display()))?;
+        let source = SourceFile::new(path.display().to_string(), contents.as_str());
+        let exprs = parse_cyclo_program(&contents).map_err(|e| {
+            Diagnostic::without_span(e.variant.message())
+                .with_label(format!("in {}", source.location(Span::from(e.as_ref()))))
+        })?;
+
+        self.loading.push(canonical.clone());
+        let module_dir = canonical.parent().unwrap_or(Path::new("")).to_path_buf();
+        let (imports, exprs) = self.load_imports(exprs, &module_dir, Some(&source))?;
+        self.loading.pop();
+
+        self.loaded.insert(canonical, name.clone());
+        self.modules.push(Module {
+            name: Some(name.clone()),
+            imports,
+            exprs,
+            source: Some(source),
+        });
+        Ok(name)
+    }
+}
+
+// Spans only index the file being compiled, so errors in an imported file
+// name the location of the import instead of pointing at it
+fn import_error(
+    message: String,
+    label: &str,
+    span: Span,
+    importer: Option<&SourceFile>,
+) -> anyhow::Error {
+    match importer {
+        None => Diagnostic::new(message, span).with_label(label).into(),
+        Some(source) => Diagnostic::without_span(message)
+            .with_label(format!("imported at {}", source.location(span)))
+            .into(),
+    }
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
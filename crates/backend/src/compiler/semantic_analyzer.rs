@@ -1,9 +1,13 @@
-use crate::compiler::validation_rules::{RequireMainFunction, ValidationRule};
+use crate::compiler::diagnostics::Diagnostics;
+use crate::compiler::validation_rules::{
+    ExhaustiveMatch, PrivateFunctionAccess, RequireMainFunction, ValidationRule,
+};
 use codegen::typed_ast::{ResolvedType, TypedExpression};
-use anyhow::{Context, Result};
+use anyhow::Result;
 
 pub struct SemanticAnalyzer {
     validation_rules: Vec<Box<dyn ValidationRule>>,
+    diagnostics: Diagnostics,
 }
 
 impl SemanticAnalyzer {
@@ -17,33 +21,61 @@ impl SemanticAnalyzer {
         Self {
             validation_rules: vec![
                 Box::new(RequireMainFunction),
+                Box::new(ExhaustiveMatch),
+                Box::new(PrivateFunctionAccess),
                 // Easy to add more rules here!
             ],
+            diagnostics: Diagnostics::new(),
         }
     }
 
-    /// Validates the entire program by running all validation rules
-    pub fn validate_program(&self, typed_exprs: &[(TypedExpression, ResolvedType)]) -> Result<()> {
+    /// Validates the entire program by running all validation rules,
+    /// collecting the problems each one finds rather than stopping at the first.
+    /// `files` holds the imported file each top-level expression came from.
+    pub fn validate_program(
+        &mut self,
+        typed_exprs: &[(TypedExpression, ResolvedType)],
+        files: &[Option<String>],
+    ) {
         for rule in &self.validation_rules {
-            rule.validate(typed_exprs)
-                .with_context(|| format!("Validation rule '{}' failed", rule.name()))?;
+            let mut diagnostics = rule.validate(typed_exprs);
+            for ((item, _), file) in typed_exprs.iter().zip(files) {
+                diagnostics.extend(
+                    rule.validate_item(item, typed_exprs)
+                        .into_iter()
+                        .map(|diagnostic| diagnostic.in_file(file.clone())),
+                );
+            }
+            for mut diagnostic in diagnostics {
+                diagnostic
+                    .label
+                    .get_or_insert_with(|| format!("required by rule '{}'", rule.name()));
+                self.diagnostics.push(diagnostic);
+            }
         }
-        Ok(())
+    }
+
+    /// Returns every problem reported by the validation rules.
+    pub fn into_diagnostics(self) -> Diagnostics {
+        self.diagnostics
     }
 
     pub fn analyze(&mut self, typed_expr: &TypedExpression) -> Result<()> {
         match typed_expr {
             TypedExpression::Number32 { value: _, .. } => self.analyze_number(typed_expr),
             TypedExpression::Number64 { value: _, .. } => self.analyze_number(typed_expr),
-            TypedExpression::String { value: _ } => Ok(()),
-            TypedExpression::Bool { value: _ } => Ok(()),
-            TypedExpression::Binary { left, op, right } => self.analyse_binary(left, op, right),
-            TypedExpression::CallStmt { callee, args: _ } => self.analyze_call(callee),
+            TypedExpression::Float64 { value: _, .. } => Ok(()),
+            TypedExpression::String { value: _, .. } => Ok(()),
+            TypedExpression::Bool { value: _, .. } => Ok(()),
+            TypedExpression::Binary { left, op, right, .. } => self.analyse_binary(left, op, right),
+            TypedExpression::Unary { operand, .. } => self.analyze(operand),
+            TypedExpression::CallStmt { callee, args: _, .. } => self.analyze_call(callee),
             TypedExpression::FuncStmt {
                 name: _,
                 args: _,
                 return_type: _,
                 body,
+                ..
             } => {
                 self.analyze(body)?;
                 Ok(())
@@ -52,27 +84,30 @@ impl SemanticAnalyzer {
                 name: _,
                 args: _,
                 return_type: _,
+                ..
             } => Ok(()),
-            TypedExpression::ExternModule { path: _ } => Ok(()),
-            TypedExpression::BlockStmt { statements } => {
+            TypedExpression::ExternModule { path: _, .. } => Ok(()),
+            TypedExpression::BlockStmt { statements, .. } => {
                 for stmt in statements {
                     self.analyze(stmt)?;
                 }
                 Ok(())
             }
-            TypedExpression::Variable { name: _ } => Ok(()),
-            TypedExpression::Print { value } => self.analyze(value),
-            TypedExpression::ReturnStmt { value } => self.analyze(value),
-            TypedExpression::BreakStmt => Ok(()),
+            TypedExpression::Variable { name: _, .. } => Ok(()),
+            TypedExpression::Print { value, .. } => self.analyze(value),
+            TypedExpression::ReturnStmt { value, .. } => self.analyze(value),
+            TypedExpression::BreakStmt { .. } | TypedExpression::ContinueStmt { .. } => Ok(()),
             TypedExpression::LetStmt {
                 name: _,
                 var_type: _,
                 value,
+                ..
             } => self.analyze(value),
             TypedExpression::IfStmt {
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => {
                 self.analyze(condition)?;
                 self.analyze(then_branch)?;
@@ -81,22 +116,31 @@ impl SemanticAnalyzer {
                 }
                 Ok(())
             }
-            TypedExpression::WhileStmt { condition, body } => {
+            TypedExpression::WhileStmt {
+                condition,
+                body,
+                step,
+                ..
+            } => {
                 self.analyze(condition)?;
                 self.analyze(body)?;
+                if let Some(step) = step {
+                    self.analyze(step)?;
+                }
                 Ok(())
             }
-            TypedExpression::AssignStmt { name: _, value } => {
+            TypedExpression::AssignStmt { name: _, value, .. } => {
                 self.analyze(value)?;
                 Ok(())
             }
-            TypedExpression::Grouping { inner } => {
+            TypedExpression::Grouping { inner, .. } => {
                 // Analyze the inner expression
                 self.analyze(inner)
             }
             TypedExpression::List {
                 elements,
                 element_type: _,
+                ..
             } => {
                 // Analyze all list elements
                 for elem in elements {
@@ -104,7 +148,7 @@ impl SemanticAnalyzer {
                 }
                 Ok(())
             }
-            TypedExpression::ListIndex { list, index } => {
+            TypedExpression::ListIndex { list, index, .. } => {
                 // Analyze the list and index expressions
                 self.analyze(list)?;
                 self.analyze(index)?;
@@ -114,21 +158,106 @@ impl SemanticAnalyzer {
                 name: _,
                 index,
                 value,
+                ..
             } => {
                 // Analyze the index and value expressions
                 self.analyze(index)?;
                 self.analyze(value)?;
                 Ok(())
             }
-            TypedExpression::Len { value } => {
+            TypedExpression::Len { value, .. } => {
                 // Analyze the value expression
                 self.analyze(value)
             }
+            TypedExpression::Cast { value, .. } | TypedExpression::IterItems { value, .. } => {
+                self.analyze(value)
+            }
+            TypedExpression::ListCall { list, args, .. } => {
+                self.analyze(list)?;
+                for arg in args {
+                    self.analyze(arg)?;
+                }
+                Ok(())
+            }
+            TypedExpression::ListSlice {
+                list, start, end, ..
+            } => {
+                self.analyze(list)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.analyze(bound)?;
+                }
+                Ok(())
+            }
+            TypedExpression::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.analyze(key)?;
+                    self.analyze(value)?;
+                }
+                Ok(())
+            }
+            TypedExpression::MapIndex { map, key, .. } => {
+                self.analyze(map)?;
+                self.analyze(key)?;
+                Ok(())
+            }
+            TypedExpression::MapAssign { key, value, .. } => {
+                self.analyze(key)?;
+                self.analyze(value)?;
+                Ok(())
+            }
+            TypedExpression::MapCall { map, args, .. } => {
+                self.analyze(map)?;
+                for arg in args {
+                    self.analyze(arg)?;
+                }
+                Ok(())
+            }
+            TypedExpression::StructStmt { .. } => Ok(()),
+            TypedExpression::StructLiteral { fields, .. } => {
+                for field in fields {
+                    self.analyze(field)?;
+                }
+                Ok(())
+            }
+            TypedExpression::FieldAccess { value, .. } => self.analyze(value),
+            TypedExpression::ImplStmt { methods, .. } => {
+                for method in methods {
+                    self.analyze(method)?;
+                }
+                Ok(())
+            }
+            TypedExpression::FieldAssign { target, value, .. } => {
+                self.analyze(target)?;
+                self.analyze(value)
+            }
+            TypedExpression::EnumStmt { .. } => Ok(()),
+            TypedExpression::EnumValue { values, .. } => {
+                for value in values {
+                    self.analyze(value)?;
+                }
+                Ok(())
+            }
+            TypedExpression::MatchStmt { value, arms, .. } => {
+                self.analyze(value)?;
+                for arm in arms {
+                    self.analyze(arm)?;
+                }
+                Ok(())
+            }
+            TypedExpression::MatchArm { body, .. } => self.analyze(body),
+            TypedExpression::OptionalValue { value, .. } => match value {
+                Some(value) => self.analyze(value),
+                None => Ok(()),
+            },
+            TypedExpression::NilCheck { value, .. } => self.analyze(value),
+            TypedExpression::Unwrap { value, .. } => self.analyze(value),
+            TypedExpression::Lambda { body, .. } => self.analyze(body),
+            TypedExpression::FunctionRef { .. } => Ok(()),
         }
     }
     pub fn analyze_number(&mut self, typed_expr: &TypedExpression) -> Result<()> {
         match typed_expr {
-            TypedExpression::Number32 { value: _ } => {
+            TypedExpression::Number32 { value: _, .. } => {
                 // Could check for specific constraints
                 // if *value < 0 && self.context.expects_unsigned() {
                 //     return Err(SemanticError::NegativeUnsigned {
@@ -138,7 +267,7 @@ impl SemanticAnalyzer {
                 // }
                 Ok(())
             }
-            TypedExpression::Number64 { value: _ } => Ok(()),
+            TypedExpression::Number64 { value: _, .. } => Ok(()),
             _ => unreachable!(),
         }
     }
@@ -1,6 +1,6 @@
-use codegen::typed_ast::{ResolvedType, TypedExpression};
-use anyhow::Result;
-use parser::Expression;
+use crate::compiler::diagnostics::{Diagnostic, Diagnostics};
+use codegen::typed_ast::{ListOp, MapOp, ResolvedType, TypedExpression};
+use parser::{Expression, ExpressionKind, Pattern};
 use std::collections::HashMap;
 
 pub struct TypeResolver {
@@ -10,8 +10,30 @@ pub struct TypeResolver {
     // Function table: function name -> (arg types, return type)
     function_table: HashMap<String, (Vec<ResolvedType>, ResolvedType)>,
     locals: HashMap<i32, Vec<String>>,
+    // Outer variables hidden by a declaration at each depth, restored when it ends
+    shadowed: HashMap<i32, Vec<(String, ResolvedType)>>,
     depth: i32,
     loop_depth: i32,
+    // Labels of the enclosing labeled loops, innermost last
+    loop_labels: Vec<String>,
+    // Struct table: struct name -> fields in declaration order
+    struct_table: HashMap<String, Vec<(String, ResolvedType)>>,
+    // Enum table: enum name -> variants and their payload types, in tag order
+    enum_table: HashMap<String, Vec<(String, Vec<ResolvedType>)>>,
+    // Return type of the function being resolved, for typing `return []`
+    return_type: Option<ResolvedType>,
+    // Lambdas being resolved, innermost last: the depth their parameters are
+    // declared at and the enclosing variables their bodies read
+    lambda_scopes: Vec<(i32, Vec<(String, ResolvedType)>)>,
+    // Imported module being resolved, None for the file being compiled. Its
+    // functions are registered as `<module>::<name>`.
+    module: Option<String>,
+    // Modules the current file imports, callable as `<module>::<name>(...)`
+    imports: Vec<String>,
+    // File the module was loaded from, which the spans of its errors index
+    file: Option<String>,
+    // Errors are collected here so resolution can continue past the first one
+    diagnostics: Diagnostics,
 }
 
 impl TypeResolver {
@@ -20,17 +42,54 @@ impl TypeResolver {
             symbol_table: HashMap::new(),
             function_table: HashMap::new(),
             locals: HashMap::new(),
+            shadowed: HashMap::new(),
             depth: 0,
             loop_depth: 0,
+            loop_labels: Vec::new(),
+            struct_table: HashMap::new(),
+            enum_table: HashMap::new(),
+            return_type: None,
+            lambda_scopes: Vec::new(),
+            module: None,
+            imports: Vec::new(),
+            file: None,
+            diagnostics: Diagnostics::new(),
         }
     }
 
+    /// Returns every error reported while resolving the program.
+    pub fn into_diagnostics(self) -> Diagnostics {
+        self.diagnostics
+    }
+
+    /// Resolves the following expressions as the top level of `module`, or of
+    /// the file being compiled when it is None, with access to `imports`.
+    /// Errors are reported against `file`, the one the module was loaded from.
+    pub fn enter_module(
+        &mut self,
+        module: Option<String>,
+        imports: Vec<String>,
+        file: Option<String>,
+    ) {
+        self.module = module;
+        self.imports = imports;
+        self.file = file;
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic.in_file(self.file.clone()));
+    }
+
     fn set_variable(&mut self, name: &str, ty: ResolvedType) {
-        self.symbol_table.insert(name.to_string(), ty);
-        self.locals
-            .entry(self.depth)
-            .or_default()
-            .push(name.to_string());
+        let outer = self.symbol_table.insert(name.to_string(), ty);
+        let locals = self.locals.entry(self.depth).or_default();
+        if let Some(outer) = outer.filter(|_| !locals.iter().any(|var| var == name)) {
+            self.shadowed
+                .entry(self.depth)
+                .or_default()
+                .push((name.to_string(), outer));
+        }
+        locals.push(name.to_string());
     }
 
     fn get_variable(&self, name: &str) -> Option<&ResolvedType> {
@@ -46,6 +105,54 @@ impl TypeResolver {
         self.function_table.get(name)
     }
 
+    // Name a function declared in the current module is registered under
+    fn qualify(&self, name: &str) -> String {
+        match &self.module {
+            Some(module) => format!("{}::{}", module, name),
+            None => name.to_string(),
+        }
+    }
+
+    // Name a call to `name` refers to, preferring the current module's own function
+    fn function_name(&self, name: &str) -> String {
+        let qualified = self.qualify(name);
+        if self.function_table.contains_key(&qualified) {
+            qualified
+        } else {
+            name.to_string()
+        }
+    }
+
+    // Depth of the innermost scope that declares `name`
+    fn variable_depth(&self, name: &str) -> Option<i32> {
+        self.locals
+            .iter()
+            .filter(|(_, vars)| vars.iter().any(|var| var == name))
+            .map(|(depth, _)| *depth)
+            .max()
+    }
+
+    /// Records `name` as a capture of every lambda being resolved that it is
+    /// declared outside of.
+    fn note_capture(&mut self, name: &str, ty: &ResolvedType) {
+        let Some(declared_at) = self.variable_depth(name) else {
+            return;
+        };
+        for (base_depth, captures) in &mut self.lambda_scopes {
+            if declared_at < *base_depth && !captures.iter().any(|(captured, _)| captured == name) {
+                captures.push((name.to_string(), ty.clone()));
+            }
+        }
+    }
+
+    // Whether `name` comes from outside the innermost lambda being resolved
+    fn is_captured(&self, name: &str) -> bool {
+        match (self.lambda_scopes.last(), self.variable_depth(name)) {
+            (Some((base_depth, _)), Some(declared_at)) => declared_at < *base_depth,
+            _ => false,
+        }
+    }
+
     fn incr_depth(&mut self) {
         self.depth += 1;
     }
@@ -56,58 +163,158 @@ impl TypeResolver {
                 self.symbol_table.remove(&var);
             }
         }
+        if let Some(outer) = self.shadowed.remove(&self.depth) {
+            self.symbol_table.extend(outer);
+        }
         self.depth -= 1;
     }
 
-    pub fn resolve_expression(
-        &mut self,
-        expr: &Expression,
-    ) -> Result<(TypedExpression, ResolvedType)> {
-        match expr {
-            Expression::Number(val) => {
-                Ok((
-                    TypedExpression::Number32 {
-                        value: *val,
-                        // span: expr.span()
-                    },
-                    ResolvedType::I32,
-                ))
-            }
-            Expression::Number64(val) => {
-                Ok((
-                    TypedExpression::Number64 {
-                        value: *val,
-                        // span: expr.span() - add error handling
-                    },
-                    ResolvedType::I64,
-                ))
-            }
-            Expression::String(val) => Ok((
-                TypedExpression::String { value: val.clone() },
+    pub fn resolve_expression(&mut self, expr: &Expression) -> (TypedExpression, ResolvedType) {
+        let span = expr.span;
+        match &expr.kind {
+            ExpressionKind::Number(val) => (
+                TypedExpression::Number32 { value: *val, span },
+                ResolvedType::I32,
+            ),
+            ExpressionKind::Number64(val) => (
+                TypedExpression::Number64 { value: *val, span },
+                ResolvedType::I64,
+            ),
+            ExpressionKind::Float(val) => (
+                TypedExpression::Float64 { value: *val, span },
+                ResolvedType::F64,
+            ),
+            ExpressionKind::String(val) => (
+                TypedExpression::String {
+                    value: val.clone(),
+                    span,
+                },
                 ResolvedType::String,
-            )),
-            Expression::Bool(val) => {
-                Ok((TypedExpression::Bool { value: *val }, ResolvedType::Bool))
+            ),
+            ExpressionKind::Bool(val) => (
+                TypedExpression::Bool { value: *val, span },
+                ResolvedType::Bool,
+            ),
+            ExpressionKind::Binary(left, op, right)
+                if (op == "==" || op == "!=")
+                    && (matches!(left.kind, ExpressionKind::Nil)
+                        || matches!(right.kind, ExpressionKind::Nil)) =>
+            {
+                self.resolve_nil_check(left, op, right, span)
             }
-            Expression::Binary(left, op, right) => {
-                let (lhs, lhs_ty) = self.resolve_expression(left)?;
-                let (rhs, rhs_ty) = self.resolve_expression(right)?;
+            ExpressionKind::Binary(left, op, right) => {
+                let (lhs, lhs_ty) = self.resolve_expression(left);
+                let (rhs, rhs_ty) = self.resolve_expression(right);
 
                 // Infer the result type of the binary operation
                 let result_type = match op.as_str() {
-                    // Comparison operators always return Bool
-                    "==" | "!=" | "<" | "<=" | ">" | ">=" => ResolvedType::Bool,
+                    // Don't guess a type from an operand that already failed to resolve
+                    _ if lhs_ty.is_error() || rhs_ty.is_error() => ResolvedType::Error,
+                    // An optional has to be checked against nil or unwrapped before use
+                    _ if lhs_ty.is_optional() || rhs_ty.is_optional() => {
+                        for (operand, ty) in [(left, &lhs_ty), (right, &rhs_ty)] {
+                            if ty.is_optional() {
+                                self.report(
+                                    Diagnostic::new(
+                                        format!(
+                                            "Operator {} is not defined for optional type {}",
+                                            op, ty
+                                        ),
+                                        operand.span,
+                                    )
+                                    .with_label("unwrap the value or compare it with nil"),
+                                );
+                            }
+                        }
+                        ResolvedType::Error
+                    }
+                    // Numbers compare with each other, strings and bools only for equality
+                    "==" | "!=" | "<" | "<=" | ">" | ">=" => match (&lhs_ty, &rhs_ty) {
+                        (lhs_ty, rhs_ty) if lhs_ty.is_numeric() && rhs_ty.is_numeric() => {
+                            ResolvedType::Bool
+                        }
+                        (ResolvedType::String, ResolvedType::String)
+                        | (ResolvedType::Bool, ResolvedType::Bool)
+                            if op == "==" || op == "!=" =>
+                        {
+                            ResolvedType::Bool
+                        }
+                        _ => {
+                            self.report_operand_mismatch(op, &lhs_ty, &rhs_ty, span);
+                            ResolvedType::Error
+                        }
+                    },
+                    // Logical operators only take bools
+                    "&&" | "||" => {
+                        for (operand, ty) in [(left, &lhs_ty), (right, &rhs_ty)] {
+                            if *ty != ResolvedType::Bool {
+                                self.report(
+                                    Diagnostic::new(
+                                        format!(
+                                            "Operator {} requires bool operands, got {}",
+                                            op, ty
+                                        ),
+                                        operand.span,
+                                    )
+                                    .with_label(format!("expected bool, found {}", ty)),
+                                );
+                            }
+                        }
+                        ResolvedType::Bool
+                    }
+                    // Exponentiation is only defined for integers
+                    "^" => match (&lhs_ty, &rhs_ty) {
+                        (ResolvedType::I32, ResolvedType::I32) => ResolvedType::I32,
+                        (
+                            ResolvedType::I32 | ResolvedType::I64,
+                            ResolvedType::I32 | ResolvedType::I64,
+                        ) => ResolvedType::I64,
+                        _ => {
+                            for (operand, ty) in [(left, &lhs_ty), (right, &rhs_ty)] {
+                                if !matches!(ty, ResolvedType::I32 | ResolvedType::I64) {
+                                    self.report(
+                                        Diagnostic::new(
+                                            format!(
+                                                "Operator ^ requires integer operands, got {}",
+                                                ty
+                                            ),
+                                            operand.span,
+                                        )
+                                        .with_label(format!("expected integer, found {}", ty)),
+                                    );
+                                }
+                            }
+                            ResolvedType::Error
+                        }
+                    },
                     // Arithmetic operators return numeric type
-                    "+" | "-" | "*" | "/" => match (&lhs_ty, &rhs_ty) {
+                    "+" | "-" | "*" | "/" | "%" => match (&lhs_ty, &rhs_ty) {
                         (ResolvedType::I32, ResolvedType::I32) => ResolvedType::I32,
                         (ResolvedType::I64, ResolvedType::I64) => ResolvedType::I64,
                         (ResolvedType::I32, ResolvedType::I64)
                         | (ResolvedType::I64, ResolvedType::I32) => ResolvedType::I64,
-                        _ => ResolvedType::Binary(
-                            Box::new(lhs_ty.clone()),
-                            op.to_string(),
-                            Box::new(rhs_ty.clone()),
-                        ),
+                        // Integers are promoted when mixed with floats
+                        (
+                            ResolvedType::F64,
+                            ResolvedType::I32 | ResolvedType::I64 | ResolvedType::F64,
+                        )
+                        | (ResolvedType::I32 | ResolvedType::I64, ResolvedType::F64) => {
+                            ResolvedType::F64
+                        }
+                        // + concatenates lists of the same element type
+                        (ResolvedType::List(lhs_inner), ResolvedType::List(rhs_inner))
+                            if op == "+" && lhs_inner == rhs_inner =>
+                        {
+                            lhs_ty.clone()
+                        }
+                        // + concatenates strings
+                        (ResolvedType::String, ResolvedType::String) if op == "+" => {
+                            ResolvedType::String
+                        }
+                        _ => {
+                            self.report_operand_mismatch(op, &lhs_ty, &rhs_ty, span);
+                            ResolvedType::Error
+                        }
                     },
                     // Default fallback
                     _ => ResolvedType::Binary(
@@ -117,84 +324,59 @@ impl TypeResolver {
                     ),
                 };
 
-                Ok((
+                (
                     TypedExpression::Binary {
                         left: Box::new(lhs),
                         op: op.to_string(),
                         right: Box::new(rhs),
+                        span,
                     },
                     result_type,
-                ))
+                )
             }
-            Expression::FuncStmt(name, args, return_type, body) => {
-                // Resolve argument types
-                let mut typed_args = Vec::new();
-                let mut resolved_arg_types = Vec::new();
-                for arg in args {
-                    match arg {
-                        Expression::FuncArg(arg_name, arg_type) => {
-                            let resolved_arg_type = self.resolve_type(arg_type);
-                            resolved_arg_types.push(resolved_arg_type.clone());
-                            typed_args.push((arg_name.clone(), resolved_arg_type));
-                        }
-                        _ => return Err(anyhow::anyhow!("Expected FuncArg in function arguments")),
-                    }
-                }
-
-                // Resolve return type
-                let resolved_return_type = self.resolve_type(return_type);
-
-                // Register function signature before resolving body (allows recursion)
-                self.set_function(name, resolved_arg_types, resolved_return_type.clone());
-
-                // Create a new scope for the function body
-                self.incr_depth();
-
-                // Add function parameters to the symbol table
-                for (arg_name, arg_type) in &typed_args {
-                    self.set_variable(arg_name, arg_type.clone());
+            ExpressionKind::FuncStmt(name, args, return_type, body) => {
+                let name = self.qualify(name);
+                self.resolve_function(&name, args, return_type, body, span)
+            }
+            ExpressionKind::PubStmt(func) => {
+                let (mut typed_func, ty) = self.resolve_expression(func);
+                if let TypedExpression::FuncStmt { is_pub, .. } = &mut typed_func {
+                    *is_pub = true;
                 }
-
-                // Resolve body (can now reference parameters)
-                let (typed_body, _body_ty) = self.resolve_expression(body)?;
-
-                // Exit the function scope
-                self.decr_depth();
-
-                let func_type = ResolvedType::Function(
-                    typed_args.iter().map(|(_, ty)| ty.clone()).collect(),
-                    Box::new(resolved_return_type.clone()),
+                (typed_func, ty)
+            }
+            ExpressionKind::Import(path) => {
+                // Imports are loaded by the module loader before resolution
+                self.report(
+                    Diagnostic::new(format!("Cannot import '{}' here", path), span)
+                        .with_label("imports must be at the top level of a file"),
                 );
-
-                Ok((
-                    TypedExpression::FuncStmt {
-                        name: name.clone(),
-                        args: typed_args,
-                        return_type: resolved_return_type,
-                        body: Box::new(typed_body),
+                (
+                    TypedExpression::BlockStmt {
+                        statements: vec![],
+                        span,
                     },
-                    func_type,
-                ))
+                    ResolvedType::Error,
+                )
             }
-            Expression::ExternFuncStmt(name, args, return_type) => {
+            ExpressionKind::ExternFuncStmt(name, args, return_type) => {
                 let mut typed_args = Vec::new();
                 let mut resolved_arg_types = Vec::new();
                 for arg in args {
-                    match arg {
-                        Expression::FuncArg(arg_name, arg_type) => {
-                            let resolved_arg_type = self.resolve_type(arg_type);
+                    match &arg.kind {
+                        ExpressionKind::FuncArg(arg_name, arg_type) => {
+                            let resolved_arg_type = self.resolve_type(arg_type, arg.span);
                             resolved_arg_types.push(resolved_arg_type.clone());
                             typed_args.push((arg_name.clone(), resolved_arg_type));
                         }
-                        _ => {
-                            return Err(anyhow::anyhow!(
-                                "Expected FuncArg in extern function arguments"
-                            ))
-                        }
+                        _ => self.report(Diagnostic::new(
+                            "Expected FuncArg in extern function arguments",
+                            arg.span,
+                        )),
                     }
                 }
 
-                let resolved_return_type = self.resolve_type(return_type);
+                let resolved_return_type = self.resolve_type(return_type, span);
                 self.set_function(name, resolved_arg_types, resolved_return_type.clone());
 
                 let func_type = ResolvedType::Function(
@@ -202,373 +384,2436 @@ impl TypeResolver {
                     Box::new(resolved_return_type.clone()),
                 );
 
-                Ok((
+                (
                     TypedExpression::ExternFuncStmt {
                         name: name.clone(),
                         args: typed_args,
                         return_type: resolved_return_type,
+                        span,
                     },
                     func_type,
-                ))
-            }
-            Expression::ExternModule(path) => {
-                Ok((
-                    TypedExpression::ExternModule {
-                        path: path.clone(),
-                    },
-                    ResolvedType::Void,
-                ))
+                )
             }
-            Expression::BlockStmt(statements) => {
+            ExpressionKind::ExternModule(path) => (
+                TypedExpression::ExternModule {
+                    path: path.clone(),
+                    span,
+                },
+                ResolvedType::Void,
+            ),
+            ExpressionKind::BlockStmt(statements) => {
                 self.incr_depth();
                 let mut typed_statements = Vec::new();
                 let mut last_type = ResolvedType::Void;
 
                 for stmt in statements {
-                    let (typed_stmt, stmt_ty) = self.resolve_expression(stmt)?;
+                    let (typed_stmt, stmt_ty) = self.resolve_expression(stmt);
                     typed_statements.push(typed_stmt);
                     last_type = stmt_ty;
                 }
 
                 self.decr_depth();
 
-                Ok((
+                (
                     TypedExpression::BlockStmt {
                         statements: typed_statements,
+                        span,
                     },
                     last_type,
-                ))
-            }
-            Expression::Variable(name) => {
-                let var_type = self
-                    .get_variable(name)
-                    .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", name))?
-                    .clone();
-                Ok((TypedExpression::Variable { name: name.clone() }, var_type))
-            }
-            Expression::Print(value) => {
-                let (typed_value, _) = self.resolve_expression(value)?;
-                Ok((
+                )
+            }
+            // A named function used as a value, e.g. passed to map
+            ExpressionKind::Variable(name)
+                if self.get_variable(name).is_none()
+                    && self.get_function(&self.function_name(name)).is_some() =>
+            {
+                let name = &self.function_name(name);
+                let (args, return_type) = self.get_function(name).cloned().unwrap();
+                let func_type = ResolvedType::Function(args.clone(), Box::new(return_type.clone()));
+                (
+                    TypedExpression::FunctionRef {
+                        name: name.clone(),
+                        args,
+                        return_type,
+                        span,
+                    },
+                    func_type,
+                )
+            }
+            ExpressionKind::Variable(name) => {
+                let var_type = match self.get_variable(name).cloned() {
+                    Some(var_type) => {
+                        self.note_capture(name, &var_type);
+                        var_type
+                    }
+                    None => {
+                        self.report(
+                            Diagnostic::new(format!("Undefined variable: {}", name), span)
+                                .with_label("not found in this scope"),
+                        );
+                        ResolvedType::Error
+                    }
+                };
+                (
+                    TypedExpression::Variable {
+                        name: name.clone(),
+                        span,
+                    },
+                    var_type,
+                )
+            }
+            ExpressionKind::Print(value) => {
+                let (typed_value, _) = self.resolve_expression(value);
+                (
                     TypedExpression::Print {
                         value: Box::new(typed_value),
+                        span,
                     },
                     ResolvedType::Void,
-                ))
+                )
             }
-            Expression::ReturnStmt(value) => {
-                let (typed_value, value_ty) = self.resolve_expression(value)?;
-                Ok((
+            ExpressionKind::ReturnStmt(value) => {
+                let return_type = self.return_type.clone().unwrap_or(ResolvedType::Void);
+                let (typed_value, value_ty) = self.resolve_with_expected(value, &return_type);
+                (
                     TypedExpression::ReturnStmt {
                         value: Box::new(typed_value),
+                        span,
                     },
                     value_ty,
-                ))
+                )
             }
-            Expression::BreakStmt => {
-                if self.loop_depth == 0 {
-                    return Err(anyhow::anyhow!(
-                        "break can only be used inside a loop"
-                    ));
-                }
-                Ok((TypedExpression::BreakStmt, ResolvedType::Void))
+            ExpressionKind::BreakStmt(label) => {
+                self.check_loop_control("break", label.as_ref(), span);
+                let label = label.clone();
+                (
+                    TypedExpression::BreakStmt { label, span },
+                    ResolvedType::Void,
+                )
             }
-            Expression::CallStmt(name, args) => {
-                let mut typed_args = Vec::new();
-                for arg in args {
-                    let (typed_arg, _) = self.resolve_expression(arg)?;
-                    typed_args.push(typed_arg);
-                }
-
-                let return_type = self
-                    .get_function(name)
-                    .map(|(_, ret)| ret.clone())
-                    .unwrap_or(ResolvedType::Void);
-
-                Ok((
-                    TypedExpression::CallStmt {
-                        callee: Box::new(TypedExpression::Variable { name: name.clone() }),
-                        args: typed_args,
+            ExpressionKind::ContinueStmt(label) => {
+                self.check_loop_control("continue", label.as_ref(), span);
+                let label = label.clone();
+                (
+                    TypedExpression::ContinueStmt { label, span },
+                    ResolvedType::Void,
+                )
+            }
+            ExpressionKind::Unary(op, operand) => {
+                let (typed_operand, operand_ty) = self.resolve_expression(operand);
+                let result_type = match op.as_str() {
+                    _ if operand_ty.is_error() => ResolvedType::Error,
+                    "!" if operand_ty == ResolvedType::Bool => ResolvedType::Bool,
+                    "-" if operand_ty.is_numeric() => operand_ty,
+                    _ => {
+                        let expected = if op == "!" { "bool" } else { "a number" };
+                        self.report(
+                            Diagnostic::new(
+                                format!("Cannot apply unary operator {} to {}", op, operand_ty),
+                                operand.span,
+                            )
+                            .with_label(format!("expected {}, found {}", expected, operand_ty)),
+                        );
+                        ResolvedType::Error
+                    }
+                };
+                (
+                    TypedExpression::Unary {
+                        op: op.to_string(),
+                        operand: Box::new(typed_operand),
+                        span,
                     },
-                    return_type,
-                ))
-            }
-            Expression::LetStmt(name, var_type, value) => {
-                // Resolve the value expression first
-                let (typed_value, value_type) = self.resolve_expression(value)?;
-
-                // Check if variable already exists - if so, this is reassignment, not declaration
-                if let Some(existing_type) = self.get_variable(name) {
-                    // This is reassignment (e.g., "x = 5" without "let")
-                    // Type check: new value must match existing variable's type
-                    if existing_type != &value_type {
-                        return Err(anyhow::anyhow!(
-                            "Cannot reassign variable '{}' of type {:?} to value of type {:?}",
-                            name,
-                            existing_type,
-                            value_type
-                        ));
+                    result_type,
+                )
+            }
+            ExpressionKind::CallStmt(name, args) if name == "unwrap" => {
+                self.check_collection_arity("unwrap", "value", &[], args, span);
+                match args.first() {
+                    Some(value) => {
+                        let resolved = self.resolve_expression(value);
+                        self.resolve_unwrap(resolved, span)
                     }
-
-                    // Return AssignStmt instead of LetStmt
-                    return Ok((
-                        TypedExpression::AssignStmt {
-                            name: name.clone(),
-                            value: Box::new(typed_value),
+                    None => (
+                        TypedExpression::BlockStmt {
+                            statements: vec![],
+                            span,
                         },
-                        ResolvedType::Void, // Assignments don't return values
-                    ));
-                }
-
-                // This is a new variable declaration
-                // Resolve the declared type
-                let declared_type = self.resolve_type(var_type);
-
-                // Type checking: ensure value matches declared type
-                // For Type::None, we allow type inference
-                if declared_type != ResolvedType::Void && declared_type != value_type {
-                    return Err(anyhow::anyhow!(
-                        "Type mismatch for variable '{}': declared as {:?}, but value is {:?}",
-                        name,
-                        declared_type,
-                        value_type
-                    ));
+                        ResolvedType::Error,
+                    ),
                 }
-
-                // Use declared type if not void, otherwise infer from value
-                let final_type = if declared_type == ResolvedType::Void {
-                    value_type
-                } else {
-                    declared_type
-                };
-
-                // Add to symbol table
-                self.set_variable(name, final_type.clone());
-
-                Ok((
-                    TypedExpression::LetStmt {
-                        name: name.clone(),
-                        var_type: Some(final_type.clone()),
-                        value: Box::new(typed_value),
-                    },
-                    final_type,
-                ))
             }
-            Expression::IfStmt(condition, then_branch, else_branch) => {
+            ExpressionKind::CallStmt(name, args) if Self::conversion_target(name).is_some() => {
+                self.resolve_conversion(name, args, span)
+            }
+            ExpressionKind::CallStmt(name, args)
+                if ListOp::from_name(name).is_some() || MapOp::from_name(name).is_some() =>
+            {
+                self.resolve_collection_call(name, args, span)
+            }
+            ExpressionKind::CallStmt(name, args)
+                if matches!(self.get_variable(name), Some(ResolvedType::Function(..))) =>
+            {
+                self.resolve_closure_call(name, args, span)
+            }
+            ExpressionKind::Lambda(params, return_type, body) => {
+                self.resolve_lambda(params, return_type, body, None, span)
+            }
+            ExpressionKind::CallStmt(name, args) => {
+                let name = self.function_name(name);
+                self.resolve_function_call(&name, args, span)
+            }
+            ExpressionKind::LetStmt(name, var_type, value) => {
+                self.resolve_let(name, var_type, value, false, span)
+            }
+            ExpressionKind::DeclareStmt(name, var_type, value) => {
+                self.resolve_let(name, var_type, value, true, span)
+            }
+            ExpressionKind::IfStmt(condition, then_branch, else_branch) => {
                 // Resolve condition - should be boolean
-                let (typed_condition, cond_type) = self.resolve_expression(condition)?;
+                let (typed_condition, cond_type) = self.resolve_expression(condition);
 
                 // Type check: condition should be boolean
-                if cond_type != ResolvedType::Bool {
-                    return Err(anyhow::anyhow!(
-                        "If condition must be boolean, got {:?}",
-                        cond_type
-                    ));
+                if !ResolvedType::Bool.is_compatible_with(&cond_type) {
+                    self.report(
+                        Diagnostic::new(
+                            format!("If condition must be boolean, got {}", cond_type),
+                            condition.span,
+                        )
+                        .with_label(format!("expected bool, found {}", cond_type)),
+                    );
                 }
 
                 // Resolve then branch
-                let (typed_then, _then_type) = self.resolve_expression(then_branch)?;
+                let (typed_then, _then_type) = self.resolve_expression(then_branch);
 
                 // Resolve else branch if it exists
                 // Note: Parser returns Box<Option<Expression>>, not Option<Box<Expression>>
                 let typed_else = if let Some(else_expr) = else_branch.as_ref().as_ref() {
-                    let (typed_else_branch, _else_type) = self.resolve_expression(else_expr)?;
+                    let (typed_else_branch, _else_type) = self.resolve_expression(else_expr);
                     Some(Box::new(typed_else_branch))
                 } else {
                     None
                 };
 
-                Ok((
+                (
                     TypedExpression::IfStmt {
                         condition: Box::new(typed_condition),
                         then_branch: Box::new(typed_then),
                         else_branch: typed_else,
+                        span,
                     },
                     ResolvedType::Void, // If statements don't return values
-                ))
+                )
             }
-            Expression::WhileStmt(condition, body) => {
-                // Resolve condition - should be boolean
-                let (typed_condition, cond_type) = self.resolve_expression(condition)?;
-
-                // Type check: condition should be boolean
-                if cond_type != ResolvedType::Bool {
-                    return Err(anyhow::anyhow!(
-                        "While condition must be boolean, got {:?}",
-                        cond_type
-                    ));
+            ExpressionKind::WhileStmt(..) | ExpressionKind::ForStmt(..) => {
+                self.resolve_loop(expr, None)
+            }
+            ExpressionKind::LabeledStmt(label, stmt) => {
+                if self.loop_labels.contains(label) {
+                    self.report(
+                        Diagnostic::new(format!("Loop label '{}' is already in use", label), span)
+                            .with_label("an enclosing loop has the same label"),
+                    );
                 }
-
-                // Resolve body
-                self.loop_depth += 1;
-                let (typed_body, _body_type) = self.resolve_expression(body)?;
-                self.loop_depth -= 1;
-
-                Ok((
-                    TypedExpression::WhileStmt {
-                        condition: Box::new(typed_condition),
-                        body: Box::new(typed_body),
-                    },
-                    ResolvedType::Void, // While loops don't return values
-                ))
+                self.resolve_loop(stmt, Some(label))
             }
-            Expression::Grouping(inner) => {
+            ExpressionKind::Grouping(inner) => {
                 // Resolve the inner expression and wrap it in Grouping
-                let (typed_inner, inner_type) = self.resolve_expression(inner)?;
-                Ok((
+                let (typed_inner, inner_type) = self.resolve_expression(inner);
+                (
                     TypedExpression::Grouping {
                         inner: Box::new(typed_inner),
+                        span,
                     },
                     inner_type, // Type passes through from inner expression
-                ))
+                )
             }
-            Expression::List(elements) => {
-                // If list is empty, we can't infer the type - error for now
+            ExpressionKind::List(elements) => {
+                // Without a declared type there is nothing to infer the element type from
                 if elements.is_empty() {
-                    return Err(anyhow::anyhow!("Empty lists are not yet supported"));
+                    self.report(
+                        Diagnostic::new("Cannot infer the element type of an empty list", span)
+                            .with_label("add a type annotation, e.g. `let xs: List<i32> = [];`"),
+                    );
+                    return (
+                        TypedExpression::List {
+                            elements: vec![],
+                            element_type: ResolvedType::Error,
+                            span,
+                        },
+                        ResolvedType::Error,
+                    );
                 }
 
                 // Resolve all elements and infer type from first element
                 let mut typed_elements = Vec::new();
-                let (first_typed, element_type) = self.resolve_expression(&elements[0])?;
+                let (first_typed, element_type) = self.resolve_expression(&elements[0]);
                 typed_elements.push(first_typed);
 
                 // Check that all elements have the same type
                 for elem in &elements[1..] {
-                    let (typed_elem, elem_ty) = self.resolve_expression(elem)?;
-                    if elem_ty != element_type {
-                        return Err(anyhow::anyhow!(
-                            "List elements must all have the same type. Expected {:?}, got {:?}",
-                            element_type,
-                            elem_ty
-                        ));
+                    let (typed_elem, elem_ty) = self.resolve_expression(elem);
+                    if !element_type.is_compatible_with(&elem_ty) {
+                        self.report(
+                            Diagnostic::new(
+                                format!(
+                                    "List elements must all have the same type. Expected {}, got {}",
+                                    element_type, elem_ty
+                                ),
+                                elem.span,
+                            )
+                            .with_label(format!("expected {}, found {}", element_type, elem_ty)),
+                        );
                     }
                     typed_elements.push(typed_elem);
                 }
 
                 let list_type = ResolvedType::List(Box::new(element_type.clone()));
 
-                Ok((
+                (
                     TypedExpression::List {
                         elements: typed_elements,
                         element_type: element_type.clone(),
+                        span,
                     },
                     list_type,
-                ))
+                )
+            }
+            ExpressionKind::Map(entries) => {
+                // As with lists, an empty map needs a declared type
+                if entries.is_empty() {
+                    self.report(
+                        Diagnostic::new(
+                            "Cannot infer the key and value types of an empty map",
+                            span,
+                        )
+                        .with_label("add a type annotation, e.g. `let m: Map<string, i32> = {};`"),
+                    );
+                    return (
+                        TypedExpression::Map {
+                            entries: vec![],
+                            key_type: ResolvedType::Error,
+                            value_type: ResolvedType::Error,
+                            span,
+                        },
+                        ResolvedType::Error,
+                    );
+                }
+
+                // Infer the key and value types from the first entry
+                let mut typed_entries = Vec::new();
+                let mut entry_types: Option<(ResolvedType, ResolvedType)> = None;
+                for (key, value) in entries {
+                    let (typed_key, key_ty) = self.resolve_expression(key);
+                    let (typed_value, value_ty) = self.resolve_expression(value);
+                    match &entry_types {
+                        None => {
+                            self.check_map_key_type(&key_ty, key.span);
+                            entry_types = Some((key_ty, value_ty));
+                        }
+                        Some((key_type, value_type)) => {
+                            self.check_map_entry_type("keys", key_type, &key_ty, key.span);
+                            self.check_map_entry_type("values", value_type, &value_ty, value.span);
+                        }
+                    }
+                    typed_entries.push((typed_key, typed_value));
+                }
+
+                let (key_type, value_type) =
+                    entry_types.unwrap_or((ResolvedType::Error, ResolvedType::Error));
+                let map_type =
+                    ResolvedType::Map(Box::new(key_type.clone()), Box::new(value_type.clone()));
+                (
+                    TypedExpression::Map {
+                        entries: typed_entries,
+                        key_type,
+                        value_type,
+                        span,
+                    },
+                    map_type,
+                )
             }
-            Expression::ListIndex(list, index) => {
+            ExpressionKind::ListIndex(list, index) => {
                 // Resolve list expression - must be a List type
-                let (typed_list, list_type) = self.resolve_expression(list)?;
+                let (typed_list, list_type) = self.resolve_expression(list);
+
+                // Indexing a map looks up a key instead
+                if let ResolvedType::Map(key_type, value_type) = list_type {
+                    let typed_key = self.resolve_map_key(index, &key_type);
+                    return (
+                        TypedExpression::MapIndex {
+                            map: Box::new(typed_list),
+                            key: Box::new(typed_key),
+                            span,
+                        },
+                        *value_type,
+                    );
+                }
 
                 // Extract element type from list
                 let element_type = match list_type {
                     ResolvedType::List(inner) => *inner,
+                    ResolvedType::Error => ResolvedType::Error,
                     _ => {
-                        return Err(anyhow::anyhow!(
-                            "Cannot index into non-list type {:?}",
-                            list_type
-                        ))
+                        self.report(
+                            Diagnostic::new(
+                                format!("Cannot index into non-list type {}", list_type),
+                                list.span,
+                            )
+                            .with_label(format!("this is of type {}", list_type)),
+                        );
+                        ResolvedType::Error
                     }
                 };
 
                 // Resolve index expression - must be i32
-                let (typed_index, index_type) = self.resolve_expression(index)?;
-                if index_type != ResolvedType::I32 {
-                    return Err(anyhow::anyhow!(
-                        "List index must be i32, got {:?}",
-                        index_type
-                    ));
-                }
+                let (typed_index, index_type) = self.resolve_expression(index);
+                self.check_index_type(&index_type, index.span);
 
-                Ok((
+                (
                     TypedExpression::ListIndex {
                         list: Box::new(typed_list),
                         index: Box::new(typed_index),
+                        span,
                     },
                     element_type, // Return element type
-                ))
+                )
             }
-            Expression::ListAssign(name, index, value) => {
+            ExpressionKind::ListAssign(name, index, value) => {
                 // Look up the list variable
-                let list_type = self
-                    .get_variable(name)
-                    .ok_or_else(|| anyhow::anyhow!("Undefined variable: {}", name))?
-                    .clone();
+                let list_type = match self.get_variable(name).cloned() {
+                    Some(list_type) => {
+                        self.note_capture(name, &list_type);
+                        list_type
+                    }
+                    None => {
+                        self.report(
+                            Diagnostic::new(format!("Undefined variable: {}", name), span)
+                                .with_label("not found in this scope"),
+                        );
+                        ResolvedType::Error
+                    }
+                };
+
+                // Assigning to a map inserts or overwrites the key
+                if let ResolvedType::Map(key_type, value_type) = list_type {
+                    let typed_key = self.resolve_map_key(index, &key_type);
+                    let (typed_value, found) = self.resolve_with_expected(value, &value_type);
+                    if !value_type.is_compatible_with(&found) {
+                        self.report(
+                            Diagnostic::new(
+                                format!(
+                                    "Cannot assign value of type {} to map of {}",
+                                    found, value_type
+                                ),
+                                value.span,
+                            )
+                            .with_label(format!("expected {}, found {}", value_type, found)),
+                        );
+                    }
+                    return (
+                        TypedExpression::MapAssign {
+                            name: name.clone(),
+                            key: Box::new(typed_key),
+                            value: Box::new(typed_value),
+                            span,
+                        },
+                        ResolvedType::Void,
+                    );
+                }
 
                 // Extract element type from list
                 let element_type = match list_type {
                     ResolvedType::List(inner) => *inner,
+                    ResolvedType::Error => ResolvedType::Error,
                     _ => {
-                        return Err(anyhow::anyhow!(
-                            "Cannot index into non-list type {:?}",
-                            list_type
-                        ))
+                        self.report(
+                            Diagnostic::new(
+                                format!("Cannot index into non-list type {}", list_type),
+                                span,
+                            )
+                            .with_label(format!("'{}' is of type {}", name, list_type)),
+                        );
+                        ResolvedType::Error
                     }
                 };
 
                 // Resolve index expression - must be i32
-                let (typed_index, index_type) = self.resolve_expression(index)?;
-                if index_type != ResolvedType::I32 {
-                    return Err(anyhow::anyhow!(
-                        "List index must be i32, got {:?}",
-                        index_type
-                    ));
-                }
+                let (typed_index, index_type) = self.resolve_expression(index);
+                self.check_index_type(&index_type, index.span);
 
                 // Resolve value expression - must match element type
-                let (typed_value, value_type) = self.resolve_expression(value)?;
-                if value_type != element_type {
-                    return Err(anyhow::anyhow!(
-                        "Cannot assign value of type {:?} to list of type {:?}",
-                        value_type,
-                        element_type
-                    ));
+                let (typed_value, value_type) = self.resolve_expression(value);
+                if !element_type.is_compatible_with(&value_type) {
+                    self.report(
+                        Diagnostic::new(
+                            format!(
+                                "Cannot assign value of type {} to list of type {}",
+                                value_type, element_type
+                            ),
+                            value.span,
+                        )
+                        .with_label(format!("expected {}, found {}", element_type, value_type)),
+                    );
                 }
 
-                Ok((
+                (
                     TypedExpression::ListAssign {
                         name: name.clone(),
                         index: Box::new(typed_index),
                         value: Box::new(typed_value),
+                        span,
                     },
                     ResolvedType::Void, // Assignments don't return values
-                ))
+                )
             }
-            Expression::Len(value) => {
+            ExpressionKind::Len(value) => {
                 // Resolve the value expression - must be a List type
-                let (typed_value, value_type) = self.resolve_expression(value)?;
+                let (typed_value, value_type) = self.resolve_expression(value);
 
-                // Check that value is a list
+                // Check that value is a list or map
                 match value_type {
-                    ResolvedType::List(_) => {}
-                    _ => {
-                        return Err(anyhow::anyhow!(
-                            "len() requires a list argument, got {:?}",
-                            value_type
-                        ))
-                    }
+                    ResolvedType::List(_) | ResolvedType::Map(..) | ResolvedType::Error => {}
+                    _ => self.report(
+                        Diagnostic::new(
+                            format!("len() requires a list or map argument, got {}", value_type),
+                            value.span,
+                        )
+                        .with_label(format!("expected a list or map, found {}", value_type)),
+                    ),
                 };
 
-                Ok((
+                (
                     TypedExpression::Len {
                         value: Box::new(typed_value),
+                        span,
                     },
                     ResolvedType::I32, // len() returns i32
-                ))
+                )
+            }
+            ExpressionKind::StructStmt(name, fields) => {
+                self.resolve_struct_stmt(name, fields, span)
+            }
+            ExpressionKind::StructLiteral(name, fields) => {
+                self.resolve_struct_literal(name, fields, span)
+            }
+            ExpressionKind::ImplStmt(self_type, methods) => {
+                self.resolve_impl_stmt(self_type, methods, span)
+            }
+            ExpressionKind::EnumStmt(name, variants) => {
+                self.resolve_enum_stmt(name, variants, span)
+            }
+            ExpressionKind::EnumValue(module, function, args)
+                if !self.enum_table.contains_key(module) && self.imports.contains(module) =>
+            {
+                self.resolve_module_call(module, function, args, span)
+            }
+            ExpressionKind::EnumValue(enum_name, variant, values) => {
+                self.resolve_enum_value(enum_name, variant, values, span)
+            }
+            ExpressionKind::MatchStmt(value, arms) => self.resolve_match(value, arms, span),
+            ExpressionKind::MatchArm(..) => {
+                self.report(
+                    Diagnostic::new("Match arm outside of a match", span)
+                        .with_label("arms can only appear inside `match (...) { }`"),
+                );
+                (
+                    TypedExpression::BlockStmt {
+                        statements: vec![],
+                        span,
+                    },
+                    ResolvedType::Error,
+                )
+            }
+            ExpressionKind::MethodCall(receiver, method, args) => {
+                self.resolve_method_call(receiver, method, args, span)
+            }
+            ExpressionKind::FieldAccess(value, field) => {
+                let (typed_value, value_type) = self.resolve_expression(value);
+                let (index, field_type) = self.resolve_field(&value_type, field, span);
+                (
+                    TypedExpression::FieldAccess {
+                        value: Box::new(typed_value),
+                        field: field.clone(),
+                        index,
+                        span,
+                    },
+                    field_type,
+                )
+            }
+            ExpressionKind::FieldAssign(target, field, value) => {
+                let (typed_target, target_type) = self.resolve_expression(target);
+                if !Self::is_assignable(&typed_target) {
+                    self.report(
+                        Diagnostic::new(
+                            "Cannot assign to a field of a temporary value",
+                            target.span,
+                        )
+                        .with_label("store the value in a variable first"),
+                    );
+                }
+                let (index, field_type) = self.resolve_field(&target_type, field, span);
+                let (typed_value, value_type) = self.resolve_with_expected(value, &field_type);
+                if !field_type.is_compatible_with(&value_type) {
+                    self.report(
+                        Diagnostic::new(
+                            format!(
+                                "Cannot assign value of type {} to field '{}' of type {}",
+                                value_type, field, field_type
+                            ),
+                            value.span,
+                        )
+                        .with_label(format!("expected {}, found {}", field_type, value_type)),
+                    );
+                }
+                (
+                    TypedExpression::FieldAssign {
+                        target: Box::new(typed_target),
+                        field: field.clone(),
+                        index,
+                        value: Box::new(typed_value),
+                        span,
+                    },
+                    ResolvedType::Void,
+                )
+            }
+            ExpressionKind::IterItems(iterable) => {
+                let (typed_iterable, iterable_type) = self.resolve_expression(iterable);
+                // Maps are walked by key and strings by character
+                let item_type = match &iterable_type {
+                    ResolvedType::List(inner) => (**inner).clone(),
+                    ResolvedType::Map(key, _) => (**key).clone(),
+                    ResolvedType::String => ResolvedType::String,
+                    ResolvedType::Error => ResolvedType::Error,
+                    other => {
+                        self.report(
+                            Diagnostic::new(
+                                format!("Cannot iterate over {}", other),
+                                iterable.span,
+                            )
+                            .with_label(format!("expected a list, map or string, found {}", other)),
+                        );
+                        ResolvedType::Error
+                    }
+                };
+                let items_type = match item_type {
+                    ResolvedType::Error => ResolvedType::Error,
+                    item_type => ResolvedType::List(Box::new(item_type)),
+                };
+                (
+                    TypedExpression::IterItems {
+                        value: Box::new(typed_iterable),
+                        span,
+                    },
+                    items_type,
+                )
+            }
+            ExpressionKind::ListSlice(list, start, end) => {
+                let (typed_list, list_type) = self.resolve_expression(list);
+                if !matches!(list_type, ResolvedType::List(_) | ResolvedType::Error) {
+                    self.report(
+                        Diagnostic::new(
+                            format!("Cannot slice non-list type {}", list_type),
+                            list.span,
+                        )
+                        .with_label(format!("expected a list, found {}", list_type)),
+                    );
+                }
+
+                let mut resolve_bound = |bound: &Option<Box<Expression>>| {
+                    bound.as_ref().map(|bound| {
+                        let (typed_bound, bound_type) = self.resolve_expression(bound);
+                        self.check_index_type(&bound_type, bound.span);
+                        Box::new(typed_bound)
+                    })
+                };
+                let typed_start = resolve_bound(start);
+                let typed_end = resolve_bound(end);
+
+                (
+                    TypedExpression::ListSlice {
+                        list: Box::new(typed_list),
+                        start: typed_start,
+                        end: typed_end,
+                        span,
+                    },
+                    list_type, // A slice is a new list of the same type
+                )
+            }
+            ExpressionKind::Nil => {
+                self.report(
+                    Diagnostic::new("Cannot infer the type of nil", span)
+                        .with_label("nil needs an optional type, e.g. `let x: i32? = nil`"),
+                );
+                (
+                    TypedExpression::BlockStmt {
+                        statements: vec![],
+                        span,
+                    },
+                    ResolvedType::Error,
+                )
             }
             // ... other cases
-            _ => unreachable!("Not implemented for expression {:?}", expr),
+            _ => {
+                self.report(Diagnostic::new(
+                    format!("Unsupported expression {:?}", expr.kind),
+                    span,
+                ));
+                (
+                    TypedExpression::BlockStmt {
+                        statements: vec![],
+                        span,
+                    },
+                    ResolvedType::Error,
+                )
+            }
         }
     }
 
-    fn resolve_type(&self, ty: &parser::Type) -> ResolvedType {
-        use parser::Type;
-        match ty {
-            Type::None => ResolvedType::Void,
-            Type::i32 => ResolvedType::I32,
-            Type::i64 => ResolvedType::I64,
-            Type::String => ResolvedType::String,
-            Type::Bool => ResolvedType::Bool,
-            Type::List(inner) => ResolvedType::List(Box::new(self.resolve_type(inner))),
+    /// Resolves an expression whose type is known from context, e.g. a `let`
+    /// annotation or a parameter type. An empty list or map has no elements to
+    /// infer its type from, so it takes it from `expected`. Where an optional
+    /// is expected, nil and values of the inner type become optionals.
+    fn resolve_with_expected(
+        &mut self,
+        expr: &Expression,
+        expected: &ResolvedType,
+    ) -> (TypedExpression, ResolvedType) {
+        match (&expr.kind, expected) {
+            (ExpressionKind::List(elements), ResolvedType::List(element_type))
+                if elements.is_empty() =>
+            {
+                (
+                    TypedExpression::List {
+                        elements: vec![],
+                        element_type: (**element_type).clone(),
+                        span: expr.span,
+                    },
+                    expected.clone(),
+                )
+            }
+            (ExpressionKind::Map(entries), ResolvedType::Map(key_type, value_type))
+                if entries.is_empty() =>
+            {
+                self.check_map_key_type(key_type, expr.span);
+                (
+                    TypedExpression::Map {
+                        entries: vec![],
+                        key_type: (**key_type).clone(),
+                        value_type: (**value_type).clone(),
+                        span: expr.span,
+                    },
+                    expected.clone(),
+                )
+            }
+            (ExpressionKind::Nil, ResolvedType::Optional(value_type)) => (
+                TypedExpression::OptionalValue {
+                    value: None,
+                    value_type: (**value_type).clone(),
+                    span: expr.span,
+                },
+                expected.clone(),
+            ),
+            (ExpressionKind::Nil, _) if *expected != ResolvedType::Void && !expected.is_error() => {
+                self.report(
+                    Diagnostic::new(
+                        format!("Cannot assign nil to non-optional type {}", expected),
+                        expr.span,
+                    )
+                    .with_label(format!("declare the type as {}? to allow nil", expected)),
+                );
+                (
+                    TypedExpression::BlockStmt {
+                        statements: vec![],
+                        span: expr.span,
+                    },
+                    ResolvedType::Error,
+                )
+            }
+            (
+                ExpressionKind::Lambda(params, return_type, body),
+                ResolvedType::Function(arg_types, expected_return),
+            ) => self.resolve_lambda(
+                params,
+                return_type,
+                body,
+                Some((arg_types, expected_return)),
+                expr.span,
+            ),
+            (_, ResolvedType::Optional(value_type)) => {
+                let (typed_value, found) = self.resolve_with_expected(expr, value_type);
+                // Anything else, e.g. another optional, is left for the caller to check
+                if found != **value_type {
+                    return (typed_value, found);
+                }
+                (
+                    TypedExpression::OptionalValue {
+                        value: Some(Box::new(typed_value)),
+                        value_type: found,
+                        span: expr.span,
+                    },
+                    expected.clone(),
+                )
+            }
+            _ => self.resolve_expression(expr),
+        }
+    }
+
+    fn report_operand_mismatch(
+        &mut self,
+        op: &str,
+        lhs_ty: &ResolvedType,
+        rhs_ty: &ResolvedType,
+        span: parser::Span,
+    ) {
+        self.report(
+            Diagnostic::new(
+                format!(
+                    "Operator {} is not defined for {} and {}",
+                    op, lhs_ty, rhs_ty
+                ),
+                span,
+            )
+            .with_label(format!("{} {} {}", lhs_ty, op, rhs_ty)),
+        );
+    }
+
+    /// Resolves `x == nil` or `x != nil`, where one side is the nil literal.
+    fn resolve_nil_check(
+        &mut self,
+        left: &Expression,
+        op: &str,
+        right: &Expression,
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let value = match left.kind {
+            ExpressionKind::Nil => right,
+            _ => left,
+        };
+        let (typed_value, value_type) = self.resolve_expression(value);
+        if !value_type.is_optional() && !value_type.is_error() {
+            self.report(
+                Diagnostic::new(
+                    format!("Cannot compare {} with nil", value_type),
+                    value.span,
+                )
+                .with_label("only optional values can be nil"),
+            );
+        }
+        (
+            TypedExpression::NilCheck {
+                value: Box::new(typed_value),
+                is_nil: op == "==",
+                span,
+            },
+            ResolvedType::Bool,
+        )
+    }
+
+    /// `unwrap(x)` and `x.unwrap()` turn a `T?` into a `T`, panicking at runtime on nil.
+    fn resolve_unwrap(
+        &mut self,
+        (typed_value, value_type): (TypedExpression, ResolvedType),
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let inner = match value_type {
+            ResolvedType::Optional(inner) => *inner,
+            ResolvedType::Error => ResolvedType::Error,
+            other => {
+                self.report(
+                    Diagnostic::new(
+                        format!("unwrap() expects an optional value, got {}", other),
+                        typed_value.span(),
+                    )
+                    .with_label(format!("expected an optional, found {}", other)),
+                );
+                ResolvedType::Error
+            }
+        };
+        (
+            TypedExpression::Unwrap {
+                value: Box::new(typed_value),
+                span,
+            },
+            inner,
+        )
+    }
+
+    /// Resolves `let name = value`, which assigns to `name` when it is already
+    /// a variable unless `declare` is set, in which case it always declares a
+    /// new one shadowing any outer variable of the same name.
+    fn resolve_let(
+        &mut self,
+        name: &String,
+        var_type: &parser::Type,
+        value: &Expression,
+        declare: bool,
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let existing_type = if declare {
+            None
+        } else {
+            self.get_variable(name).cloned()
+        };
+        let declared_type = self.resolve_type(var_type, span);
+
+        // Resolve the value expression first, against the variable's type so
+        // an empty list knows its element type
+        let expected_type = existing_type.clone().unwrap_or(declared_type.clone());
+        let (typed_value, value_type) = self.resolve_with_expected(value, &expected_type);
+
+        // Check if variable already exists - if so, this is reassignment, not declaration
+        if let Some(existing_type) = existing_type {
+            // This is reassignment (e.g., "x = 5" without "let")
+            if self.is_captured(name) {
+                self.report(
+                    Diagnostic::new(
+                        format!("Cannot assign to captured variable '{}'", name),
+                        span,
+                    )
+                    .with_label("closures capture variables by value"),
+                );
+            }
+            // Type check: new value must match existing variable's type
+            if !existing_type.is_compatible_with(&value_type) {
+                self.report(
+                    Diagnostic::new(
+                        format!(
+                            "Cannot reassign variable '{}' of type {} to value of type {}",
+                            name, existing_type, value_type
+                        ),
+                        value.span,
+                    )
+                    .with_label(format!("expected {}, found {}", existing_type, value_type)),
+                );
+            }
+
+            // Return AssignStmt instead of LetStmt
+            return (
+                TypedExpression::AssignStmt {
+                    name: name.clone(),
+                    value: Box::new(typed_value),
+                    span,
+                },
+                ResolvedType::Void, // Assignments don't return values
+            );
+        }
+
+        // This is a new variable declaration
+        // Type checking: ensure value matches declared type
+        // For Type::None, we allow type inference
+        if declared_type != ResolvedType::Void && !declared_type.is_compatible_with(&value_type) {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "Type mismatch for variable '{}': declared as {}, but value is {}",
+                        name, declared_type, value_type
+                    ),
+                    value.span,
+                )
+                .with_label(format!("expected {}, found {}", declared_type, value_type)),
+            );
+        }
+
+        // Use declared type if not void, otherwise infer from value
+        let final_type = if declared_type == ResolvedType::Void {
+            value_type
+        } else {
+            declared_type
+        };
+
+        // Add to symbol table
+        self.set_variable(name, final_type.clone());
+
+        (
+            TypedExpression::LetStmt {
+                name: name.clone(),
+                var_type: Some(final_type.clone()),
+                value: Box::new(typed_value),
+                span,
+            },
+            final_type,
+        )
+    }
+
+    /// Resolves a while or for loop, `label` is set when the loop is labeled.
+    /// A for loop is lowered to its init followed by a while loop that runs
+    /// the step at the end of every iteration.
+    fn resolve_loop(
+        &mut self,
+        expr: &Expression,
+        label: Option<&String>,
+    ) -> (TypedExpression, ResolvedType) {
+        let span = expr.span;
+        let (init, condition, step, body) = match &expr.kind {
+            ExpressionKind::WhileStmt(condition, body) => (None, condition, None, body),
+            ExpressionKind::ForStmt(init, condition, step, body) => {
+                (Some(init), condition, Some(step), body)
+            }
+            _ => return self.resolve_expression(expr),
+        };
+
+        // The loop variable of a for loop is scoped to the loop, like a block,
+        // and is always a new variable even when an outer one has its name
+        self.incr_depth();
+        let typed_init = init.map(|init| match &init.kind {
+            ExpressionKind::LetStmt(name, var_type, value) => {
+                self.resolve_let(name, var_type, value, true, init.span).0
+            }
+            _ => self.resolve_expression(init).0,
+        });
+
+        let (typed_condition, cond_type) = self.resolve_expression(condition);
+        if !ResolvedType::Bool.is_compatible_with(&cond_type) {
+            let kind = if init.is_some() { "For" } else { "While" };
+            self.report(
+                Diagnostic::new(
+                    format!("{} condition must be boolean, got {}", kind, cond_type),
+                    condition.span,
+                )
+                .with_label(format!("expected bool, found {}", cond_type)),
+            );
+        }
+
+        self.loop_depth += 1;
+        if let Some(label) = label {
+            self.loop_labels.push(label.clone());
+        }
+        let (typed_body, _body_type) = self.resolve_expression(body);
+        if label.is_some() {
+            self.loop_labels.pop();
+        }
+        self.loop_depth -= 1;
+        let typed_step = step.map(|step| Box::new(self.resolve_expression(step).0));
+        self.decr_depth();
+
+        let typed_loop = TypedExpression::WhileStmt {
+            condition: Box::new(typed_condition),
+            body: Box::new(typed_body),
+            step: typed_step,
+            label: label.cloned(),
+            span,
+        };
+        let typed = match typed_init {
+            Some(init) => TypedExpression::BlockStmt {
+                statements: vec![init, typed_loop],
+                span,
+            },
+            None => typed_loop,
+        };
+        (typed, ResolvedType::Void) // Loops don't return values
+    }
+
+    /// Checks a `break` or `continue` is inside a loop, and that its label
+    /// names one of the loops it is in.
+    fn check_loop_control(&mut self, keyword: &str, label: Option<&String>, span: parser::Span) {
+        if self.loop_depth == 0 {
+            self.report(
+                Diagnostic::new(format!("{} can only be used inside a loop", keyword), span)
+                    .with_label(format!("cannot `{}` outside of a loop", keyword)),
+            );
+        } else if let Some(label) = label.filter(|l| !self.loop_labels.contains(l)) {
+            self.report(
+                Diagnostic::new(format!("Undefined loop label: {}", label), span)
+                    .with_label(format!("no enclosing loop is labeled `{}`", label)),
+            );
+        }
+    }
+
+    fn resolve_struct_stmt(
+        &mut self,
+        name: &str,
+        fields: &[(String, parser::Type)],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        if self.struct_table.contains_key(name) || self.enum_table.contains_key(name) {
+            self.report(
+                Diagnostic::new(format!("Struct '{}' is already defined", name), span)
+                    .with_label("a type with this name was declared earlier"),
+            );
+        }
+        // Registered up front so fields can refer to the struct through a list
+        self.struct_table.insert(name.to_string(), vec![]);
+
+        let mut typed_fields: Vec<(String, ResolvedType)> = Vec::new();
+        for (field_name, field_type) in fields {
+            if typed_fields
+                .iter()
+                .any(|(existing, _)| existing == field_name)
+            {
+                self.report(
+                    Diagnostic::new(
+                        format!(
+                            "Field '{}' is declared more than once in struct '{}'",
+                            field_name, name
+                        ),
+                        span,
+                    )
+                    .with_label("duplicate field"),
+                );
+                continue;
+            }
+            let resolved = match field_type {
+                // A struct holding itself by value would have no finite size
+                parser::Type::Struct(inner) if inner == name => {
+                    self.report(
+                        Diagnostic::new(format!("Struct '{}' cannot contain itself", name), span)
+                            .with_label(format!("field '{}' has type {}", field_name, name)),
+                    );
+                    ResolvedType::Error
+                }
+                _ => self.resolve_type(field_type, span),
+            };
+            typed_fields.push((field_name.clone(), resolved));
+        }
+        self.struct_table
+            .insert(name.to_string(), typed_fields.clone());
+
+        (
+            TypedExpression::StructStmt {
+                name: name.to_string(),
+                fields: typed_fields,
+                span,
+            },
+            ResolvedType::Void,
+        )
+    }
+
+    fn resolve_struct_literal(
+        &mut self,
+        name: &str,
+        fields: &[(String, Expression)],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let Some(declared) = self.struct_table.get(name).cloned() else {
+            self.report(
+                Diagnostic::new(format!("Undefined struct: {}", name), span)
+                    .with_label("no struct with this name is declared"),
+            );
+            return (
+                TypedExpression::BlockStmt {
+                    statements: vec![],
+                    span,
+                },
+                ResolvedType::Error,
+            );
+        };
+
+        let mut values: Vec<Option<TypedExpression>> = vec![None; declared.len()];
+        for (field_name, value) in fields {
+            let Some(index) = declared.iter().position(|(f, _)| f == field_name) else {
+                self.report(
+                    Diagnostic::new(
+                        format!("Struct '{}' has no field '{}'", name, field_name),
+                        value.span,
+                    )
+                    .with_label("unknown field"),
+                );
+                continue;
+            };
+            let field_type = &declared[index].1;
+            let (typed_value, value_type) = self.resolve_with_expected(value, field_type);
+            if !field_type.is_compatible_with(&value_type) {
+                self.report(
+                    Diagnostic::new(
+                        format!(
+                            "Field '{}' of struct '{}' expects {}, got {}",
+                            field_name, name, field_type, value_type
+                        ),
+                        value.span,
+                    )
+                    .with_label(format!("expected {}, found {}", field_type, value_type)),
+                );
+            }
+            if values[index].replace(typed_value).is_some() {
+                self.report(
+                    Diagnostic::new(
+                        format!("Field '{}' is set more than once", field_name),
+                        value.span,
+                    )
+                    .with_label("duplicate field"),
+                );
+            }
+        }
+
+        let missing: Vec<&str> = declared
+            .iter()
+            .zip(&values)
+            .filter(|(_, value)| value.is_none())
+            .map(|((field_name, _), _)| field_name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "Missing fields in struct '{}': {}",
+                        name,
+                        missing.join(", ")
+                    ),
+                    span,
+                )
+                .with_label("every field must be given a value"),
+            );
+            return (
+                TypedExpression::BlockStmt {
+                    statements: vec![],
+                    span,
+                },
+                ResolvedType::Error,
+            );
+        }
+
+        (
+            TypedExpression::StructLiteral {
+                name: name.to_string(),
+                fields: values.into_iter().flatten().collect(),
+                span,
+            },
+            ResolvedType::Struct(name.to_string()),
+        )
+    }
+
+    fn resolve_enum_stmt(
+        &mut self,
+        name: &str,
+        variants: &[(String, Vec<parser::Type>)],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        if self.enum_table.contains_key(name) || self.struct_table.contains_key(name) {
+            self.report(
+                Diagnostic::new(format!("Enum '{}' is already defined", name), span)
+                    .with_label("a type with this name was declared earlier"),
+            );
+        }
+        // Registered up front so payloads can refer to the enum through a list
+        self.enum_table.insert(name.to_string(), vec![]);
+
+        let mut typed_variants: Vec<(String, Vec<ResolvedType>)> = Vec::new();
+        for (variant, payload) in variants {
+            if typed_variants
+                .iter()
+                .any(|(existing, _)| existing == variant)
+            {
+                self.report(
+                    Diagnostic::new(
+                        format!(
+                            "Variant '{}' is declared more than once in enum '{}'",
+                            variant, name
+                        ),
+                        span,
+                    )
+                    .with_label("duplicate variant"),
+                );
+                continue;
+            }
+            let mut typed_payload = Vec::new();
+            for ty in payload {
+                let resolved = match ty {
+                    // An enum holding itself by value would have no finite size
+                    parser::Type::Struct(inner) if inner == name => {
+                        self.report(
+                            Diagnostic::new(format!("Enum '{}' cannot contain itself", name), span)
+                                .with_label(format!("variant '{}' holds a {}", variant, name)),
+                        );
+                        ResolvedType::Error
+                    }
+                    _ => self.resolve_type(ty, span),
+                };
+                typed_payload.push(resolved);
+            }
+            typed_variants.push((variant.clone(), typed_payload));
+        }
+        self.enum_table
+            .insert(name.to_string(), typed_variants.clone());
+
+        (
+            TypedExpression::EnumStmt {
+                name: name.to_string(),
+                variants: typed_variants,
+                span,
+            },
+            ResolvedType::Void,
+        )
+    }
+
+    fn resolve_enum_value(
+        &mut self,
+        enum_name: &str,
+        variant: &str,
+        values: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let error = (
+            TypedExpression::BlockStmt {
+                statements: vec![],
+                span,
+            },
+            ResolvedType::Error,
+        );
+        let Some(variants) = self.enum_table.get(enum_name).cloned() else {
+            self.report(
+                Diagnostic::new(format!("Undefined enum: {}", enum_name), span)
+                    .with_label("no enum with this name is declared"),
+            );
+            return error;
+        };
+        let Some(tag) = variants.iter().position(|(name, _)| name == variant) else {
+            self.report(
+                Diagnostic::new(
+                    format!("Enum '{}' has no variant '{}'", enum_name, variant),
+                    span,
+                )
+                .with_label("unknown variant"),
+            );
+            return error;
+        };
+        let payload = &variants[tag].1;
+        if payload.len() != values.len() {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "{}::{} takes {} value{} but {} were supplied",
+                        enum_name,
+                        variant,
+                        payload.len(),
+                        if payload.len() == 1 { "" } else { "s" },
+                        values.len()
+                    ),
+                    span,
+                )
+                .with_label(format!("expected {} value(s)", payload.len())),
+            );
+            return error;
+        }
+
+        let mut typed_values = Vec::new();
+        for (value, expected) in values.iter().zip(payload) {
+            let (typed_value, value_type) = self.resolve_with_expected(value, expected);
+            if !expected.is_compatible_with(&value_type) {
+                self.report(
+                    Diagnostic::new(
+                        format!(
+                            "Value of {}::{} expects {}, got {}",
+                            enum_name, variant, expected, value_type
+                        ),
+                        value.span,
+                    )
+                    .with_label(format!("expected {}, found {}", expected, value_type)),
+                );
+            }
+            typed_values.push(typed_value);
+        }
+
+        (
+            TypedExpression::EnumValue {
+                enum_name: enum_name.to_string(),
+                tag: tag as u32,
+                values: typed_values,
+                span,
+            },
+            ResolvedType::Enum(enum_name.to_string()),
+        )
+    }
+
+    /// Resolves a match on an enum value. Each arm's bindings are scoped to its
+    /// body; whether every variant is handled is checked by a validation rule.
+    fn resolve_match(
+        &mut self,
+        value: &Expression,
+        arms: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let (typed_value, value_type) = self.resolve_expression(value);
+        let enum_name = match &value_type {
+            ResolvedType::Enum(name) => name.clone(),
+            ResolvedType::Error => String::new(),
+            _ => {
+                self.report(
+                    Diagnostic::new(format!("Cannot match on {}", value_type), value.span)
+                        .with_label(format!("expected an enum, found {}", value_type)),
+                );
+                String::new()
+            }
+        };
+        let variants = self.enum_table.get(&enum_name).cloned().unwrap_or_default();
+
+        let mut typed_arms = Vec::new();
+        let mut matched: Vec<u32> = Vec::new();
+        let mut seen_wildcard = false;
+        for arm in arms {
+            let ExpressionKind::MatchArm(pattern, body) = &arm.kind else {
+                continue;
+            };
+            if seen_wildcard {
+                self.report(
+                    Diagnostic::new("Unreachable match arm", arm.span)
+                        .with_label("the `_` arm above already matches every value"),
+                );
+            }
+            let (tag, bindings) = match pattern {
+                Pattern::Wildcard => {
+                    seen_wildcard = true;
+                    (None, vec![])
+                }
+                Pattern::Variant(pattern_enum, variant, names) => self.resolve_variant_pattern(
+                    &enum_name,
+                    &variants,
+                    (pattern_enum, variant, names),
+                    &mut matched,
+                    arm.span,
+                ),
+            };
+
+            self.incr_depth();
+            for (name, ty) in &bindings {
+                if name != "_" {
+                    self.set_variable(name, ty.clone());
+                }
+            }
+            let (typed_body, _) = self.resolve_expression(body);
+            self.decr_depth();
+
+            typed_arms.push(TypedExpression::MatchArm {
+                tag,
+                bindings,
+                body: Box::new(typed_body),
+                span: arm.span,
+            });
+        }
+
+        (
+            TypedExpression::MatchStmt {
+                value: Box::new(typed_value),
+                enum_name,
+                variants: variants.into_iter().map(|(name, _)| name).collect(),
+                arms: typed_arms,
+                span,
+            },
+            ResolvedType::Void,
+        )
+    }
+
+    /// Checks a `Enum::Variant(a, b)` pattern against the matched enum and
+    /// returns the variant's tag along with the type of each binding.
+    fn resolve_variant_pattern(
+        &mut self,
+        enum_name: &str,
+        variants: &[(String, Vec<ResolvedType>)],
+        (pattern_enum, variant, names): (&String, &String, &[String]),
+        matched: &mut Vec<u32>,
+        span: parser::Span,
+    ) -> (Option<u32>, Vec<(String, ResolvedType)>) {
+        // Bindings still get a type so the arm body resolves without extra errors
+        let error_bindings = names
+            .iter()
+            .map(|name| (name.clone(), ResolvedType::Error))
+            .collect();
+        if enum_name.is_empty() {
+            return (None, error_bindings);
+        }
+        if pattern_enum != enum_name {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "Expected a variant of {}, found {}::{}",
+                        enum_name, pattern_enum, variant
+                    ),
+                    span,
+                )
+                .with_label(format!("the matched value is a {}", enum_name)),
+            );
+            return (None, error_bindings);
+        }
+        let Some(tag) = variants.iter().position(|(name, _)| name == variant) else {
+            self.report(
+                Diagnostic::new(
+                    format!("Enum '{}' has no variant '{}'", enum_name, variant),
+                    span,
+                )
+                .with_label("unknown variant"),
+            );
+            return (None, error_bindings);
+        };
+        let tag = tag as u32;
+        if matched.contains(&tag) {
+            self.report(
+                Diagnostic::new(format!("Variant '{}' is already matched", variant), span)
+                    .with_label("an earlier arm handles this variant"),
+            );
+        }
+        matched.push(tag);
+
+        let payload = &variants[tag as usize].1;
+        if payload.len() != names.len() {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "{}::{} has {} value{} but the pattern binds {}",
+                        enum_name,
+                        variant,
+                        payload.len(),
+                        if payload.len() == 1 { "" } else { "s" },
+                        names.len()
+                    ),
+                    span,
+                )
+                .with_label(format!("expected {} name(s)", payload.len())),
+            );
+            return (Some(tag), error_bindings);
+        }
+        let bindings = names.iter().cloned().zip(payload.iter().cloned()).collect();
+        (Some(tag), bindings)
+    }
+
+    /// Looks up `field` on a value of type `ty`, returning its position in the
+    /// struct and its type.
+    fn resolve_field(
+        &mut self,
+        ty: &ResolvedType,
+        field: &str,
+        span: parser::Span,
+    ) -> (u32, ResolvedType) {
+        let fields = match ty {
+            ResolvedType::Struct(name) => self.struct_table.get(name).cloned().unwrap_or_default(),
+            ResolvedType::Error => return (0, ResolvedType::Error),
+            _ => {
+                self.report(
+                    Diagnostic::new(format!("Cannot access field '{}' on {}", field, ty), span)
+                        .with_label(format!("expected a struct, found {}", ty)),
+                );
+                return (0, ResolvedType::Error);
+            }
+        };
+        match fields.iter().position(|(name, _)| name == field) {
+            Some(index) => (index as u32, fields[index].1.clone()),
+            None => {
+                self.report(
+                    Diagnostic::new(format!("No field '{}' on type {}", field, ty), span)
+                        .with_label("unknown field"),
+                );
+                (0, ResolvedType::Error)
+            }
+        }
+    }
+
+    /// Methods live in the function table under `<type>.<method>`, e.g. `List<i32>.sum`.
+    fn method_name(self_type: &ResolvedType, method: &str) -> String {
+        format!("{}.{}", self_type, method)
+    }
+
+    // len, push, contains, ... can be called as methods on the collections they take
+    fn is_builtin_method(self_type: &ResolvedType, method: &str) -> bool {
+        match self_type {
+            ResolvedType::List(_) => method == "len" || ListOp::from_name(method).is_some(),
+            ResolvedType::Map(..) => method == "len" || MapOp::from_name(method).is_some(),
+            ResolvedType::Optional(_) => method == "unwrap",
+            _ => false,
+        }
+    }
+
+    /// Resolves a call to the function registered as `name`. Unknown functions
+    /// are left for codegen to report.
+    fn resolve_function_call(
+        &mut self,
+        name: &str,
+        args: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let arg_types = self
+            .get_function(name)
+            .map(|(arg_types, _)| arg_types.clone())
+            .unwrap_or_default();
+        let mut typed_args = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            let expected = arg_types.get(i).unwrap_or(&ResolvedType::Void);
+            let (typed_arg, _) = self.resolve_with_expected(arg, expected);
+            typed_args.push(typed_arg);
+        }
+
+        let return_type = self
+            .get_function(name)
+            .map(|(_, ret)| ret.clone())
+            .unwrap_or(ResolvedType::Void);
+
+        (
+            TypedExpression::CallStmt {
+                callee: Box::new(TypedExpression::Variable {
+                    name: name.to_string(),
+                    span,
+                }),
+                args: typed_args,
+                span,
+            },
+            return_type,
+        )
+    }
+
+    /// `util::add(1, 2)` calls a function of an imported module. It parses like
+    /// an enum value, so it is told apart by the name before the `::`.
+    fn resolve_module_call(
+        &mut self,
+        module: &str,
+        function: &str,
+        args: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let name = format!("{}::{}", module, function);
+        if self.get_function(&name).is_none() {
+            self.report(
+                Diagnostic::new(
+                    format!("Module '{}' has no function '{}'", module, function),
+                    span,
+                )
+                .with_label("not found in this module"),
+            );
+            return (
+                TypedExpression::BlockStmt {
+                    statements: vec![],
+                    span,
+                },
+                ResolvedType::Error,
+            );
+        }
+        self.resolve_function_call(&name, args, span)
+    }
+
+    /// Resolves a function declaration under `name`, registering its signature
+    /// before the body so it can call itself.
+    fn resolve_function(
+        &mut self,
+        name: &str,
+        args: &[Expression],
+        return_type: &parser::Type,
+        body: &Expression,
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        // Resolve argument types
+        let mut typed_args = Vec::new();
+        let mut resolved_arg_types = Vec::new();
+        for arg in args {
+            match &arg.kind {
+                ExpressionKind::FuncArg(arg_name, arg_type) => {
+                    let resolved_arg_type = self.resolve_type(arg_type, arg.span);
+                    resolved_arg_types.push(resolved_arg_type.clone());
+                    typed_args.push((arg_name.clone(), resolved_arg_type));
+                }
+                _ => self.report(Diagnostic::new(
+                    "Expected FuncArg in function arguments",
+                    arg.span,
+                )),
+            }
+        }
+
+        // Resolve return type
+        let resolved_return_type = self.resolve_type(return_type, span);
+
+        // Register function signature before resolving body (allows recursion)
+        self.set_function(name, resolved_arg_types, resolved_return_type.clone());
+
+        // Create a new scope for the function body
+        self.incr_depth();
+
+        // Add function parameters to the symbol table
+        for (arg_name, arg_type) in &typed_args {
+            self.set_variable(arg_name, arg_type.clone());
+        }
+
+        // Resolve body (can now reference parameters)
+        let enclosing_return_type = self.return_type.replace(resolved_return_type.clone());
+        let (typed_body, _body_ty) = self.resolve_expression(body);
+        self.return_type = enclosing_return_type;
+
+        // Exit the function scope
+        self.decr_depth();
+
+        let func_type = ResolvedType::Function(
+            typed_args.iter().map(|(_, ty)| ty.clone()).collect(),
+            Box::new(resolved_return_type.clone()),
+        );
+
+        (
+            TypedExpression::FuncStmt {
+                name: name.to_string(),
+                args: typed_args,
+                return_type: resolved_return_type,
+                body: Box::new(typed_body),
+                is_pub: false,
+                span,
+            },
+            func_type,
+        )
+    }
+
+    /// Resolves each method of an impl block as a function named after the
+    /// type, so codegen can lower them like any other function.
+    fn resolve_impl_stmt(
+        &mut self,
+        self_type: &parser::Type,
+        methods: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let resolved_self = self.resolve_type(self_type, span);
+        let mut typed_methods = Vec::new();
+        for method in methods {
+            let ExpressionKind::FuncStmt(name, args, return_type, body) = &method.kind else {
+                continue;
+            };
+            let method_name = Self::method_name(&resolved_self, name);
+            if Self::is_builtin_method(&resolved_self, name) {
+                self.report(
+                    Diagnostic::new(
+                        format!("Method '{}' is already defined for {}", name, resolved_self),
+                        method.span,
+                    )
+                    .with_label(format!("`{}` is a built-in method", name)),
+                );
+            } else if self.get_function(&method_name).is_some() {
+                self.report(
+                    Diagnostic::new(
+                        format!("Method '{}' is already defined for {}", name, resolved_self),
+                        method.span,
+                    )
+                    .with_label("methods on a type must have unique names"),
+                );
+            }
+            let (typed_method, _) =
+                self.resolve_function(&method_name, args, return_type, body, method.span);
+            typed_methods.push(typed_method);
+        }
+        (
+            TypedExpression::ImplStmt {
+                methods: typed_methods,
+                span,
+            },
+            ResolvedType::Void,
+        )
+    }
+
+    /// `value.method(args)` calls a method from an impl block with `value` as
+    /// `self`, or one of the collection builtins with `value` as the collection.
+    fn resolve_method_call(
+        &mut self,
+        receiver: &Expression,
+        method: &str,
+        args: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let (typed_receiver, receiver_type) = self.resolve_expression(receiver);
+
+        if let Some((arg_types, return_type)) = self
+            .get_function(&Self::method_name(&receiver_type, method))
+            .cloned()
+        {
+            // arg_types includes self
+            let arity = arg_types.len() - 1;
+            if args.len() != arity {
+                self.report(
+                    Diagnostic::new(
+                        format!(
+                            "{}() takes {} argument{} but {} were supplied",
+                            method,
+                            arity,
+                            if arity == 1 { "" } else { "s" },
+                            args.len()
+                        ),
+                        span,
+                    )
+                    .with_label(format!("method on {}", receiver_type)),
+                );
+            }
+            let mut typed_args = vec![typed_receiver];
+            for (arg, expected) in args.iter().zip(arg_types.iter().skip(1)) {
+                let (typed_arg, arg_type) = self.resolve_with_expected(arg, expected);
+                if !expected.is_compatible_with(&arg_type) {
+                    self.report(
+                        Diagnostic::new(
+                            format!(
+                                "Argument of {}() expects {}, got {}",
+                                method, expected, arg_type
+                            ),
+                            arg.span,
+                        )
+                        .with_label(format!("expected {}, found {}", expected, arg_type)),
+                    );
+                }
+                typed_args.push(typed_arg);
+            }
+            return (
+                TypedExpression::CallStmt {
+                    callee: Box::new(TypedExpression::Variable {
+                        name: Self::method_name(&receiver_type, method),
+                        span,
+                    }),
+                    args: typed_args,
+                    span,
+                },
+                return_type,
+            );
+        }
+
+        // The builtins take the receiver as their first argument
+        let call_args: Vec<Expression> = std::iter::once(receiver.clone())
+            .chain(args.iter().cloned())
+            .collect();
+        let list_op = ListOp::from_name(method);
+        let map_op = MapOp::from_name(method);
+        match (&receiver_type, method, list_op, map_op) {
+            (ResolvedType::Error, ..) => (typed_receiver, ResolvedType::Error),
+            (ResolvedType::List(_) | ResolvedType::Map(..), "len", ..) => {
+                let receiver_name = match receiver_type {
+                    ResolvedType::List(_) => "list",
+                    _ => "map",
+                };
+                self.check_collection_arity("len", receiver_name, &[], &call_args, span);
+                (
+                    TypedExpression::Len {
+                        value: Box::new(typed_receiver),
+                        span,
+                    },
+                    ResolvedType::I32,
+                )
+            }
+            (ResolvedType::List(_), _, Some(op), _) => {
+                self.resolve_list_call(op, (typed_receiver, receiver_type), &call_args, span)
+            }
+            (ResolvedType::Map(..), _, _, Some(op)) => {
+                self.resolve_map_call(op, (typed_receiver, receiver_type), &call_args, span)
+            }
+            (ResolvedType::Optional(_), "unwrap", ..) => {
+                self.check_collection_arity("unwrap", "value", &[], &call_args, span);
+                self.resolve_unwrap((typed_receiver, receiver_type), span)
+            }
+            _ => {
+                self.report(
+                    Diagnostic::new(
+                        format!("No method '{}' on type {}", method, receiver_type),
+                        span,
+                    )
+                    .with_label(format!("method not found in {}", receiver_type)),
+                );
+                (typed_receiver, ResolvedType::Error)
+            }
+        }
+    }
+
+    /// Whether a field of `target` can be written in place: the struct has
+    /// to live in a variable or a list element, possibly nested in other structs.
+    fn is_assignable(target: &TypedExpression) -> bool {
+        match target {
+            TypedExpression::Variable { .. } | TypedExpression::ListIndex { .. } => true,
+            TypedExpression::FieldAccess { value, .. } => Self::is_assignable(value),
+            _ => false,
+        }
+    }
+
+    fn check_index_type(&mut self, index_type: &ResolvedType, span: parser::Span) {
+        if !ResolvedType::I32.is_compatible_with(index_type) {
+            self.report(
+                Diagnostic::new(format!("List index must be i32, got {}", index_type), span)
+                    .with_label(format!("expected i32, found {}", index_type)),
+            );
+        }
+    }
+
+    /// Map keys are hashed by the runtime, which only knows strings and integers.
+    fn check_map_key_type(&mut self, key_type: &ResolvedType, span: parser::Span) {
+        if !matches!(
+            key_type,
+            ResolvedType::String | ResolvedType::I32 | ResolvedType::I64 | ResolvedType::Error
+        ) {
+            self.report(
+                Diagnostic::new(
+                    format!("Map keys must be string, i32 or i64, got {}", key_type),
+                    span,
+                )
+                .with_label(format!("found {}", key_type)),
+            );
+        }
+    }
+
+    fn check_map_entry_type(
+        &mut self,
+        part: &str,
+        expected: &ResolvedType,
+        found: &ResolvedType,
+        span: parser::Span,
+    ) {
+        if !expected.is_compatible_with(found) {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "Map {} must all have the same type. Expected {}, got {}",
+                        part, expected, found
+                    ),
+                    span,
+                )
+                .with_label(format!("expected {}, found {}", expected, found)),
+            );
+        }
+    }
+
+    fn resolve_map_key(&mut self, key: &Expression, key_type: &ResolvedType) -> TypedExpression {
+        let (typed_key, found) = self.resolve_expression(key);
+        if !key_type.is_compatible_with(&found) {
+            self.report(
+                Diagnostic::new(
+                    format!("Map key must be {}, got {}", key_type, found),
+                    key.span,
+                )
+                .with_label(format!("expected {}, found {}", key_type, found)),
+            );
+        }
+        typed_key
+    }
+
+    /// Builtins such as `remove` work on both lists and maps, so the first
+    /// argument decides which one is being called.
+    fn resolve_collection_call(
+        &mut self,
+        name: &str,
+        args: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let receiver = match args.first() {
+            Some(receiver) => self.resolve_expression(receiver),
+            None => (
+                TypedExpression::BlockStmt {
+                    statements: vec![],
+                    span,
+                },
+                ResolvedType::Error,
+            ),
+        };
+        match (ListOp::from_name(name), MapOp::from_name(name), &receiver.1) {
+            (_, Some(op), ResolvedType::Map(..)) | (None, Some(op), _) => {
+                self.resolve_map_call(op, receiver, args, span)
+            }
+            (Some(op), _, _) => self.resolve_list_call(op, receiver, args, span),
+            (None, None, _) => unreachable!("{} is not a collection builtin", name),
+        }
+    }
+
+    fn resolve_map_call(
+        &mut self,
+        op: MapOp,
+        (typed_map, map_type): (TypedExpression, ResolvedType),
+        args: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let name = op.name();
+        let params: &[&str] = match op {
+            MapOp::Get | MapOp::Contains | MapOp::Remove => &["key"],
+            MapOp::Keys => &[],
+        };
+        self.check_collection_arity(name, "map", params, args, span);
+
+        let (key_type, value_type) = match map_type {
+            ResolvedType::Map(key, value) => (*key, *value),
+            ResolvedType::Error => (ResolvedType::Error, ResolvedType::Error),
+            other => {
+                if let Some(map) = args.first() {
+                    self.report(
+                        Diagnostic::new(
+                            format!("{}() requires a map argument, got {}", name, other),
+                            map.span,
+                        )
+                        .with_label(format!("expected a map, found {}", other)),
+                    );
+                }
+                (ResolvedType::Error, ResolvedType::Error)
+            }
+        };
+
+        let typed_args = args
+            .iter()
+            .skip(1)
+            .take(params.len())
+            .map(|key| self.resolve_map_key(key, &key_type))
+            .collect();
+
+        let return_type = match op {
+            // get hands back nil rather than panicking when the key is missing
+            MapOp::Get => ResolvedType::Optional(Box::new(value_type)),
+            MapOp::Contains => ResolvedType::Bool,
+            // remove hands back the value that was stored under the key
+            MapOp::Remove => value_type,
+            MapOp::Keys => ResolvedType::List(Box::new(key_type)),
+        };
+        (
+            TypedExpression::MapCall {
+                op,
+                map: Box::new(typed_map),
+                args: typed_args,
+                span,
+            },
+            return_type,
+        )
+    }
+
+    fn check_collection_arity(
+        &mut self,
+        name: &str,
+        receiver: &str,
+        params: &[&str],
+        args: &[Expression],
+        span: parser::Span,
+    ) {
+        if args.len() != params.len() + 1 {
+            let arity = params.len() + 1;
+            let signature = [receiver].iter().chain(params).cloned().collect::<Vec<_>>();
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "{}() takes {} argument{} but {} were supplied",
+                        name,
+                        arity,
+                        if arity == 1 { "" } else { "s" },
+                        args.len()
+                    ),
+                    span,
+                )
+                .with_label(format!("expected {}({})", name, signature.join(", "))),
+            );
+        }
+    }
+
+    fn resolve_list_call(
+        &mut self,
+        op: ListOp,
+        (typed_list, list_type): (TypedExpression, ResolvedType),
+        args: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let name = op.name();
+        // Arguments that follow the list
+        let params: &[&str] = match op {
+            ListOp::Push => &["value"],
+            ListOp::Insert => &["index", "value"],
+            ListOp::Remove => &["index"],
+            ListOp::Map | ListOp::Filter => &["function"],
+            ListOp::Pop | ListOp::Clear => &[],
+        };
+        self.check_collection_arity(name, "list", params, args, span);
+
+        let element_type = match list_type {
+            ResolvedType::List(inner) => *inner,
+            ResolvedType::Error => ResolvedType::Error,
+            other => {
+                if let Some(list) = args.first() {
+                    self.report(
+                        Diagnostic::new(
+                            format!("{}() requires a list argument, got {}", name, other),
+                            list.span,
+                        )
+                        .with_label(format!("expected a list, found {}", other)),
+                    );
+                }
+                ResolvedType::Error
+            }
+        };
+
+        let mut typed_args = Vec::new();
+        // What the function passed to map hands back for each element
+        let mut mapped_type = ResolvedType::Error;
+        for (param, arg) in params.iter().zip(args.iter().skip(1)) {
+            if *param == "function" {
+                let (typed_arg, result_type) = self.resolve_list_function(op, &element_type, arg);
+                mapped_type = result_type;
+                typed_args.push(typed_arg);
+                continue;
+            }
+            let (typed_arg, arg_type) = self.resolve_with_expected(arg, &element_type);
+            if *param == "index" {
+                self.check_index_type(&arg_type, arg.span);
+            } else if !element_type.is_compatible_with(&arg_type) {
+                self.report(
+                    Diagnostic::new(
+                        format!(
+                            "Cannot {} a value of type {} into a list of {}",
+                            name, arg_type, element_type
+                        ),
+                        arg.span,
+                    )
+                    .with_label(format!("expected {}, found {}", element_type, arg_type)),
+                );
+            }
+            typed_args.push(typed_arg);
+        }
+
+        // pop and remove hand back the element they took out of the list
+        let return_type = match op {
+            ListOp::Pop | ListOp::Remove => element_type,
+            ListOp::Push | ListOp::Insert | ListOp::Clear => ResolvedType::Void,
+            ListOp::Map if mapped_type.is_error() => ResolvedType::Error,
+            ListOp::Map => ResolvedType::List(Box::new(mapped_type)),
+            ListOp::Filter => ResolvedType::List(Box::new(element_type)),
+        };
+        (
+            TypedExpression::ListCall {
+                op,
+                list: Box::new(typed_list),
+                args: typed_args,
+                span,
+            },
+            return_type,
+        )
+    }
+
+    /// Resolves the function passed to map or filter, which is called with each
+    /// element. Returns the type the function hands back.
+    fn resolve_list_function(
+        &mut self,
+        op: ListOp,
+        element_type: &ResolvedType,
+        arg: &Expression,
+    ) -> (TypedExpression, ResolvedType) {
+        // map takes whatever the function returns, filter needs a bool
+        let expected_return = match op {
+            ListOp::Filter => ResolvedType::Bool,
+            _ => ResolvedType::Void,
+        };
+        let (typed_arg, arg_type) = match &arg.kind {
+            ExpressionKind::Lambda(params, return_type, body) => self.resolve_lambda(
+                params,
+                return_type,
+                body,
+                Some((std::slice::from_ref(element_type), &expected_return)),
+                arg.span,
+            ),
+            _ => self.resolve_expression(arg),
+        };
+
+        let takes_element = match &arg_type {
+            ResolvedType::Function(args, _) => {
+                args.len() == 1 && args[0].is_compatible_with(element_type)
+            }
+            _ => false,
+        };
+        let result_type = match &arg_type {
+            ResolvedType::Error => ResolvedType::Error,
+            ResolvedType::Function(_, ret)
+                if takes_element
+                    && op == ListOp::Filter
+                    && ret.is_compatible_with(&expected_return) =>
+            {
+                ResolvedType::Bool
+            }
+            ResolvedType::Function(_, ret)
+                if takes_element && op == ListOp::Map && **ret != ResolvedType::Void =>
+            {
+                (**ret).clone()
+            }
+            _ => {
+                let expected = match op {
+                    ListOp::Filter => format!("fn({}) -> bool", element_type),
+                    _ => format!("fn({}) -> T", element_type),
+                };
+                self.report(
+                    Diagnostic::new(
+                        format!("{}() expects {}, got {}", op.name(), expected, arg_type),
+                        arg.span,
+                    )
+                    .with_label(format!("expected {}, found {}", expected, arg_type)),
+                );
+                ResolvedType::Error
+            }
+        };
+        (typed_arg, result_type)
+    }
+
+    /// Resolves a call through a variable holding a function or closure.
+    fn resolve_closure_call(
+        &mut self,
+        name: &str,
+        args: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let func_type = self
+            .get_variable(name)
+            .cloned()
+            .unwrap_or(ResolvedType::Error);
+        self.note_capture(name, &func_type);
+        let ResolvedType::Function(arg_types, return_type) = &func_type else {
+            unreachable!("{} is not a function", name);
+        };
+        if args.len() != arg_types.len() {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "{}() takes {} argument{} but {} were supplied",
+                        name,
+                        arg_types.len(),
+                        if arg_types.len() == 1 { "" } else { "s" },
+                        args.len()
+                    ),
+                    span,
+                )
+                .with_label(format!("{} has type {}", name, func_type)),
+            );
+        }
+        let mut typed_args = Vec::new();
+        for (arg, expected) in args.iter().zip(arg_types.iter()) {
+            let (typed_arg, arg_type) = self.resolve_with_expected(arg, expected);
+            if !expected.is_compatible_with(&arg_type) {
+                self.report(
+                    Diagnostic::new(
+                        format!(
+                            "Argument of {}() expects {}, got {}",
+                            name, expected, arg_type
+                        ),
+                        arg.span,
+                    )
+                    .with_label(format!("expected {}, found {}", expected, arg_type)),
+                );
+            }
+            typed_args.push(typed_arg);
+        }
+        (
+            TypedExpression::CallStmt {
+                callee: Box::new(TypedExpression::Variable {
+                    name: name.to_string(),
+                    span,
+                }),
+                args: typed_args,
+                span,
+            },
+            (**return_type).clone(),
+        )
+    }
+
+    /// Resolves `|params| body`. Parameter and return types the lambda leaves
+    /// out are taken from `expected`, the function type its context wants.
+    fn resolve_lambda(
+        &mut self,
+        params: &[(String, parser::Type)],
+        return_type: &parser::Type,
+        body: &Expression,
+        expected: Option<(&[ResolvedType], &ResolvedType)>,
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        if let Some((expected_args, expected_return)) = expected {
+            if expected_args.len() != params.len() {
+                let expected_type = ResolvedType::Function(
+                    expected_args.to_vec(),
+                    Box::new(expected_return.clone()),
+                );
+                self.report(
+                    Diagnostic::new(
+                        format!(
+                            "Expected a function taking {} argument{}, but the lambda takes {}",
+                            expected_args.len(),
+                            if expected_args.len() == 1 { "" } else { "s" },
+                            params.len()
+                        ),
+                        span,
+                    )
+                    .with_label(format!("expected {}", expected_type)),
+                );
+            }
+        }
+
+        let mut typed_params = Vec::new();
+        for (i, (name, ty)) in params.iter().enumerate() {
+            let expected_arg = expected.and_then(|(args, _)| args.get(i));
+            let param_type = match (ty, expected_arg) {
+                (parser::Type::None, Some(expected_arg)) => expected_arg.clone(),
+                (parser::Type::None, None) => {
+                    self.report(
+                        Diagnostic::new(
+                            format!("Cannot infer the type of parameter '{}'", name),
+                            span,
+                        )
+                        .with_label(format!("add a type, e.g. |i32 {}|", name)),
+                    );
+                    ResolvedType::Error
+                }
+                (ty, _) => self.resolve_type(ty, span),
+            };
+            typed_params.push((name.clone(), param_type));
+        }
+
+        // A return type written on the lambda wins over the expected one, and
+        // without either it is inferred from the body
+        let declared_return = match (return_type, expected) {
+            (parser::Type::None, Some((_, ret))) if *ret != ResolvedType::Void => Some(ret.clone()),
+            (parser::Type::None, _) => None,
+            (ty, _) => Some(self.resolve_type(ty, span)),
+        };
+
+        // The body can't see the enclosing function's loops, and the flat symbol
+        // table would lose any outer variable a parameter shadows
+        let enclosing_symbols = self.symbol_table.clone();
+        let enclosing_return_type =
+            std::mem::replace(&mut self.return_type, declared_return.clone());
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let enclosing_loop_labels = std::mem::take(&mut self.loop_labels);
+
+        self.incr_depth();
+        self.lambda_scopes.push((self.depth, Vec::new()));
+        for (name, ty) in &typed_params {
+            self.set_variable(name, ty.clone());
+        }
+        let is_block = matches!(body.kind, ExpressionKind::BlockStmt(_));
+        let (typed_body, body_type) = match &declared_return {
+            Some(ret) if !is_block => self.resolve_with_expected(body, ret),
+            _ => self.resolve_expression(body),
+        };
+        let (_, captures) = self.lambda_scopes.pop().unwrap();
+        self.decr_depth();
+
+        self.symbol_table = enclosing_symbols;
+        self.return_type = enclosing_return_type;
+        self.loop_depth = enclosing_loop_depth;
+        self.loop_labels = enclosing_loop_labels;
+
+        let resolved_return = match declared_return {
+            Some(ret) => {
+                if !is_block && !ret.is_compatible_with(&body_type) {
+                    self.report(
+                        Diagnostic::new(
+                            format!(
+                                "Lambda should return {}, but its body is {}",
+                                ret, body_type
+                            ),
+                            body.span,
+                        )
+                        .with_label(format!("expected {}, found {}", ret, body_type)),
+                    );
+                }
+                ret
+            }
+            None if !is_block => body_type,
+            // A block hands back its value through a final `return`
+            None => match &typed_body {
+                TypedExpression::BlockStmt { statements, .. }
+                    if matches!(statements.last(), Some(TypedExpression::ReturnStmt { .. })) =>
+                {
+                    body_type
+                }
+                _ => ResolvedType::Void,
+            },
+        };
+
+        // An expression body is the lambda's return value
+        let body_span = body.span;
+        let typed_body = if is_block || resolved_return == ResolvedType::Void {
+            typed_body
+        } else {
+            TypedExpression::BlockStmt {
+                statements: vec![TypedExpression::ReturnStmt {
+                    value: Box::new(typed_body),
+                    span: body_span,
+                }],
+                span: body_span,
+            }
+        };
+
+        let func_type = ResolvedType::Function(
+            typed_params.iter().map(|(_, ty)| ty.clone()).collect(),
+            Box::new(resolved_return.clone()),
+        );
+        (
+            TypedExpression::Lambda {
+                params: typed_params,
+                return_type: resolved_return,
+                captures,
+                body: Box::new(typed_body),
+                span,
+            },
+            func_type,
+        )
+    }
+
+    // Numeric conversions are written like calls to a function named after the target type
+    fn conversion_target(name: &str) -> Option<ResolvedType> {
+        match name {
+            "i32" => Some(ResolvedType::I32),
+            "i64" => Some(ResolvedType::I64),
+            "f64" => Some(ResolvedType::F64),
+            _ => None,
+        }
+    }
+
+    fn resolve_conversion(
+        &mut self,
+        name: &str,
+        args: &[Expression],
+        span: parser::Span,
+    ) -> (TypedExpression, ResolvedType) {
+        let to = Self::conversion_target(name).unwrap_or(ResolvedType::Error);
+        if args.len() != 1 {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "{}() takes 1 argument but {} were supplied",
+                        name,
+                        args.len()
+                    ),
+                    span,
+                )
+                .with_label("expected a single numeric value"),
+            );
+        }
+        let (value, from) = match args.first() {
+            Some(arg) => self.resolve_expression(arg),
+            None => (
+                TypedExpression::Number32 { value: 0, span },
+                ResolvedType::Error,
+            ),
+        };
+        if !from.is_numeric() && !from.is_error() {
+            self.report(
+                Diagnostic::new(format!("Cannot convert {} to {}", from, to), value.span())
+                    .with_label(format!("expected a number, found {}", from)),
+            );
+        }
+        (
+            TypedExpression::Cast {
+                value: Box::new(value),
+                from,
+                to: to.clone(),
+                span,
+            },
+            to,
+        )
+    }
+
+    fn resolve_type(&mut self, ty: &parser::Type, span: parser::Span) -> ResolvedType {
+        use parser::Type;
+        match ty {
+            Type::None => ResolvedType::Void,
+            Type::i32 => ResolvedType::I32,
+            Type::i64 => ResolvedType::I64,
+            Type::f64 => ResolvedType::F64,
+            Type::String => ResolvedType::String,
+            Type::Bool => ResolvedType::Bool,
+            Type::List(inner) => ResolvedType::List(Box::new(self.resolve_type(inner, span))),
+            Type::Map(key, value) => ResolvedType::Map(
+                Box::new(self.resolve_type(key, span)),
+                Box::new(self.resolve_type(value, span)),
+            ),
+            Type::Optional(inner) => {
+                ResolvedType::Optional(Box::new(self.resolve_type(inner, span)))
+            }
+            Type::Function(args, ret) => ResolvedType::Function(
+                args.iter()
+                    .map(|arg| self.resolve_type(arg, span))
+                    .collect(),
+                Box::new(self.resolve_type(ret, span)),
+            ),
+            Type::Struct(name) if self.struct_table.contains_key(name) => {
+                ResolvedType::Struct(name.clone())
+            }
+            Type::Struct(name) if self.enum_table.contains_key(name) => {
+                ResolvedType::Enum(name.clone())
+            }
+            Type::Struct(name) => {
+                self.report(
+                    Diagnostic::new(format!("Undefined type: {}", name), span)
+                        .with_label("no struct or enum with this name is declared"),
+                );
+                ResolvedType::Error
+            }
         }
     }
 }
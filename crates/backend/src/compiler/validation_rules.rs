@@ -1,13 +1,25 @@
+use crate::compiler::diagnostics::Diagnostic;
 use codegen::typed_ast::{ResolvedType, TypedExpression};
-use anyhow::{anyhow, Result};
 
 /// Trait for validation rules that can be applied to a program
 pub trait ValidationRule {
     /// Returns the name of this validation rule
     fn name(&self) -> &str;
 
-    /// Validates the program and returns an error if validation fails
-    fn validate(&self, program: &[(TypedExpression, ResolvedType)]) -> Result<()>;
+    /// Validates the program as a whole and returns every problem found, if any
+    fn validate(&self, _program: &[(TypedExpression, ResolvedType)]) -> Vec<Diagnostic> {
+        vec![]
+    }
+
+    /// Validates one top-level item, with the whole program for context. The
+    /// problems found are reported against the file the item came from.
+    fn validate_item(
+        &self,
+        _item: &TypedExpression,
+        _program: &[(TypedExpression, ResolvedType)],
+    ) -> Vec<Diagnostic> {
+        vec![]
+    }
 }
 
 /// Rule: Program must contain a 'main' function
@@ -18,17 +30,154 @@ impl ValidationRule for RequireMainFunction {
         "require-main-function"
     }
 
-    fn validate(&self, program: &[(TypedExpression, ResolvedType)]) -> Result<()> {
+    fn validate(&self, program: &[(TypedExpression, ResolvedType)]) -> Vec<Diagnostic> {
         let has_main = program.iter().any(
             |(expr, _)| matches!(expr, TypedExpression::FuncStmt { name, .. } if name == "main"),
         );
 
         if !has_main {
-            return Err(anyhow!(
-                "Program must contain a 'main' function as the entry point"
-            ));
+            return vec![Diagnostic::without_span(
+                "Program must contain a 'main' function as the entry point",
+            )];
+        }
+
+        vec![]
+    }
+}
+
+/// Rule: Every match must handle each variant of its enum, or have a `_` arm
+pub struct ExhaustiveMatch;
+
+impl ExhaustiveMatch {
+    fn check(expr: &TypedExpression, diagnostics: &mut Vec<Diagnostic>) {
+        if let TypedExpression::MatchStmt {
+            enum_name,
+            variants,
+            arms,
+            span,
+            ..
+        } = expr
+        {
+            let mut covered = vec![false; variants.len()];
+            for arm in arms {
+                match arm {
+                    TypedExpression::MatchArm { tag: None, .. } => {
+                        covered.iter_mut().for_each(|c| *c = true)
+                    }
+                    TypedExpression::MatchArm { tag: Some(tag), .. } => {
+                        if let Some(c) = covered.get_mut(*tag as usize) {
+                            *c = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let missing: Vec<String> = variants
+                .iter()
+                .zip(&covered)
+                .filter(|(_, covered)| !**covered)
+                .map(|(variant, _)| format!("{}::{}", enum_name, variant))
+                .collect();
+            if !missing.is_empty() {
+                diagnostics.push(
+                    Diagnostic::new(
+                        format!("Non-exhaustive match: {} not covered", missing.join(", ")),
+                        *span,
+                    )
+                    .with_label("add an arm for each missing variant or a `_` arm"),
+                );
+            }
+        }
+        for child in expr.children() {
+            Self::check(child, diagnostics);
         }
+    }
+}
+
+impl ValidationRule for ExhaustiveMatch {
+    fn name(&self) -> &str {
+        "exhaustive-match"
+    }
+
+    fn validate_item(
+        &self,
+        item: &TypedExpression,
+        _program: &[(TypedExpression, ResolvedType)],
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        Self::check(item, &mut diagnostics);
+        diagnostics
+    }
+}
+
+/// Rule: A function can only be called from another module if it is `pub`
+pub struct PrivateFunctionAccess;
+
+impl PrivateFunctionAccess {
+    // Module a function named `<module>::<name>` belongs to, None for the program itself
+    fn module_of(name: &str) -> Option<&str> {
+        name.rsplit_once("::").map(|(module, _)| module)
+    }
+
+    fn is_private(program: &[(TypedExpression, ResolvedType)], function: &str) -> bool {
+        program.iter().any(|(expr, _)| match expr {
+            TypedExpression::FuncStmt { name, is_pub, .. } => name == function && !is_pub,
+            _ => false,
+        })
+    }
+
+    fn check(
+        expr: &TypedExpression,
+        caller: Option<&str>,
+        program: &[(TypedExpression, ResolvedType)],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let callee = match expr {
+            TypedExpression::CallStmt { callee, .. } => match callee.as_ref() {
+                TypedExpression::Variable { name, .. } => Some(name),
+                _ => None,
+            },
+            TypedExpression::FunctionRef { name, .. } => Some(name),
+            _ => None,
+        };
+        if let Some(name) = callee {
+            let module = Self::module_of(name);
+            if module.is_some() && module != caller && Self::is_private(program, name) {
+                let (module, function) = name.rsplit_once("::").unwrap();
+                diagnostics.push(
+                    Diagnostic::new(
+                        format!("Function '{}' is private to module '{}'", function, module),
+                        expr.span(),
+                    )
+                    .with_label(format!(
+                        "mark `{}` as `pub fn` to call it from here",
+                        function
+                    )),
+                );
+            }
+        }
+        for child in expr.children() {
+            Self::check(child, caller, program, diagnostics);
+        }
+    }
+}
+
+impl ValidationRule for PrivateFunctionAccess {
+    fn name(&self) -> &str {
+        "private-function-access"
+    }
 
-        Ok(())
+    fn validate_item(
+        &self,
+        item: &TypedExpression,
+        program: &[(TypedExpression, ResolvedType)],
+    ) -> Vec<Diagnostic> {
+        let caller = match item {
+            TypedExpression::FuncStmt { name, .. } => Self::module_of(name),
+            _ => None,
+        };
+        let mut diagnostics = vec![];
+        Self::check(item, caller, program, &mut diagnostics);
+        diagnostics
     }
 }
@@ -1,29 +1,43 @@
 use crate::context::{LLVMCallFn, LLVMFunction, LLVMFunctionCache};
 use crate::stdlib::list::load_list_helper_funcs;
+use crate::stdlib::map::load_map_helper_funcs;
+use crate::stdlib::math::load_math_helper_funcs;
 use crate::stdlib::{link_bitcode_file, load_bitcode_and_set_stdlib_funcs};
 use crate::stdlib::string::load_string_helper_funcs;
 use crate::{
-    cstr_from_string, int1_type, int32_ptr_type, int32_type, int64_type, int8_ptr_type,
+    cstr_from_string, double_type, int1_type, int32_ptr_type, int32_type, int64_type,
+    int8_ptr_type, int8_type,
 };
 use crate::code_generator::GeneratedValue;
+use crate::emit::{optimize_module, set_module_target, write_module, EmitKind, OptLevel};
+use crate::target::{host_triple, Target};
 use crate::typed_ast::ResolvedType;
 use crate::types::bool::BoolType;
 use crate::types::{BaseTypes, TypeBase};
 use crate::CompileOptions;
+use parser::{SourceFile, Span};
 use anyhow::{anyhow, Result};
 use libc::c_uint;
 use llvm_sys::analysis::{LLVMVerifierFailureAction, LLVMVerifyModule};
 use llvm_sys::core::{
     LLVMAddFunction, LLVMAppendBasicBlock, LLVMAppendBasicBlockInContext, LLVMArrayType2,
-    LLVMBuildAdd, LLVMBuildAlloca, LLVMBuildBr, LLVMBuildCall2, LLVMBuildCondBr, LLVMBuildGEP2,
-    LLVMBuildGlobalString, LLVMBuildICmp, LLVMBuildLoad2, LLVMBuildMul, LLVMBuildRet,
-    LLVMBuildRetVoid, LLVMBuildSDiv, LLVMBuildSExt, LLVMBuildStore, LLVMBuildSub, LLVMConstArray2,
-    LLVMConstInt, LLVMCreateBuilderInContext,
+    LLVMBuildAdd, LLVMBuildAlloca, LLVMBuildAnd, LLVMBuildBr, LLVMBuildCall2, LLVMBuildCondBr,
+    LLVMBuildExtractValue, LLVMBuildFAdd, LLVMConstNull,
+    LLVMBuildFCmp, LLVMBuildFDiv, LLVMBuildFMul, LLVMBuildFPToSI, LLVMBuildFRem, LLVMBuildFSub,
+    LLVMBuildGEP2, LLVMBuildMalloc,
+    LLVMAddIncoming, LLVMBuildFNeg, LLVMBuildGlobalString, LLVMBuildICmp, LLVMBuildIsNull,
+    LLVMBuildLoad2, LLVMBuildMul, LLVMBuildNeg, LLVMBuildNot, LLVMBuildPhi, LLVMBuildRet,
+    LLVMBuildRetVoid, LLVMBuildSDiv, LLVMBuildSExt, LLVMBuildSIToFP, LLVMBuildSRem, LLVMBuildStore,
+    LLVMBuildStructGEP2, LLVMBuildSub, LLVMBuildUnreachable, LLVMBuildIntCast2, LLVMBuildZExt,
+    LLVMSizeOf, LLVMStructTypeInContext, LLVMStructCreateNamed, LLVMStructSetBody,
+    LLVMBuildInsertValue, LLVMBuildSelect, LLVMGetUndef, LLVMBuildSwitch, LLVMAddCase,
+    LLVMBuildTrunc, LLVMConstArray2, LLVMConstInt, LLVMConstReal, LLVMCreateBuilderInContext,
     LLVMDeleteFunction, LLVMDisposeBuilder, LLVMDisposeModule, LLVMGetBasicBlockTerminator,
-    LLVMGetGlobalContext,
-    LLVMFunctionType, LLVMGetIntTypeWidth, LLVMGetNamedFunction, LLVMGetParam, LLVMGetTypeByName2,
+    LLVMGetFirstInstruction, LLVMGetGlobalContext, LLVMPositionBuilderBefore,
+    LLVMFunctionType, LLVMGetIntTypeWidth, LLVMGetIntrinsicDeclaration, LLVMIntrinsicGetType,
+    LLVMLookupIntrinsicID, LLVMGetNamedFunction, LLVMGetParam, LLVMGetTypeByName2,
     LLVMInt8TypeInContext, LLVMModuleCreateWithName, LLVMPointerType, LLVMPositionBuilderAtEnd,
-    LLVMPrintModuleToFile, LLVMPrintModuleToString,
+    LLVMPrintModuleToString,
     LLVMSetDataLayout, LLVMSetTarget, LLVMTypeOf, LLVMVoidTypeInContext, LLVMDisposeMessage,
 };
 use llvm_sys::error::{LLVMDisposeErrorMessage, LLVMGetErrorMessage, LLVMErrorRef};
@@ -45,9 +59,14 @@ use llvm_sys::prelude::{
 };
 use llvm_sys::target::{LLVM_InitializeNativeAsmPrinter, LLVM_InitializeNativeTarget};
 use llvm_sys::LLVMIntPredicate;
+use llvm_sys::LLVMRealPredicate;
+use llvm_sys::LLVMRealPredicate::{
+    LLVMRealOEQ, LLVMRealOGE, LLVMRealOGT, LLVMRealOLE, LLVMRealOLT, LLVMRealUNE,
+};
 use llvm_sys::LLVMIntPredicate::{
-    LLVMIntEQ, LLVMIntNE, LLVMIntSGE, LLVMIntSGT, LLVMIntSLE, LLVMIntSLT,
+    LLVMIntEQ, LLVMIntNE, LLVMIntSGE, LLVMIntSGT, LLVMIntSLE, LLVMIntSLT, LLVMIntUGE,
 };
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::process::Command;
 use std::ptr;
@@ -61,10 +80,20 @@ pub struct LLVMCodegenBuilder {
     pub printf_str_value: LLVMValueRef,
     pub printf_str_num_value: LLVMValueRef,
     pub printf_str_num64_value: LLVMValueRef,
+    pub printf_str_float64_value: LLVMValueRef,
     is_execution_engine: bool,
     emit_llvm_ir: bool,
     emit_llvm_ir_main_only: bool,
     emit_llvm_ir_with_called: bool,
+    checked_arithmetic: bool,
+    source: Option<SourceFile>,
+    output: Option<String>,
+    emit: Option<EmitKind>,
+    opt_level: OptLevel,
+    // Cross compilation target, the host when None
+    target: Option<Target>,
+    // User-defined structs: struct name -> named LLVM struct type
+    struct_types: HashMap<String, LLVMTypeRef>,
 }
 
 macro_rules! llvm_build_fn {
@@ -78,36 +107,40 @@ impl LLVMCodegenBuilder {
     pub fn init(compile_options: Option<CompileOptions>) -> Result<LLVMCodegenBuilder> {
         unsafe {
             let mut is_execution_engine = false;
-            let mut is_default_target: bool = true;
 
             let mut emit_llvm_ir = false;
             let mut emit_llvm_ir_main_only = true;
             let mut emit_llvm_ir_with_called = false;
-            if let Some(compile_options) = compile_options {
+            let mut checked_arithmetic = true;
+            let mut source = None;
+            let mut output = None;
+            let mut emit = None;
+            let mut opt_level = OptLevel::O0;
+            let mut target = None;
+            if let Some(compile_options) = &compile_options {
                 is_execution_engine = compile_options.is_execution_engine;
-                is_default_target = compile_options.target.is_none();
                 emit_llvm_ir = compile_options.emit_llvm_ir;
                 emit_llvm_ir_main_only = compile_options.emit_llvm_ir_main_only;
                 emit_llvm_ir_with_called = compile_options.emit_llvm_ir_with_called;
+                checked_arithmetic = compile_options.checked_arithmetic;
+                source = compile_options.source.clone();
+                output = compile_options.output.clone();
+                emit = compile_options.emit;
+                opt_level = compile_options.opt_level;
+                target = compile_options.target.clone();
             }
 
-            if is_default_target {
-                LLVM_InitializeNativeTarget();
-                LLVM_InitializeNativeAsmPrinter();
-            }
-            if !is_default_target {
-                compile_options.unwrap().target.unwrap().initialize();
+            match &target {
+                Some(target) => target.initialize(),
+                None => {
+                    LLVM_InitializeNativeTarget();
+                    LLVM_InitializeNativeAsmPrinter();
+                }
             }
 
             let context = LLVMGetGlobalContext();
             let module = LLVMModuleCreateWithName(cstr_from_string("main").as_ptr());
             let builder = LLVMCreateBuilderInContext(context);
-            if !is_default_target {
-                LLVMSetTarget(
-                    module,
-                    cstr_from_string("wasm32-unknown-unknown-wasm").as_ptr(),
-                );
-            }
 
             let dummy_func = Self::build_dummy_function(context, builder, module);
 
@@ -123,6 +156,11 @@ impl LLVMCodegenBuilder {
                 cstr_from_string("%llu\n").as_ptr(),
                 cstr_from_string("number64_printf_val").as_ptr(),
             );
+            let printf_str_float64_value = LLVMBuildGlobalString(
+                builder,
+                cstr_from_string("%f\n").as_ptr(),
+                cstr_from_string("float64_printf_val").as_ptr(),
+            );
             let printf_str_value = LLVMBuildGlobalString(
                 builder,
                 cstr_from_string("%s\n").as_ptr(),
@@ -131,8 +169,17 @@ impl LLVMCodegenBuilder {
 
             let llvm_func_cache = LLVMFunctionCache::new();
 
-            let llvm_func_cache =
-                load_bitcode_and_set_stdlib_funcs(context, module, llvm_func_cache)?;
+            let llvm_func_cache = load_bitcode_and_set_stdlib_funcs(
+                context,
+                module,
+                llvm_func_cache,
+                target.as_ref(),
+            )?;
+            // Cross targets set the triple and data layout of the whole module
+            // once the stdlib compiled for them is linked in
+            if target.is_some() {
+                set_module_target(module, target.as_ref())?;
+            }
 
             let mut codegen_builder = LLVMCodegenBuilder {
                 builder,
@@ -143,10 +190,18 @@ impl LLVMCodegenBuilder {
                 printf_str_value,
                 printf_str_num_value,
                 printf_str_num64_value,
+                printf_str_float64_value,
                 is_execution_engine,
                 emit_llvm_ir,
                 emit_llvm_ir_main_only,
                 emit_llvm_ir_with_called,
+                checked_arithmetic,
+                source,
+                output,
+                emit,
+                opt_level,
+                target,
+                struct_types: HashMap::new(),
             };
             LLVMDeleteFunction(dummy_func.function);
             codegen_builder.build_helper_funcs();
@@ -156,34 +211,36 @@ impl LLVMCodegenBuilder {
 
     pub fn dispose_and_get_module_str(&self) -> Result<String> {
         unsafe {
+            // Verify the module before optimizing, running or writing it so invalid IR
+            // surfaces as an error instead of crashing LLVM.
+            let mut error: *mut i8 = ptr::null_mut();
+            let has_error = LLVMVerifyModule(
+                self.module,
+                LLVMVerifierFailureAction::LLVMReturnStatusAction,
+                &mut error,
+            );
+            if has_error != 0 {
+                let msg = if error.is_null() {
+                    "Unknown verification error".to_string()
+                } else {
+                    let cstr = std::ffi::CStr::from_ptr(error);
+                    let msg = cstr.to_string_lossy().to_string();
+                    LLVMDisposeMessage(error);
+                    msg
+                };
+                let ir = LLVMPrintModuleToString(self.module);
+                let ir_str = if ir.is_null() {
+                    "<unable to print module>".to_string()
+                } else {
+                    let cstr = std::ffi::CStr::from_ptr(ir);
+                    let s = cstr.to_string_lossy().to_string();
+                    LLVMDisposeMessage(ir);
+                    s
+                };
+                return Err(anyhow!("LLVM module verification failed:\n{msg}\n{ir_str}"));
+            }
+            optimize_module(self.module, self.opt_level, self.target.as_ref())?;
             if self.is_execution_engine {
-                // Verify module before JIT to surface invalid IR instead of crashing.
-                let mut error: *mut i8 = ptr::null_mut();
-                let has_error = LLVMVerifyModule(
-                    self.module,
-                    LLVMVerifierFailureAction::LLVMReturnStatusAction,
-                    &mut error,
-                );
-                if has_error != 0 {
-                    let msg = if error.is_null() {
-                        "Unknown verification error".to_string()
-                    } else {
-                        let cstr = std::ffi::CStr::from_ptr(error);
-                        let msg = cstr.to_string_lossy().to_string();
-                        LLVMDisposeMessage(error);
-                        msg
-                    };
-                    let ir = LLVMPrintModuleToString(self.module);
-                    let ir_str = if ir.is_null() {
-                        "<unable to print module>".to_string()
-                    } else {
-                        let cstr = std::ffi::CStr::from_ptr(ir);
-                        let s = cstr.to_string_lossy().to_string();
-                        LLVMDisposeMessage(ir);
-                        s
-                    };
-                    return Err(anyhow!("LLVM module verification failed:\n{msg}\n{ir_str}"));
-                }
                 self.run_orc_jit_main()?;
             }
 
@@ -218,11 +275,11 @@ impl LLVMCodegenBuilder {
             }
 
             if !self.is_execution_engine {
-                LLVMPrintModuleToFile(
-                    self.module,
-                    cstr_from_string("bin/main.ll").as_ptr(),
-                    ptr::null_mut(),
-                );
+                let output = self.output.as_deref().unwrap_or("bin/main");
+                match self.emit {
+                    Some(kind) => write_module(self.module, kind, output, self.target.as_ref())?,
+                    None => self.build_executable(output)?,
+                }
             }
             // clean up
             LLVMDisposeBuilder(self.builder);
@@ -230,6 +287,9 @@ impl LLVMCodegenBuilder {
                 LLVMDisposeModule(self.module);
             }
             // Global context is managed by LLVM; don't dispose it.
+            if self.output.is_some() {
+                return Ok("".to_string());
+            }
             self.emit_binary()
         }
     }
@@ -355,14 +415,54 @@ impl LLVMCodegenBuilder {
         }
     }
 
+    /// Emits an object file for the module and links it into a native
+    /// executable at `output`.
+    pub fn build_executable(&self, output: &str) -> Result<()> {
+        let object = tempfile::Builder::new().suffix(".o").tempfile()?;
+        unsafe {
+            write_module(
+                self.module,
+                EmitKind::Object,
+                object.path().to_str().unwrap(),
+                self.target.as_ref(),
+            )?;
+        }
+        let mut link = Command::new("clang");
+        if let Some(target) = &self.target {
+            link.arg(format!("--target={}", target.get_llvm_target_name()));
+        }
+        link.arg(object.path());
+        // f64 % calls fmod, and libm is only part of libc on Apple platforms
+        let triple = self
+            .target
+            .as_ref()
+            .map_or_else(host_triple, Target::get_llvm_target_name);
+        if !triple.contains("apple") {
+            link.arg("-lm");
+        }
+        let link = link.arg("-o").arg(output).output()?;
+        if !link.status.success() {
+            return Err(anyhow!(
+                "failed to link {}: {}",
+                output,
+                String::from_utf8_lossy(&link.stderr)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sets the file the spans of the code generated next index, used for
+    /// runtime panic locations.
+    pub fn set_source(&mut self, source: Option<SourceFile>) {
+        self.source = source;
+    }
+
+    /// Runs the executable built at bin/main, returning what it printed.
     pub fn emit_binary(&self) -> Result<String> {
         if !self.is_execution_engine {
-            Command::new("clang")
-                .arg("bin/main.ll")
-                .arg("-o")
-                .arg("bin/main")
-                .output()?;
             let output = Command::new("bin/main").output()?;
+            // Surface runtime panics, which the program writes to stderr
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
             return Ok(String::from_utf8_lossy(&output.stdout).to_string());
         }
         Ok("".to_string())
@@ -425,7 +525,10 @@ impl LLVMCodegenBuilder {
 
     /// build_alloca
     ///
-    /// This builds memory on the stack via the LLVMBuildAlloca instruction
+    /// This builds memory on the stack via the LLVMBuildAlloca instruction. The
+    /// alloca is placed at the top of the current function's entry block, so a
+    /// `let` inside a loop body reuses one slot rather than growing the stack
+    /// on every iteration.
     ///
     /// # Arguments
     ///
@@ -433,7 +536,19 @@ impl LLVMCodegenBuilder {
     /// * `name` - The LLVM name of the alloca
     ///
     pub fn build_alloca(&self, ptr_type: LLVMTypeRef, name: &str) -> LLVMValueRef {
-        unsafe { LLVMBuildAlloca(self.builder, ptr_type, cstr_from_string(name).as_ptr()) }
+        unsafe {
+            let entry_block = self.current_function.entry_block;
+            let alloca_builder = LLVMCreateBuilderInContext(self.context);
+            let first_instruction = LLVMGetFirstInstruction(entry_block);
+            if first_instruction.is_null() {
+                LLVMPositionBuilderAtEnd(alloca_builder, entry_block);
+            } else {
+                LLVMPositionBuilderBefore(alloca_builder, first_instruction);
+            }
+            let alloca = LLVMBuildAlloca(alloca_builder, ptr_type, cstr_from_string(name).as_ptr());
+            LLVMDisposeBuilder(alloca_builder);
+            alloca
+        }
     }
 
     /// build_alloca_store
@@ -527,6 +642,53 @@ impl LLVMCodegenBuilder {
         }
     }
 
+    pub fn cast_int_to_f64(&self, value: LLVMValueRef, ty: &ResolvedType) -> LLVMValueRef {
+        match ty {
+            ResolvedType::I32 | ResolvedType::I64 => unsafe {
+                LLVMBuildSIToFP(
+                    self.builder,
+                    value,
+                    double_type(),
+                    cstr_from_string("cast_to_f64").as_ptr(),
+                )
+            },
+            _ => value,
+        }
+    }
+
+    /// Converts a numeric value between i32, i64 and f64. Floats are
+    /// truncated towards zero when converted to an integer.
+    pub fn build_numeric_cast(
+        &self,
+        value: LLVMValueRef,
+        from: &ResolvedType,
+        to: &ResolvedType,
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            let name = cstr_from_string("cast");
+            let cast = match (from, to) {
+                _ if from == to => value,
+                (ResolvedType::I32, ResolvedType::I64) => {
+                    LLVMBuildSExt(self.builder, value, int64_type(), name.as_ptr())
+                }
+                (ResolvedType::I64, ResolvedType::I32) => {
+                    LLVMBuildTrunc(self.builder, value, int32_type(), name.as_ptr())
+                }
+                (ResolvedType::I32 | ResolvedType::I64, ResolvedType::F64) => {
+                    self.cast_int_to_f64(value, from)
+                }
+                (ResolvedType::F64, ResolvedType::I32) => {
+                    LLVMBuildFPToSI(self.builder, value, int32_type(), name.as_ptr())
+                }
+                (ResolvedType::F64, ResolvedType::I64) => {
+                    LLVMBuildFPToSI(self.builder, value, int64_type(), name.as_ptr())
+                }
+                _ => return Err(anyhow!("Cannot convert {} to {}", from, to)),
+            };
+            Ok(cast)
+        }
+    }
+
     pub fn set_current_block(&mut self, block: LLVMBasicBlockRef) {
         self.position_builder_at_end(block);
         self.current_function.block = block;
@@ -540,6 +702,7 @@ impl LLVMCodegenBuilder {
         match val {
             BaseTypes::Number => self.printf_str_num_value,
             BaseTypes::Number64 => self.printf_str_num64_value,
+            BaseTypes::Float64 => self.printf_str_float64_value,
             BaseTypes::Bool => self.printf_str_value,
             BaseTypes::String => self.printf_str_value,
             BaseTypes::List(_) => self.printf_str_value, // placeholder - no-op
@@ -566,6 +729,40 @@ impl LLVMCodegenBuilder {
         unsafe { LLVMBuildCondBr(self.builder, cond, then_block, else_block) }
     }
 
+    pub fn build_phi(
+        &self,
+        phi_type: LLVMTypeRef,
+        incoming: &[(LLVMValueRef, LLVMBasicBlockRef)],
+        name: &str,
+    ) -> LLVMValueRef {
+        unsafe {
+            let phi = LLVMBuildPhi(self.builder, phi_type, cstr_from_string(name).as_ptr());
+            let mut values: Vec<LLVMValueRef> = incoming.iter().map(|(v, _)| *v).collect();
+            let mut blocks: Vec<LLVMBasicBlockRef> = incoming.iter().map(|(_, b)| *b).collect();
+            LLVMAddIncoming(
+                phi,
+                values.as_mut_ptr(),
+                blocks.as_mut_ptr(),
+                incoming.len() as c_uint,
+            );
+            phi
+        }
+    }
+
+    pub fn build_not(&self, value: LLVMValueRef) -> LLVMValueRef {
+        unsafe { LLVMBuildNot(self.builder, value, cstr_from_string("not").as_ptr()) }
+    }
+
+    pub fn build_neg(&self, value: LLVMValueRef, ty: &ResolvedType) -> LLVMValueRef {
+        unsafe {
+            let name = cstr_from_string("neg");
+            match ty {
+                ResolvedType::F64 => LLVMBuildFNeg(self.builder, value, name.as_ptr()),
+                _ => LLVMBuildNeg(self.builder, value, name.as_ptr()),
+            }
+        }
+    }
+
     pub fn position_builder_at_end(&self, block: LLVMBasicBlockRef) {
         unsafe {
             LLVMPositionBuilderAtEnd(self.builder, block);
@@ -591,6 +788,10 @@ impl LLVMCodegenBuilder {
         unsafe { LLVMConstInt(int_type, val, sign_extend) }
     }
 
+    pub fn const_real(&self, real_type: LLVMTypeRef, val: f64) -> LLVMValueRef {
+        unsafe { LLVMConstReal(real_type, val) }
+    }
+
     pub fn const_array(
         &self,
         element_type: LLVMTypeRef,
@@ -615,6 +816,370 @@ impl LLVMCodegenBuilder {
         unsafe { LLVMBuildGEP2(self.builder, llvm_type, ptr, indices, num_indices, name) }
     }
 
+    /// Layout of the runtime's `List` struct in types.c: data pointer, length,
+    /// capacity and element size in bytes.
+    pub fn list_struct_type(&self) -> LLVMTypeRef {
+        unsafe {
+            let mut fields = [int8_ptr_type(), int32_type(), int32_type(), int32_type()];
+            LLVMStructTypeInContext(self.context, fields.as_mut_ptr(), fields.len() as c_uint, 0)
+        }
+    }
+
+    /// Loads the length of a list, which the runtime keeps in the list struct.
+    pub fn build_list_len(&self, list: LLVMValueRef) -> LLVMValueRef {
+        let length_ptr = unsafe {
+            LLVMBuildStructGEP2(
+                self.builder,
+                self.list_struct_type(),
+                list,
+                1,
+                cstr_from_string("length_ptr").as_ptr(),
+            )
+        };
+        self.build_load(length_ptr, int32_type(), "length")
+    }
+
+    /// Type of a list element as it is laid out in the list's data buffer.
+    /// Bools are widened to a byte to match the runtime's `bool`.
+    fn list_element_type(&self, element_type: &ResolvedType) -> Result<LLVMTypeRef> {
+        match element_type {
+            ResolvedType::I32 => Ok(int32_type()),
+            ResolvedType::I64 => Ok(int64_type()),
+            ResolvedType::F64 => Ok(double_type()),
+            ResolvedType::Bool => Ok(int8_type()),
+            ResolvedType::String | ResolvedType::List(_) | ResolvedType::Map(..) => {
+                Ok(int8_ptr_type())
+            }
+            // Structs and enums are stored by value, so the element size is their size
+            ResolvedType::Struct(name) | ResolvedType::Enum(name) => self.struct_type(name),
+            // Stored as the same { i1, T } value used everywhere else, so bools stay i1
+            ResolvedType::Optional(inner) => {
+                let value_type = match **inner {
+                    ResolvedType::Bool => int1_type(),
+                    _ => self.list_element_type(inner)?,
+                };
+                Ok(self.optional_type(value_type))
+            }
+            ResolvedType::Function(..) => Ok(self.closure_type()),
+            _ => Err(anyhow!(
+                "Lists of type {:?} are not yet supported",
+                element_type
+            )),
+        }
+    }
+
+    /// Size in bytes of one list element, passed to `createList`.
+    pub fn build_list_element_size(&self, element_type: &ResolvedType) -> Result<LLVMValueRef> {
+        let llvm_type = self.list_element_type(element_type)?;
+        unsafe {
+            Ok(LLVMBuildIntCast2(
+                self.builder,
+                LLVMSizeOf(llvm_type),
+                int32_type(),
+                0,
+                cstr_from_string("element_size").as_ptr(),
+            ))
+        }
+    }
+
+    /// Loads the element at `index`. Callers check the index against the list
+    /// length first.
+    pub fn build_list_load(
+        &self,
+        list: LLVMValueRef,
+        index: LLVMValueRef,
+        element_type: &ResolvedType,
+    ) -> Result<LLVMValueRef> {
+        let llvm_type = self.list_element_type(element_type)?;
+        let element_ptr = self.build_list_element_ptr(list, index, llvm_type);
+        self.build_list_slot_load(element_ptr, element_type)
+    }
+
+    /// Loads the value held in a slot handed out by the runtime, e.g. by `mapGetInt`.
+    pub fn build_list_slot_load(
+        &self,
+        slot: LLVMValueRef,
+        element_type: &ResolvedType,
+    ) -> Result<LLVMValueRef> {
+        let llvm_type = self.list_element_type(element_type)?;
+        let value = self.build_load(slot, llvm_type, "list_element");
+        if *element_type == ResolvedType::Bool {
+            return Ok(unsafe {
+                LLVMBuildTrunc(
+                    self.builder,
+                    value,
+                    int1_type(),
+                    cstr_from_string("list_bool").as_ptr(),
+                )
+            });
+        }
+        Ok(value)
+    }
+
+    /// Stores `value` at `index`. Callers check the index against the list
+    /// length first.
+    pub fn build_list_store(
+        &self,
+        list: LLVMValueRef,
+        index: LLVMValueRef,
+        value: LLVMValueRef,
+        element_type: &ResolvedType,
+    ) -> Result<()> {
+        let llvm_type = self.list_element_type(element_type)?;
+        let element_ptr = self.build_list_element_ptr(list, index, llvm_type);
+        self.build_list_slot_store(element_ptr, value, element_type)
+    }
+
+    /// Stores `value` into a slot handed out by the runtime, e.g. by `pushList`.
+    pub fn build_list_slot_store(
+        &self,
+        slot: LLVMValueRef,
+        value: LLVMValueRef,
+        element_type: &ResolvedType,
+    ) -> Result<()> {
+        let llvm_type = self.list_element_type(element_type)?;
+        let value = if *element_type == ResolvedType::Bool {
+            unsafe {
+                LLVMBuildZExt(
+                    self.builder,
+                    value,
+                    llvm_type,
+                    cstr_from_string("list_bool").as_ptr(),
+                )
+            }
+        } else {
+            value
+        };
+        self.build_store(value, slot);
+        Ok(())
+    }
+
+    /// Pointer to the element at `index`, for writing to a field of a struct
+    /// held in a list. Callers check the index against the list length first.
+    pub fn build_list_slot(
+        &self,
+        list: LLVMValueRef,
+        index: LLVMValueRef,
+        element_type: &ResolvedType,
+    ) -> Result<LLVMValueRef> {
+        let llvm_type = self.list_element_type(element_type)?;
+        Ok(self.build_list_element_ptr(list, index, llvm_type))
+    }
+
+    /// Declares the named LLVM struct type for a user-defined struct.
+    pub fn build_struct_type(
+        &mut self,
+        name: &str,
+        field_types: &mut [LLVMTypeRef],
+    ) -> LLVMTypeRef {
+        unsafe {
+            let struct_type = LLVMStructCreateNamed(self.context, cstr_from_string(name).as_ptr());
+            LLVMStructSetBody(
+                struct_type,
+                field_types.as_mut_ptr(),
+                field_types.len() as c_uint,
+                0,
+            );
+            self.struct_types.insert(name.to_string(), struct_type);
+            struct_type
+        }
+    }
+
+    /// Declares an enum as a named struct of its tag and a payload of
+    /// `payload_slots` 8 byte slots, large enough for its biggest variant.
+    pub fn build_enum_type(&mut self, name: &str, payload_slots: u64) -> LLVMTypeRef {
+        let payload_type = unsafe { LLVMArrayType2(int64_type(), payload_slots) };
+        self.build_struct_type(name, &mut [int32_type(), payload_type])
+    }
+
+    /// The unnamed struct a variant's payload values are stored as.
+    pub fn variant_payload_type(&self, field_types: &mut [LLVMTypeRef]) -> LLVMTypeRef {
+        unsafe {
+            LLVMStructTypeInContext(
+                self.context,
+                field_types.as_mut_ptr(),
+                field_types.len() as c_uint,
+                0,
+            )
+        }
+    }
+
+    /// The `{ i1, T }` struct an optional is stored as, the flag is set when
+    /// a value is present.
+    pub fn optional_type(&self, value_type: LLVMTypeRef) -> LLVMTypeRef {
+        self.variant_payload_type(&mut [int1_type(), value_type])
+    }
+
+    /// The `{ ptr, ptr }` struct a function value is stored as: the function to
+    /// call and the environment holding its captured variables, which is passed
+    /// to the function ahead of its own arguments.
+    pub fn closure_type(&self) -> LLVMTypeRef {
+        self.variant_payload_type(&mut [int8_ptr_type(), int8_ptr_type()])
+    }
+
+    /// Allocates a value of `ty` on the heap, e.g. a closure's environment, which
+    /// has to outlive the function that created it.
+    pub fn build_malloc(&self, ty: LLVMTypeRef, name: &str) -> LLVMValueRef {
+        unsafe { LLVMBuildMalloc(self.builder, ty, cstr_from_string(name).as_ptr()) }
+    }
+
+    /// An optional holding `value`, or nil when there is none.
+    pub fn build_optional(
+        &self,
+        optional_type: LLVMTypeRef,
+        value: Option<LLVMValueRef>,
+    ) -> LLVMValueRef {
+        match value {
+            Some(value) => {
+                let present = self.const_int(int1_type(), 1, 0);
+                self.build_struct_value(optional_type, &[present, value])
+            }
+            None => unsafe { LLVMConstNull(optional_type) },
+        }
+    }
+
+    /// The LLVM type of a struct declared with `build_struct_type`.
+    pub fn struct_type(&self, name: &str) -> Result<LLVMTypeRef> {
+        self.struct_types
+            .get(name)
+            .copied()
+            .ok_or_else(|| anyhow!("Undefined struct: {}", name))
+    }
+
+    /// Pointer to field `index` of the struct `struct_ptr` points at.
+    pub fn build_struct_field_ptr(
+        &self,
+        struct_type: LLVMTypeRef,
+        struct_ptr: LLVMValueRef,
+        index: u32,
+    ) -> LLVMValueRef {
+        unsafe {
+            LLVMBuildStructGEP2(
+                self.builder,
+                struct_type,
+                struct_ptr,
+                index,
+                cstr_from_string("field_ptr").as_ptr(),
+            )
+        }
+    }
+
+    pub fn build_extract_value(&self, value: LLVMValueRef, index: u32, name: &str) -> LLVMValueRef {
+        unsafe {
+            LLVMBuildExtractValue(self.builder, value, index, cstr_from_string(name).as_ptr())
+        }
+    }
+
+    /// Builds a struct value from its field values, in declaration order.
+    pub fn build_struct_value(
+        &self,
+        struct_type: LLVMTypeRef,
+        fields: &[LLVMValueRef],
+    ) -> LLVMValueRef {
+        unsafe {
+            let mut value = LLVMGetUndef(struct_type);
+            for (i, field) in fields.iter().enumerate() {
+                value = LLVMBuildInsertValue(
+                    self.builder,
+                    value,
+                    *field,
+                    i as c_uint,
+                    cstr_from_string("struct_field").as_ptr(),
+                );
+            }
+            value
+        }
+    }
+
+    pub fn build_switch(
+        &self,
+        value: LLVMValueRef,
+        default_block: LLVMBasicBlockRef,
+        num_cases: u32,
+    ) -> LLVMValueRef {
+        unsafe { LLVMBuildSwitch(self.builder, value, default_block, num_cases) }
+    }
+
+    pub fn add_switch_case(
+        &self,
+        switch: LLVMValueRef,
+        value: LLVMValueRef,
+        block: LLVMBasicBlockRef,
+    ) {
+        unsafe { LLVMAddCase(switch, value, block) }
+    }
+
+    pub fn build_unreachable(&self) {
+        unsafe {
+            LLVMBuildUnreachable(self.builder);
+        }
+    }
+
+    pub fn build_global_string(&self, value: &str, name: &str) -> LLVMValueRef {
+        unsafe {
+            LLVMBuildGlobalString(
+                self.builder,
+                cstr_from_string(value).as_ptr(),
+                cstr_from_string(name).as_ptr(),
+            )
+        }
+    }
+
+    pub fn build_select(
+        &self,
+        cond: LLVMValueRef,
+        then_value: LLVMValueRef,
+        else_value: LLVMValueRef,
+    ) -> LLVMValueRef {
+        unsafe {
+            LLVMBuildSelect(
+                self.builder,
+                cond,
+                then_value,
+                else_value,
+                cstr_from_string("select").as_ptr(),
+            )
+        }
+    }
+
+    /// Calls printf with a format string built at compile time.
+    pub fn build_printf(&self, format: &str, mut args: Vec<LLVMValueRef>) -> Result<()> {
+        let printf_func = self
+            .llvm_func_cache
+            .get("printf")
+            .ok_or_else(|| anyhow!("printf function not found in cache"))?;
+        args.insert(0, self.build_global_string(format, "print_format"));
+        let num_args = args.len() as u32;
+        self.build_call(printf_func, args, num_args, "");
+        Ok(())
+    }
+
+    fn build_list_element_ptr(
+        &self,
+        list: LLVMValueRef,
+        index: LLVMValueRef,
+        element_type: LLVMTypeRef,
+    ) -> LLVMValueRef {
+        let data_ptr = unsafe {
+            LLVMBuildStructGEP2(
+                self.builder,
+                self.list_struct_type(),
+                list,
+                0,
+                cstr_from_string("data_ptr").as_ptr(),
+            )
+        };
+        let data = self.build_load(data_ptr, int8_ptr_type(), "data");
+        let mut indices = [index];
+        self.build_gep(
+            element_type,
+            data,
+            indices.as_mut_ptr(),
+            1,
+            cstr_from_string("element_ptr").as_ptr(),
+        )
+    }
+
     pub fn build_helper_funcs(&mut self) {
         unsafe {
             let bool_to_str_func = self.build_bool_to_str_func();
@@ -637,6 +1202,8 @@ impl LLVMCodegenBuilder {
             );
             load_string_helper_funcs(self.context, self.module, &mut self.llvm_func_cache);
             load_list_helper_funcs(self.context, self.module, &mut self.llvm_func_cache);
+            load_map_helper_funcs(self.context, self.module, &mut self.llvm_func_cache);
+            load_math_helper_funcs(self.context, self.module, &mut self.llvm_func_cache);
         }
     }
 
@@ -709,7 +1276,7 @@ impl LLVMCodegenBuilder {
     ) -> Result<Box<dyn TypeBase>> {
         unsafe {
             match lhs.get_type() {
-                BaseTypes::Number | BaseTypes::Bool => {
+                BaseTypes::Number | BaseTypes::Number64 | BaseTypes::Bool => {
                     let mut lhs_val = match lhs.get_ptr() {
                         Some(lhs_ptr) => {
                             self.build_load(lhs_ptr, lhs.get_llvm_type(), lhs.get_name_as_str())
@@ -739,17 +1306,62 @@ impl LLVMCodegenBuilder {
                         llvm_value_pointer: Some(alloca),
                     }))
                 }
-                _ => {
-                    unreachable!(
-                        "Can't do operation type {:?} and type {:?}",
-                        lhs.get_type(),
-                        rhs.get_type()
-                    )
-                }
+                _ => Err(anyhow!(
+                    "Can't compare type {:?} and type {:?}",
+                    lhs.get_type(),
+                    rhs.get_type()
+                )),
             }
         }
     }
 
+    pub fn fcmp(
+        &self,
+        lhs: Box<dyn TypeBase>,
+        rhs: Box<dyn TypeBase>,
+        op: LLVMRealPredicate,
+    ) -> Result<Box<dyn TypeBase>> {
+        let mut operands = Vec::new();
+        for operand in [&lhs, &rhs] {
+            let value = match operand.get_ptr() {
+                Some(ptr) => self.build_load(
+                    ptr,
+                    operand.get_llvm_type(),
+                    operand.get_name_as_str(),
+                ),
+                None => operand.get_value(),
+            };
+            let ty = match operand.get_type() {
+                BaseTypes::Number => ResolvedType::I32,
+                BaseTypes::Number64 => ResolvedType::I64,
+                BaseTypes::Float64 => ResolvedType::F64,
+                other => {
+                    return Err(anyhow!(
+                        "Can't compare type {:?} with a floating point number",
+                        other
+                    ))
+                }
+            };
+            operands.push(self.cast_int_to_f64(value, &ty));
+        }
+        unsafe {
+            let cmp = LLVMBuildFCmp(
+                self.builder,
+                op,
+                operands[0],
+                operands[1],
+                cstr_from_string("result").as_ptr(),
+            );
+            let alloca = self.build_alloca_store(cmp, int1_type(), "bool_cmp");
+            Ok(Box::new(BoolType {
+                name: lhs.get_name_as_str().to_string(),
+                builder: self.builder,
+                llvm_value: cmp,
+                llvm_value_pointer: Some(alloca),
+            }))
+        }
+    }
+
     pub fn llvm_build_fn(&self, lhs: LLVMValueRef, rhs: LLVMValueRef, op: String) -> LLVMValueRef {
         unsafe {
             match op.as_str() {
@@ -789,6 +1401,15 @@ impl LLVMCodegenBuilder {
                         cstr_from_string("mulNumberType").as_ptr()
                     )
                 }
+                "%" => {
+                    llvm_build_fn!(
+                        LLVMBuildSRem,
+                        self.builder,
+                        lhs,
+                        rhs,
+                        cstr_from_string("remNumberType").as_ptr()
+                    )
+                }
                 _ => {
                     unreachable!()
                 }
@@ -796,11 +1417,263 @@ impl LLVMCodegenBuilder {
         }
     }
 
-    pub fn arithmetic_v2(
+    pub fn llvm_build_float_fn(
         &self,
+        lhs: LLVMValueRef,
+        rhs: LLVMValueRef,
+        op: &str,
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            let build_fn = match op {
+                "+" => LLVMBuildFAdd,
+                "-" => LLVMBuildFSub,
+                "*" => LLVMBuildFMul,
+                "/" => LLVMBuildFDiv,
+                "%" => LLVMBuildFRem,
+                _ => return Err(anyhow!("Operator {} is not supported for f64", op)),
+            };
+            Ok(llvm_build_fn!(
+                build_fn,
+                self.builder,
+                lhs,
+                rhs,
+                cstr_from_string("floatNumberType").as_ptr()
+            ))
+        }
+    }
+
+    /// Builds an integer operation on two operands of the same width. `/` and
+    /// `%` panic at runtime on a zero divisor or `MIN / -1`, and `^` calls the
+    /// runtime's exponentiation helper, which panics on a negative exponent.
+    /// With checked arithmetic enabled, overflow panics instead of wrapping.
+    pub fn build_int_arithmetic(
+        &mut self,
+        lhs: LLVMValueRef,
+        rhs: LLVMValueRef,
+        op: &str,
+        span: Span,
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            match op {
+                "+" | "-" | "*" if self.checked_arithmetic => {
+                    let (intrinsic, message) = match op {
+                        "+" => ("sadd", "attempt to add with overflow"),
+                        "-" => ("ssub", "attempt to subtract with overflow"),
+                        _ => ("smul", "attempt to multiply with overflow"),
+                    };
+                    self.build_overflow_checked(intrinsic, lhs, rhs, message, span)
+                }
+                "/" | "%" => {
+                    let int_type = LLVMTypeOf(rhs);
+                    let zero = self.const_int(int_type, 0, 0);
+                    let is_zero = self.build_int_eq(rhs, zero, "is_zero");
+                    let (zero_message, overflow_message) = if op == "/" {
+                        (
+                            "attempt to divide by zero",
+                            "attempt to divide with overflow",
+                        )
+                    } else {
+                        (
+                            "attempt to calculate the remainder with a divisor of zero",
+                            "attempt to calculate the remainder with overflow",
+                        )
+                    };
+                    self.build_panic_if(is_zero, zero_message, span)?;
+                    // MIN / -1 is the only overflowing case. LLVM leaves it
+                    // undefined, so it panics even without checked arithmetic.
+                    let width = LLVMGetIntTypeWidth(int_type);
+                    let min = self.const_int(int_type, 1 << (width - 1), 0);
+                    let minus_one = self.const_int(int_type, u64::MAX, 1);
+                    let is_min = self.build_int_eq(lhs, min, "is_min");
+                    let is_minus_one = self.build_int_eq(rhs, minus_one, "is_minus_one");
+                    let overflows = LLVMBuildAnd(
+                        self.builder,
+                        is_min,
+                        is_minus_one,
+                        cstr_from_string("overflows").as_ptr(),
+                    );
+                    self.build_panic_if(overflows, overflow_message, span)?;
+                    Ok(self.llvm_build_fn(lhs, rhs, op.to_string()))
+                }
+                "^" => {
+                    let width = LLVMGetIntTypeWidth(LLVMTypeOf(lhs));
+                    let pow_func_name = match (width, self.checked_arithmetic) {
+                        (64, true) => "checkedPowInt64",
+                        (64, false) => "powInt64",
+                        (_, true) => "checkedPowInt32",
+                        (_, false) => "powInt32",
+                    };
+                    let pow_func = self
+                        .llvm_func_cache
+                        .get(pow_func_name)
+                        .ok_or_else(|| anyhow!("{} function not found", pow_func_name))?;
+                    let location = self.build_source_location(span);
+                    Ok(self.build_call(pow_func, vec![lhs, rhs, location], 3, "pow"))
+                }
+                _ => Ok(self.llvm_build_fn(lhs, rhs, op.to_string())),
+            }
+        }
+    }
+
+    /// Negates an integer, panicking on overflow when checked arithmetic is
+    /// enabled.
+    pub fn build_int_neg(&mut self, value: LLVMValueRef, span: Span) -> Result<LLVMValueRef> {
+        if !self.checked_arithmetic {
+            return Ok(self.build_neg(value, &ResolvedType::I32));
+        }
+        let zero = self.const_int(unsafe { LLVMTypeOf(value) }, 0, 0);
+        self.build_overflow_checked("ssub", zero, value, "attempt to negate with overflow", span)
+    }
+
+    /// Calls `llvm.<intrinsic>.with.overflow` and panics with `message` if the
+    /// overflow bit is set, returning the result value otherwise.
+    fn build_overflow_checked(
+        &mut self,
+        intrinsic: &str,
+        lhs: LLVMValueRef,
+        rhs: LLVMValueRef,
+        message: &str,
+        span: Span,
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            let intrinsic_name = format!("llvm.{}.with.overflow", intrinsic);
+            let intrinsic_id =
+                LLVMLookupIntrinsicID(intrinsic_name.as_ptr() as *const _, intrinsic_name.len());
+            let mut param_types = [LLVMTypeOf(lhs)];
+            let intrinsic_fn = LLVMCallFn {
+                function: LLVMGetIntrinsicDeclaration(
+                    self.module,
+                    intrinsic_id,
+                    param_types.as_mut_ptr(),
+                    1,
+                ),
+                func_type: LLVMIntrinsicGetType(
+                    self.context,
+                    intrinsic_id,
+                    param_types.as_mut_ptr(),
+                    1,
+                ),
+            };
+            let result = self.build_call(intrinsic_fn, vec![lhs, rhs], 2, intrinsic);
+            let value =
+                LLVMBuildExtractValue(self.builder, result, 0, cstr_from_string("value").as_ptr());
+            let overflow = LLVMBuildExtractValue(
+                self.builder,
+                result,
+                1,
+                cstr_from_string("overflow").as_ptr(),
+            );
+            self.build_panic_if(overflow, message, span)?;
+            Ok(value)
+        }
+    }
+
+    pub fn build_int_eq(&self, lhs: LLVMValueRef, rhs: LLVMValueRef, name: &str) -> LLVMValueRef {
+        unsafe {
+            LLVMBuildICmp(
+                self.builder,
+                LLVMIntEQ,
+                lhs,
+                rhs,
+                cstr_from_string(name).as_ptr(),
+            )
+        }
+    }
+
+    pub fn build_is_null(&self, value: LLVMValueRef, name: &str) -> LLVMValueRef {
+        unsafe { LLVMBuildIsNull(self.builder, value, cstr_from_string(name).as_ptr()) }
+    }
+
+    /// Builds a global string holding `file:line:column` for `span`, passed to
+    /// the runtime so panics can point back at the source.
+    pub fn build_source_location(&self, span: Span) -> LLVMValueRef {
+        let location = match &self.source {
+            Some(source) => source.location(span),
+            None => "<unknown>".to_string(),
+        };
+        unsafe {
+            LLVMBuildGlobalString(
+                self.builder,
+                cstr_from_string(&location).as_ptr(),
+                cstr_from_string("panic_location").as_ptr(),
+            )
+        }
+    }
+
+    /// Calls the runtime panic handler with `message` and the location of
+    /// `span` when `cond` is true, leaving the builder positioned in the block
+    /// where execution continues.
+    pub fn build_panic_if(&mut self, cond: LLVMValueRef, message: &str, span: Span) -> Result<()> {
+        let message = unsafe {
+            LLVMBuildGlobalString(
+                self.builder,
+                cstr_from_string(message).as_ptr(),
+                cstr_from_string("panic_message").as_ptr(),
+            )
+        };
+        let location = self.build_source_location(span);
+        self.build_call_and_abort_if(cond, "cyclangPanic", vec![message, location])
+    }
+
+    /// Panics with the index and length when `index` is outside `0..length`.
+    pub fn build_bounds_check(
+        &mut self,
+        index: LLVMValueRef,
+        length: LLVMValueRef,
+        span: Span,
+    ) -> Result<()> {
+        // Negative indices wrap to large unsigned values, so one compare covers both ends
+        let out_of_bounds = unsafe {
+            LLVMBuildICmp(
+                self.builder,
+                LLVMIntUGE,
+                index,
+                length,
+                cstr_from_string("out_of_bounds").as_ptr(),
+            )
+        };
+        let location = self.build_source_location(span);
+        self.build_call_and_abort_if(
+            out_of_bounds,
+            "cyclangIndexPanic",
+            vec![index, length, location],
+        )
+    }
+
+    /// Branches to a block that calls the runtime function `func_name`, which
+    /// never returns, when `cond` is true.
+    fn build_call_and_abort_if(
+        &mut self,
+        cond: LLVMValueRef,
+        func_name: &str,
+        args: Vec<LLVMValueRef>,
+    ) -> Result<()> {
+        let panic_func = self
+            .llvm_func_cache
+            .get(func_name)
+            .ok_or_else(|| anyhow!("{} function not found", func_name))?;
+        let function = self.current_function.function;
+        let panic_block = self.append_basic_block(function, "panic");
+        let continue_block = self.append_basic_block(function, "no_panic");
+        self.build_cond_br(cond, panic_block, continue_block);
+
+        self.set_current_block(panic_block);
+        let num_args = args.len() as c_uint;
+        self.build_call(panic_func, args, num_args, "");
+        unsafe {
+            LLVMBuildUnreachable(self.builder);
+        }
+
+        self.set_current_block(continue_block);
+        Ok(())
+    }
+
+    pub fn arithmetic_v2(
+        &mut self,
         lhs: &GeneratedValue,
         rhs: &GeneratedValue,
         op: &String,
+        span: Span,
     ) -> Result<GeneratedValue> {
         match (&lhs.ty, &rhs.ty) {
             // String concatenation
@@ -811,14 +1684,29 @@ impl LLVMCodegenBuilder {
                         op
                     ));
                 }
+                let string_init_func = self
+                    .llvm_func_cache
+                    .get("stringInit")
+                    .ok_or_else(|| anyhow!("stringInit function not found"))?;
                 let add_string_func = self
                     .llvm_func_cache
                     .get("stringAdd")
                     .ok_or_else(|| anyhow!("stringAdd function not found"))?;
-                self.build_call(add_string_func, vec![lhs.value, rhs.value], 2, "");
+                // Copy both operands into a new string so neither is modified
+                let empty = unsafe {
+                    LLVMBuildGlobalString(
+                        self.builder,
+                        cstr_from_string("").as_ptr(),
+                        cstr_from_string("empty_string").as_ptr(),
+                    )
+                };
+                let result = self.build_call(string_init_func, vec![empty], 1, "string_concat");
+                for operand in [lhs.value, rhs.value] {
+                    self.build_call(add_string_func.clone(), vec![result, operand], 2, "");
+                }
                 Ok(GeneratedValue {
-                    value: lhs.value,
-                    pointer: lhs.pointer,
+                    value: result,
+                    pointer: Some(result),
                     ty: ResolvedType::String,
                 })
             }
@@ -832,20 +1720,10 @@ impl LLVMCodegenBuilder {
                         op
                     ));
                 }
-                let concat_func_name = match **lhs_inner {
-                    ResolvedType::I32 => "concatInt32List",
-                    ResolvedType::String => "concatStringList",
-                    _ => {
-                        return Err(anyhow!(
-                            "List concatenation not supported for type {:?}",
-                            lhs_inner
-                        ))
-                    }
-                };
                 let concat_func = self
                     .llvm_func_cache
-                    .get(concat_func_name)
-                    .ok_or_else(|| anyhow!("{} function not found", concat_func_name))?;
+                    .get("concatList")
+                    .ok_or_else(|| anyhow!("concatList function not found"))?;
                 let result =
                     self.build_call(concat_func, vec![lhs.value, rhs.value], 2, "list_concat");
                 Ok(GeneratedValue {
@@ -861,7 +1739,7 @@ impl LLVMCodegenBuilder {
                     let mut rhs_val = self.build_load(rhs_ptr, int32_type(), "rhs");
                     lhs_val = self.cast_i32_to_i64(lhs_val, rhs_val);
                     rhs_val = self.cast_i32_to_i64(rhs_val, lhs_val);
-                    let result = self.llvm_build_fn(lhs_val, rhs_val, op.to_string());
+                    let result = self.build_int_arithmetic(lhs_val, rhs_val, op, span)?;
                     Ok(GeneratedValue {
                         value: result,
                         pointer: None,
@@ -873,7 +1751,7 @@ impl LLVMCodegenBuilder {
                     let mut rhs_val = rhs.value;
                     lhs_val = self.cast_i32_to_i64(lhs_val, rhs_val);
                     rhs_val = self.cast_i32_to_i64(rhs_val, lhs_val);
-                    let result = self.llvm_build_fn(lhs_val, rhs_val, op.to_string());
+                    let result = self.build_int_arithmetic(lhs_val, rhs_val, op, span)?;
                     Ok(GeneratedValue {
                         value: result,
                         pointer: None,
@@ -881,6 +1759,35 @@ impl LLVMCodegenBuilder {
                     })
                 }
             },
+            // Mixed width integer arithmetic widens to i64
+            (
+                ResolvedType::I32 | ResolvedType::I64,
+                ResolvedType::I32 | ResolvedType::I64,
+            ) => {
+                let lhs_val = self.cast_i32_to_i64(lhs.value, rhs.value);
+                let rhs_val = self.cast_i32_to_i64(rhs.value, lhs_val);
+                let result = self.build_int_arithmetic(lhs_val, rhs_val, op, span)?;
+                Ok(GeneratedValue {
+                    value: result,
+                    pointer: None,
+                    ty: ResolvedType::I64,
+                })
+            }
+            // Float arithmetic, promoting an integer operand to f64
+            (lhs_ty, rhs_ty)
+                if lhs_ty.is_numeric()
+                    && rhs_ty.is_numeric()
+                    && (*lhs_ty == ResolvedType::F64 || *rhs_ty == ResolvedType::F64) =>
+            {
+                let lhs_val = self.cast_int_to_f64(lhs.value, lhs_ty);
+                let rhs_val = self.cast_int_to_f64(rhs.value, rhs_ty);
+                let result = self.llvm_build_float_fn(lhs_val, rhs_val, op)?;
+                Ok(GeneratedValue {
+                    value: result,
+                    pointer: None,
+                    ty: ResolvedType::F64,
+                })
+            }
             _ => Err(anyhow!(
                 "Unsupported arithmetic operation {} for types {:?} and {:?}",
                 op,
@@ -896,15 +1803,36 @@ impl LLVMCodegenBuilder {
         rhs: Box<dyn TypeBase>,
         op: String,
     ) -> Result<Box<dyn TypeBase>> {
+        if lhs.get_type() == BaseTypes::Float64 || rhs.get_type() == BaseTypes::Float64 {
+            return match op.as_str() {
+                "==" => self.fcmp(lhs, rhs, LLVMRealOEQ),
+                "!=" => self.fcmp(lhs, rhs, LLVMRealUNE),
+                "<" => self.fcmp(lhs, rhs, LLVMRealOLT),
+                "<=" => self.fcmp(lhs, rhs, LLVMRealOLE),
+                ">" => self.fcmp(lhs, rhs, LLVMRealOGT),
+                ">=" => self.fcmp(lhs, rhs, LLVMRealOGE),
+                _ => Err(anyhow!("Operator {} is not a comparison", op)),
+            };
+        }
         match rhs.get_type() {
             BaseTypes::String => {
+                if op != "==" && op != "!=" {
+                    return Err(anyhow!("Strings can only be compared with == and !="));
+                }
                 let is_string_equal_func = self
                     .llvm_func_cache
                     .get("isStringEqual")
                     .ok_or(anyhow!("unable to get function isStringEqual"))?;
-                let is_string_equal_args = vec![lhs.get_ptr().unwrap(), rhs.get_ptr().unwrap()];
+                let (Some(lhs_ptr), Some(rhs_ptr)) = (lhs.get_ptr(), rhs.get_ptr()) else {
+                    return Err(anyhow!("Can't compare strings without their pointers"));
+                };
+                let is_string_equal_args = vec![lhs_ptr, rhs_ptr];
 
-                let bool_value = self.build_call(is_string_equal_func, is_string_equal_args, 2, "");
+                let mut bool_value =
+                    self.build_call(is_string_equal_func, is_string_equal_args, 2, "");
+                if op == "!=" {
+                    bool_value = self.build_not(bool_value);
+                }
                 let alloca = self.build_alloca_store(bool_value, int1_type(), "");
                 return Ok(Box::new(BoolType {
                     name: "bool_type".to_string(),
@@ -913,13 +1841,13 @@ impl LLVMCodegenBuilder {
                     llvm_value_pointer: Some(alloca),
                 }));
             }
-            BaseTypes::Number | BaseTypes::Bool => {}
+            BaseTypes::Number | BaseTypes::Number64 | BaseTypes::Bool => {}
             _ => {
-                unreachable!(
-                    "Can't do operation type {:?} and type {:?}",
+                return Err(anyhow!(
+                    "Can't compare type {:?} and type {:?}",
                     lhs.get_type(),
                     rhs.get_type()
-                )
+                ))
             }
         }
         match op.as_str() {
@@ -929,9 +1857,7 @@ impl LLVMCodegenBuilder {
             "<=" => self.icmp(lhs, rhs, LLVMIntSLE),
             ">" => self.icmp(lhs, rhs, LLVMIntSGT),
             ">=" => self.icmp(lhs, rhs, LLVMIntSGE),
-            _ => {
-                unimplemented!()
-            }
+            _ => Err(anyhow!("Operator {} is not a comparison", op)),
         }
     }
 
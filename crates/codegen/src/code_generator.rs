@@ -1,12 +1,14 @@
 use crate::builder::LLVMCodegenBuilder;
+use crate::context::{LLVMCallFn, LLVMFunction};
 use crate::{
-    cstr_from_string, int1_type, int32_type, int64_type, int8_ptr_type,
+    cstr_from_string, double_type, int1_type, int32_type, int64_type, int8_ptr_type,
 };
-use crate::typed_ast::{ResolvedType, TypedExpression};
+use crate::typed_ast::{ListOp, MapOp, ResolvedType, TypedExpression};
+use parser::{SourceFile, Span};
 use anyhow::{anyhow, Result};
 use llvm_sys::core::{
-    LLVMAddFunction, LLVMConstStringInContext2, LLVMFunctionType, LLVMGetNamedFunction,
-    LLVMGetParam, LLVMVoidType,
+    LLVMAddFunction, LLVMConstNull, LLVMConstStringInContext2, LLVMFunctionType,
+    LLVMGetNamedFunction, LLVMGetParam, LLVMTypeOf, LLVMVoidType,
 };
 use llvm_sys::prelude::{LLVMBasicBlockRef, LLVMTypeRef, LLVMValueRef};
 use std::collections::HashMap;
@@ -22,11 +24,26 @@ pub struct CodeGenerator<'a> {
     function_cache: HashMap<String, FunctionInfo>,
     // Track local variables by depth for scoping
     locals: HashMap<i32, Vec<String>>,
+    // Outer variables hidden by a declaration at each depth, restored when it ends
+    shadowed: HashMap<i32, Vec<(String, GeneratedValue)>>,
     depth: i32,
     // Track the previous function when entering a new function
     previous_block: Option<LLVMBasicBlockRef>,
-    // Track loop break targets (innermost last)
-    loop_break_stack: Vec<LLVMBasicBlockRef>,
+    // Track the enclosing loops' break and continue targets (innermost last)
+    loop_stack: Vec<LoopTargets>,
+    // Struct name -> field names and types, in declaration order
+    struct_fields: HashMap<String, Vec<(String, ResolvedType)>>,
+    // Enum name -> payload types of each variant, in tag order
+    enum_variants: HashMap<String, Vec<Vec<ResolvedType>>>,
+    // Number of lambdas generated so far, used to name their functions
+    lambda_count: u32,
+}
+
+// Where `break` and `continue` jump to for one loop
+struct LoopTargets {
+    label: Option<String>,
+    break_block: LLVMBasicBlockRef,
+    continue_block: LLVMBasicBlockRef,
 }
 
 #[derive(Clone)]
@@ -49,18 +66,31 @@ impl<'a> CodeGenerator<'a> {
             symbol_table: HashMap::new(),
             function_cache: HashMap::new(),
             locals: HashMap::new(),
+            shadowed: HashMap::new(),
             depth: 0,
             previous_block: None,
-            loop_break_stack: Vec::new(),
+            loop_stack: Vec::new(),
+            struct_fields: HashMap::new(),
+            enum_variants: HashMap::new(),
+            lambda_count: 0,
         }
     }
 
+    /// Sets the file the expressions generated next came from.
+    pub fn set_source(&mut self, source: Option<SourceFile>) {
+        self.builder.set_source(source);
+    }
+
     fn set_variable(&mut self, name: &str, value: GeneratedValue) {
-        self.symbol_table.insert(name.to_string(), value);
-        self.locals
-            .entry(self.depth)
-            .or_default()
-            .push(name.to_string());
+        let outer = self.symbol_table.insert(name.to_string(), value);
+        let locals = self.locals.entry(self.depth).or_default();
+        if let Some(outer) = outer.filter(|_| !locals.iter().any(|var| var == name)) {
+            self.shadowed
+                .entry(self.depth)
+                .or_default()
+                .push((name.to_string(), outer));
+        }
+        locals.push(name.to_string());
     }
 
     fn get_variable(&self, name: &str) -> Option<&GeneratedValue> {
@@ -77,6 +107,9 @@ impl<'a> CodeGenerator<'a> {
                 self.symbol_table.remove(&var);
             }
         }
+        if let Some(outer) = self.shadowed.remove(&self.depth) {
+            self.symbol_table.extend(outer);
+        }
         self.depth -= 1;
     }
 
@@ -84,60 +117,172 @@ impl<'a> CodeGenerator<'a> {
         match typed_expr {
             TypedExpression::Number32 { value, .. } => self.generate_i32(*value),
             TypedExpression::Number64 { value, .. } => self.generate_i64(*value),
-            TypedExpression::String { value } => self.generate_string(value),
-            TypedExpression::Bool { value } => self.generate_bool(*value),
-            TypedExpression::Binary { left, op, right } => self.generate_binary(left, op, right),
-            TypedExpression::CallStmt { callee, args } => self.generate_call(callee, args),
+            TypedExpression::Float64 { value, .. } => self.generate_f64(*value),
+            TypedExpression::String { value, .. } => self.generate_string(value),
+            TypedExpression::Bool { value, .. } => self.generate_bool(*value),
+            TypedExpression::Binary {
+                left,
+                op,
+                right,
+                span,
+            } => self.generate_binary(left, op, right, *span),
+            TypedExpression::Unary { op, operand, span } => {
+                self.generate_unary(op, operand, *span)
+            }
+            TypedExpression::CallStmt { callee, args, .. } => self.generate_call(callee, args),
             TypedExpression::FuncStmt {
                 name,
                 args,
                 return_type,
                 body,
+                ..
             } => self.generate_function(name, args, return_type, body),
             TypedExpression::ExternFuncStmt {
                 name,
                 args,
                 return_type,
+                ..
             } => self.generate_extern_function(name, args, return_type),
-            TypedExpression::ExternModule { path: _ } => Ok(GeneratedValue {
+            TypedExpression::ExternModule { path: _, .. } => Ok(GeneratedValue {
                 value: ptr::null_mut(),
                 pointer: None,
                 ty: ResolvedType::Void,
             }),
-            TypedExpression::BlockStmt { statements } => self.generate_block(statements),
-            TypedExpression::Variable { name } => self.generate_variable(name),
-            TypedExpression::Print { value } => self.generate_print(value),
-            TypedExpression::ReturnStmt { value } => self.generate_return(value),
-            TypedExpression::BreakStmt => self.generate_break(),
+            TypedExpression::BlockStmt { statements, .. } => self.generate_block(statements),
+            TypedExpression::Variable { name, .. } => self.generate_variable(name),
+            TypedExpression::Print { value, .. } => self.generate_print(value),
+            TypedExpression::ReturnStmt { value, .. } => self.generate_return(value),
+            TypedExpression::BreakStmt { label, .. } => self.generate_loop_jump(label, true),
+            TypedExpression::ContinueStmt { label, .. } => self.generate_loop_jump(label, false),
             TypedExpression::LetStmt {
                 name,
                 var_type,
                 value,
+                ..
             } => self.generate_let_stmt(name, var_type, value),
             TypedExpression::IfStmt {
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => self.generate_if(
                 condition,
                 then_branch,
                 else_branch.as_ref().map(|b| b.as_ref()),
             ),
-            TypedExpression::WhileStmt { condition, body } => self.generate_while(condition, body),
-            TypedExpression::AssignStmt { name, value } => self.generate_assign(name, value),
-            TypedExpression::Grouping { inner } => {
+            TypedExpression::WhileStmt {
+                condition,
+                body,
+                step,
+                label,
+                ..
+            } => self.generate_while(condition, body, step.as_deref(), label),
+            TypedExpression::AssignStmt { name, value, .. } => self.generate_assign(name, value),
+            TypedExpression::Grouping { inner, .. } => {
                 // Grouping just generates the inner expression
                 self.generate_expression(inner)
             }
             TypedExpression::List {
                 elements,
                 element_type,
+                ..
             } => self.generate_list(elements, element_type),
-            TypedExpression::ListIndex { list, index } => self.generate_list_index(list, index),
-            TypedExpression::ListAssign { name, index, value } => {
-                self.generate_list_assign(name, index, value)
+            TypedExpression::ListIndex { list, index, span } => {
+                self.generate_list_index(list, index, *span)
+            }
+            TypedExpression::ListAssign {
+                name,
+                index,
+                value,
+                span,
+            } => self.generate_list_assign(name, index, value, *span),
+            TypedExpression::Len { value, .. } => self.generate_len(value),
+            TypedExpression::ListCall {
+                op,
+                list,
+                args,
+                span,
+            } => self.generate_list_call(*op, list, args, *span),
+            TypedExpression::ListSlice {
+                list,
+                start,
+                end,
+                span,
+            } => self.generate_list_slice(list, start.as_deref(), end.as_deref(), *span),
+            TypedExpression::Map {
+                entries,
+                key_type,
+                value_type,
+                ..
+            } => self.generate_map(entries, key_type, value_type),
+            TypedExpression::MapIndex { map, key, span } => {
+                self.generate_map_index(map, key, *span)
+            }
+            TypedExpression::MapAssign {
+                name, key, value, ..
+            } => self.generate_map_assign(name, key, value),
+            TypedExpression::MapCall {
+                op,
+                map,
+                args,
+                span,
+            } => self.generate_map_call(*op, map, args, *span),
+            TypedExpression::IterItems { value, .. } => self.generate_iter_items(value),
+            TypedExpression::StructStmt { name, fields, .. } => {
+                self.generate_struct_stmt(name, fields)
+            }
+            TypedExpression::StructLiteral { name, fields, .. } => {
+                self.generate_struct_literal(name, fields)
             }
-            TypedExpression::Len { value } => self.generate_len(value),
+            TypedExpression::FieldAccess { value, index, .. } => {
+                self.generate_field_access(value, *index)
+            }
+            TypedExpression::FieldAssign {
+                target,
+                index,
+                value,
+                ..
+            } => self.generate_field_assign(target, *index, value),
+            TypedExpression::ImplStmt { methods, .. } => self.generate_impl(methods),
+            TypedExpression::EnumStmt { name, variants, .. } => {
+                self.generate_enum_stmt(name, variants)
+            }
+            TypedExpression::EnumValue {
+                enum_name,
+                tag,
+                values,
+                ..
+            } => self.generate_enum_value(enum_name, *tag, values),
+            TypedExpression::MatchStmt {
+                value,
+                enum_name,
+                arms,
+                ..
+            } => self.generate_match(value, enum_name, arms),
+            TypedExpression::MatchArm { .. } => Err(anyhow!("match arm outside of a match")),
+            TypedExpression::OptionalValue {
+                value, value_type, ..
+            } => self.generate_optional_value(value.as_deref(), value_type),
+            TypedExpression::NilCheck { value, is_nil, .. } => {
+                self.generate_nil_check(value, *is_nil)
+            }
+            TypedExpression::Unwrap { value, span } => self.generate_unwrap(value, *span),
+            TypedExpression::Lambda {
+                params,
+                return_type,
+                captures,
+                body,
+                ..
+            } => self.generate_lambda(params, return_type, captures, body),
+            TypedExpression::FunctionRef {
+                name,
+                args,
+                return_type,
+                ..
+            } => self.generate_function_ref(name, args, return_type),
+            TypedExpression::Cast {
+                value, from, to, ..
+            } => self.generate_cast(value, from, to),
         }
     }
 
@@ -165,11 +310,39 @@ impl<'a> CodeGenerator<'a> {
         })
     }
 
+    fn generate_f64(&mut self, value: f64) -> Result<GeneratedValue> {
+        let llvm_value = self.builder.const_real(double_type(), value);
+
+        Ok(GeneratedValue {
+            value: llvm_value,
+            pointer: None,
+            ty: ResolvedType::F64,
+        })
+    }
+
+    fn generate_cast(
+        &mut self,
+        value: &TypedExpression,
+        from: &ResolvedType,
+        to: &ResolvedType,
+    ) -> Result<GeneratedValue> {
+        let generated_value = self.generate_expression(value)?;
+        let llvm_value = self
+            .builder
+            .build_numeric_cast(generated_value.value, from, to)?;
+
+        Ok(GeneratedValue {
+            value: llvm_value,
+            pointer: None,
+            ty: to.clone(),
+        })
+    }
+
     fn generate_string(&mut self, value: &str) -> Result<GeneratedValue> {
         unsafe {
             // Remove quotes from string
             let val = value.replace('"', "");
-            let string = CString::new(val.clone()).unwrap();
+            let string = CString::new(val.clone())?;
 
             // Create LLVM constant string
             let llvm_string = LLVMConstStringInContext2(
@@ -179,10 +352,10 @@ impl<'a> CodeGenerator<'a> {
                 0,
             );
 
-            // Allocate and store the string
+            // Allocate and store the string, in a slot as long as its bytes
             let string_ptr =
                 self.builder
-                    .build_alloca_store(llvm_string, int8_ptr_type(), "string_ptr");
+                    .build_alloca_store(llvm_string, LLVMTypeOf(llvm_string), "string_ptr");
 
             // Call stringInit helper function to create a proper string object
             let string_init_func = self
@@ -218,17 +391,20 @@ impl<'a> CodeGenerator<'a> {
         left: &TypedExpression,
         op: &String,
         right: &TypedExpression,
+        span: Span,
     ) -> Result<GeneratedValue> {
+        if op == "&&" || op == "||" {
+            return self.generate_logical(left, op, right);
+        }
         let lhs = self.generate_expression(left)?;
         let rhs = self.generate_expression(right)?;
         match op.as_str() {
-            "+" | "-" | "/" | "*" => self.builder.arithmetic_v2(&lhs, &rhs, op),
-            "^" => Err(anyhow!("^ is not implemented yet")),
+            "+" | "-" | "/" | "*" | "%" | "^" => self.builder.arithmetic_v2(&lhs, &rhs, op, span),
             "==" | "!=" | "<" | "<=" | ">" | ">=" => {
                 // Use the existing cmp method from builder
                 // Convert GeneratedValue back to TypeBase for compatibility
                 use crate::types::bool::BoolType;
-                use crate::types::num::NumberType;
+                use crate::types::num::{FloatType, Number64Type, NumberType};
                 use crate::types::string::StringType;
                 use crate::types::TypeBase;
 
@@ -238,6 +414,16 @@ impl<'a> CodeGenerator<'a> {
                         llvm_value: lhs.value,
                         llvm_value_pointer: lhs.pointer,
                     }),
+                    ResolvedType::I64 => Box::new(Number64Type {
+                        name: "lhs".to_string(),
+                        llvm_value: lhs.value,
+                        llvm_value_pointer: lhs.pointer,
+                    }),
+                    ResolvedType::F64 => Box::new(FloatType {
+                        name: "lhs".to_string(),
+                        llvm_value: lhs.value,
+                        llvm_value_pointer: lhs.pointer,
+                    }),
                     ResolvedType::Bool => Box::new(BoolType {
                         name: "lhs".to_string(),
                         builder: self.builder.builder,
@@ -258,6 +444,16 @@ impl<'a> CodeGenerator<'a> {
                         llvm_value: rhs.value,
                         llvm_value_pointer: rhs.pointer,
                     }),
+                    ResolvedType::I64 => Box::new(Number64Type {
+                        name: "rhs".to_string(),
+                        llvm_value: rhs.value,
+                        llvm_value_pointer: rhs.pointer,
+                    }),
+                    ResolvedType::F64 => Box::new(FloatType {
+                        name: "rhs".to_string(),
+                        llvm_value: rhs.value,
+                        llvm_value_pointer: rhs.pointer,
+                    }),
                     ResolvedType::Bool => Box::new(BoolType {
                         name: "rhs".to_string(),
                         builder: self.builder.builder,
@@ -284,6 +480,85 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
+    fn load_bool(&self, value: &GeneratedValue, name: &str) -> LLVMValueRef {
+        match value.pointer {
+            Some(ptr) => self.builder.build_load(ptr, int1_type(), name),
+            None => value.value,
+        }
+    }
+
+    // The right hand side only runs when the left doesn't already decide the
+    // result, so `a && b` skips `b` when `a` is false and `a || b` skips it
+    // when `a` is true.
+    fn generate_logical(
+        &mut self,
+        left: &TypedExpression,
+        op: &str,
+        right: &TypedExpression,
+    ) -> Result<GeneratedValue> {
+        let function = self.builder.current_function.function;
+        let lhs = self.generate_expression(left)?;
+        let lhs_val = self.load_bool(&lhs, "lhs");
+        let lhs_end_block = self.builder.current_function.block;
+
+        let rhs_block = self.builder.append_basic_block(function, "logical_rhs");
+        let merge_block = self.builder.append_basic_block(function, "logical_merge");
+        let short_circuit = op == "||";
+        if short_circuit {
+            self.builder.build_cond_br(lhs_val, merge_block, rhs_block);
+        } else {
+            self.builder.build_cond_br(lhs_val, rhs_block, merge_block);
+        }
+
+        self.builder.set_current_block(rhs_block);
+        let rhs = self.generate_expression(right)?;
+        let rhs_val = self.load_bool(&rhs, "rhs");
+        let rhs_end_block = self.builder.current_function.block;
+        self.builder.build_br(merge_block);
+
+        self.builder.set_current_block(merge_block);
+        let short_circuit_val = self
+            .builder
+            .const_int(int1_type(), short_circuit as c_ulonglong, 0);
+        let result = self.builder.build_phi(
+            int1_type(),
+            &[(short_circuit_val, lhs_end_block), (rhs_val, rhs_end_block)],
+            "logical_result",
+        );
+
+        Ok(GeneratedValue {
+            value: result,
+            pointer: None,
+            ty: ResolvedType::Bool,
+        })
+    }
+
+    fn generate_unary(
+        &mut self,
+        op: &str,
+        operand: &TypedExpression,
+        span: Span,
+    ) -> Result<GeneratedValue> {
+        let value = self.generate_expression(operand)?;
+        let result = match (op, &value.ty) {
+            ("!", ResolvedType::Bool) => {
+                let bool_val = self.load_bool(&value, "operand");
+                self.builder.build_not(bool_val)
+            }
+            ("-", ResolvedType::I32 | ResolvedType::I64) => {
+                self.builder.build_int_neg(value.value, span)?
+            }
+            ("-", ty) if ty.is_numeric() => self.builder.build_neg(value.value, ty),
+            _ => return Err(anyhow!("Unary operator {} not implemented for {}", op, value.ty)),
+        };
+
+        Ok(GeneratedValue {
+            value: result,
+            pointer: None,
+            ty: value.ty,
+        })
+    }
+
     fn generate_call(
         &mut self,
         callee: &TypedExpression,
@@ -291,10 +566,24 @@ impl<'a> CodeGenerator<'a> {
     ) -> Result<GeneratedValue> {
         // Get the function name from the callee (should be a Variable)
         let func_name = match callee {
-            TypedExpression::Variable { name } => name,
+            TypedExpression::Variable { name, .. } => name,
             _ => return Err(anyhow!("Callee must be a variable (function name)")),
         };
 
+        // A variable holding a function or closure is called through it
+        if let Some(GeneratedValue {
+            ty: ResolvedType::Function(..),
+            ..
+        }) = self.get_variable(func_name)
+        {
+            let closure = self.generate_variable(func_name)?;
+            let mut arg_values = Vec::new();
+            for arg in args {
+                arg_values.push(self.generate_expression(arg)?.value);
+            }
+            return self.build_closure_call(&closure, arg_values);
+        }
+
         // Look up the function in the cache
         let func_info = self
             .function_cache
@@ -335,13 +624,14 @@ impl<'a> CodeGenerator<'a> {
         })
     }
 
-    fn resolved_type_to_llvm(&self, ty: &ResolvedType) -> LLVMTypeRef {
+    fn resolved_type_to_llvm(&self, ty: &ResolvedType) -> Result<LLVMTypeRef> {
         unsafe {
             use llvm_sys::core::{LLVMGetTypeByName2, LLVMPointerType};
 
-            match ty {
+            let llvm_type = match ty {
                 ResolvedType::I32 => int32_type(),
                 ResolvedType::I64 => int64_type(),
+                ResolvedType::F64 => double_type(),
                 ResolvedType::Bool => int1_type(),
                 ResolvedType::Void => LLVMVoidType(),
                 ResolvedType::String => {
@@ -353,12 +643,22 @@ impl<'a> CodeGenerator<'a> {
                     LLVMPointerType(string_type, 0)
                 }
                 ResolvedType::List(_inner) => {
-                    // Lists are represented as pointers (i32* for i32 lists, struct.StringType** for string lists)
-                    // We'll use int8_ptr_type() as a generic pointer type since lists are opaque pointers
+                    // Lists are pointers to the runtime's List struct, see list_struct_type
                     int8_ptr_type()
                 }
-                _ => unimplemented!("Type conversion not implemented for {:?}", ty),
-            }
+                // Maps are pointers to the runtime's Map struct
+                ResolvedType::Map(..) => int8_ptr_type(),
+                // Structs and enums are passed and stored by value
+                ResolvedType::Struct(name) | ResolvedType::Enum(name) => {
+                    self.builder.struct_type(name)?
+                }
+                ResolvedType::Optional(inner) => self
+                    .builder
+                    .optional_type(self.resolved_type_to_llvm(inner)?),
+                ResolvedType::Function(..) => self.builder.closure_type(),
+                _ => return Err(anyhow!("{:?} has no LLVM type", ty)),
+            };
+            Ok(llvm_type)
         }
     }
 
@@ -374,10 +674,10 @@ impl<'a> CodeGenerator<'a> {
             let mut param_types: Vec<LLVMTypeRef> = args
                 .iter()
                 .map(|(_, ty)| self.resolved_type_to_llvm(ty))
-                .collect();
+                .collect::<Result<_>>()?;
 
             // 2. Create function type
-            let ret_type = self.resolved_type_to_llvm(return_type);
+            let ret_type = self.resolved_type_to_llvm(return_type)?;
             let function_type =
                 LLVMFunctionType(ret_type, param_types.as_mut_ptr(), args.len() as u32, 0);
 
@@ -420,7 +720,7 @@ impl<'a> CodeGenerator<'a> {
                 let param_value = LLVMGetParam(function, i as u32);
                 let param_ptr = self.builder.build_alloca_store(
                     param_value,
-                    self.resolved_type_to_llvm(arg_type),
+                    self.resolved_type_to_llvm(arg_type)?,
                     &format!("{}_ptr", arg_name),
                 );
 
@@ -461,6 +761,252 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
+    /// Lambdas and the wrappers around named functions take the closure's
+    /// environment ahead of their own arguments.
+    fn closure_function_type(
+        &self,
+        args: &[ResolvedType],
+        return_type: &ResolvedType,
+    ) -> Result<LLVMTypeRef> {
+        let mut param_types: Vec<LLVMTypeRef> = std::iter::once(Ok(int8_ptr_type()))
+            .chain(args.iter().map(|ty| self.resolved_type_to_llvm(ty)))
+            .collect::<Result<_>>()?;
+        unsafe {
+            Ok(LLVMFunctionType(
+                self.resolved_type_to_llvm(return_type)?,
+                param_types.as_mut_ptr(),
+                param_types.len() as u32,
+                0,
+            ))
+        }
+    }
+
+    /// Calls the function held in `closure`, passing it the closure's environment.
+    fn build_closure_call(
+        &mut self,
+        closure: &GeneratedValue,
+        args: Vec<LLVMValueRef>,
+    ) -> Result<GeneratedValue> {
+        let ResolvedType::Function(arg_types, return_type) = &closure.ty else {
+            return Err(anyhow!("Cannot call a value of type {}", closure.ty));
+        };
+        let function = self
+            .builder
+            .build_extract_value(closure.value, 0, "closure_fn");
+        let env = self
+            .builder
+            .build_extract_value(closure.value, 1, "closure_env");
+        let func_type = self.closure_function_type(arg_types, return_type)?;
+        let call_args: Vec<LLVMValueRef> = std::iter::once(env).chain(args).collect();
+        let num_args = call_args.len() as u32;
+        let call_name = if **return_type == ResolvedType::Void {
+            ""
+        } else {
+            "closure_call"
+        };
+        let value = self.builder.build_call(
+            LLVMCallFn {
+                function,
+                func_type,
+            },
+            call_args,
+            num_args,
+            call_name,
+        );
+        Ok(GeneratedValue {
+            value,
+            pointer: None,
+            ty: return_type.as_ref().clone(),
+        })
+    }
+
+    /// Moves the builder into the entry block of `function`, returning the
+    /// function being generated before so `leave_function` can go back to it.
+    fn enter_function(&mut self, function: LLVMValueRef, func_type: LLVMTypeRef) -> LLVMFunction {
+        let entry_block = self.builder.append_basic_block(function, "entry");
+        let enclosing = self.builder.current_function.clone();
+        self.builder.current_function.function = function;
+        self.builder.current_function.func_type = func_type;
+        self.builder.current_function.entry_block = entry_block;
+        self.builder.set_current_block(entry_block);
+        enclosing
+    }
+
+    fn leave_function(&mut self, enclosing: LLVMFunction) {
+        let block = enclosing.block;
+        self.builder.current_function = enclosing;
+        self.builder.set_current_block(block);
+    }
+
+    /// Builds the closure for `|params| body`. The body becomes a function of its
+    /// own, and the variables it captures are copied into an environment on the
+    /// heap so the closure can outlive the scope that created it.
+    fn generate_lambda(
+        &mut self,
+        params: &[(String, ResolvedType)],
+        return_type: &ResolvedType,
+        captures: &[(String, ResolvedType)],
+        body: &TypedExpression,
+    ) -> Result<GeneratedValue> {
+        let mut capture_types: Vec<LLVMTypeRef> = captures
+            .iter()
+            .map(|(_, ty)| self.resolved_type_to_llvm(ty))
+            .collect::<Result<_>>()?;
+        let env_type = self.builder.variant_payload_type(&mut capture_types);
+        let env = if captures.is_empty() {
+            unsafe { LLVMConstNull(int8_ptr_type()) }
+        } else {
+            let env = self.builder.build_malloc(env_type, "closure_env");
+            for (i, (name, _)) in captures.iter().enumerate() {
+                let value = self.generate_variable(name)?.value;
+                let field_ptr = self.builder.build_struct_field_ptr(env_type, env, i as u32);
+                self.builder.build_store(value, field_ptr);
+            }
+            env
+        };
+
+        let arg_types: Vec<ResolvedType> = params.iter().map(|(_, ty)| ty.clone()).collect();
+        let func_type = self.closure_function_type(&arg_types, return_type)?;
+        let name = format!("lambda.{}", self.lambda_count);
+        self.lambda_count += 1;
+        let function = unsafe {
+            LLVMAddFunction(
+                self.builder.module,
+                cstr_from_string(&name).as_ptr(),
+                func_type,
+            )
+        };
+        let enclosing = self.enter_function(function, func_type);
+
+        // The body only sees its captures and parameters, and can't jump to
+        // the loops around the lambda
+        let enclosing_symbols = std::mem::take(&mut self.symbol_table);
+        let enclosing_loops = std::mem::take(&mut self.loop_stack);
+        self.incr_depth();
+        let env_value = unsafe {
+            self.builder
+                .build_load(LLVMGetParam(function, 0), env_type, "env")
+        };
+        let mut bindings = Vec::new();
+        for (i, (name, ty)) in captures.iter().enumerate() {
+            let value = self.builder.build_extract_value(env_value, i as u32, name);
+            bindings.push((name, ty, value));
+        }
+        for (i, (name, ty)) in params.iter().enumerate() {
+            bindings.push((name, ty, unsafe { LLVMGetParam(function, i as u32 + 1) }));
+        }
+        for (name, ty, value) in bindings {
+            let llvm_type = self.resolved_type_to_llvm(ty)?;
+            let ptr = self.builder.build_alloca_store(value, llvm_type, name);
+            self.set_variable(
+                name,
+                GeneratedValue {
+                    value,
+                    pointer: Some(ptr),
+                    ty: ty.clone(),
+                },
+            );
+        }
+
+        self.generate_expression(body)?;
+        if !self
+            .builder
+            .block_has_terminator(self.builder.current_function.block)
+        {
+            if *return_type == ResolvedType::Void {
+                self.builder.build_ret_void();
+            } else {
+                self.builder.build_unreachable();
+            }
+        }
+
+        self.decr_depth();
+        self.symbol_table = enclosing_symbols;
+        self.loop_stack = enclosing_loops;
+        self.leave_function(enclosing);
+
+        let closure = self
+            .builder
+            .build_struct_value(self.builder.closure_type(), &[function, env]);
+        Ok(GeneratedValue {
+            value: closure,
+            pointer: None,
+            ty: ResolvedType::Function(arg_types, Box::new(return_type.clone())),
+        })
+    }
+
+    /// A named function used as a value becomes a closure around a wrapper that
+    /// ignores the environment and forwards its arguments to the function.
+    fn generate_function_ref(
+        &mut self,
+        name: &str,
+        args: &[ResolvedType],
+        return_type: &ResolvedType,
+    ) -> Result<GeneratedValue> {
+        let wrapper_name = format!("{}.closure", name);
+        let wrapper = match self.function_cache.get(&wrapper_name) {
+            Some(wrapper) => wrapper.function,
+            None => {
+                let func_info = self
+                    .function_cache
+                    .get(name)
+                    .ok_or_else(|| anyhow!("Undefined function: {}", name))?
+                    .clone();
+                let func_type = self.closure_function_type(args, return_type)?;
+                let wrapper = unsafe {
+                    LLVMAddFunction(
+                        self.builder.module,
+                        cstr_from_string(&wrapper_name).as_ptr(),
+                        func_type,
+                    )
+                };
+                let enclosing = self.enter_function(wrapper, func_type);
+                let forwarded: Vec<LLVMValueRef> = (0..args.len())
+                    .map(|i| unsafe { LLVMGetParam(wrapper, i as u32 + 1) })
+                    .collect();
+                let call_name = if *return_type == ResolvedType::Void {
+                    ""
+                } else {
+                    "forward"
+                };
+                let result = self.builder.build_call(
+                    LLVMCallFn {
+                        function: func_info.function,
+                        func_type: func_info.func_type,
+                    },
+                    forwarded,
+                    args.len() as u32,
+                    call_name,
+                );
+                if *return_type == ResolvedType::Void {
+                    self.builder.build_ret_void();
+                } else {
+                    self.builder.build_ret(result);
+                }
+                self.leave_function(enclosing);
+                self.function_cache.insert(
+                    wrapper_name,
+                    FunctionInfo {
+                        function: wrapper,
+                        func_type,
+                        return_type: return_type.clone(),
+                    },
+                );
+                wrapper
+            }
+        };
+
+        let env = unsafe { LLVMConstNull(int8_ptr_type()) };
+        let closure = self
+            .builder
+            .build_struct_value(self.builder.closure_type(), &[wrapper, env]);
+        Ok(GeneratedValue {
+            value: closure,
+            pointer: None,
+            ty: ResolvedType::Function(args.to_vec(), Box::new(return_type.clone())),
+        })
+    }
+
     fn generate_extern_function(
         &mut self,
         name: &str,
@@ -471,9 +1017,9 @@ impl<'a> CodeGenerator<'a> {
             let mut param_types: Vec<LLVMTypeRef> = args
                 .iter()
                 .map(|(_, ty)| self.resolved_type_to_llvm(ty))
-                .collect();
+                .collect::<Result<_>>()?;
 
-            let ret_type = self.resolved_type_to_llvm(return_type);
+            let ret_type = self.resolved_type_to_llvm(return_type)?;
             let function_type =
                 LLVMFunctionType(ret_type, param_types.as_mut_ptr(), args.len() as u32, 0);
 
@@ -533,15 +1079,15 @@ impl<'a> CodeGenerator<'a> {
 
         // If the variable has a pointer, we need to load the current value from memory
         // This is important for variable reassignment to work correctly
-        // Exception: Lists and Strings are already pointers, so we don't load them
+        // Exception: Lists, Maps and Strings are already pointers, so we don't load them
         let current_value = match (&var.ty, var.pointer) {
-            (ResolvedType::List(_), _) | (ResolvedType::String, _) => {
-                // Lists and Strings are already pointer values, don't load
+            (ResolvedType::List(_), _) | (ResolvedType::Map(..), _) | (ResolvedType::String, _) => {
+                // Lists, Maps and Strings are already pointer values, don't load
                 var.value
             }
             (_, Some(ptr)) => {
                 // Load the current value from the pointer
-                let llvm_type = self.resolved_type_to_llvm(&var.ty);
+                let llvm_type = self.resolved_type_to_llvm(&var.ty)?;
                 self.builder
                     .build_load(ptr, llvm_type, &format!("{}_load", name))
             }
@@ -590,11 +1136,12 @@ impl<'a> CodeGenerator<'a> {
                     .ok_or_else(|| anyhow!("printf function not found in cache"))?;
                 self.builder.build_call(printf_func, vec![str_value], 1, "");
             }
-            ResolvedType::I32 | ResolvedType::I64 => {
+            ResolvedType::I32 | ResolvedType::I64 | ResolvedType::F64 => {
                 // For numeric types, use printf with format string
                 let base_type = match &generated_value.ty {
                     ResolvedType::I32 => crate::types::BaseTypes::Number,
                     ResolvedType::I64 => crate::types::BaseTypes::Number64,
+                    ResolvedType::F64 => crate::types::BaseTypes::Float64,
                     _ => unreachable!(),
                 };
                 let fmt_str = self.builder.get_printf_str(base_type);
@@ -632,10 +1179,17 @@ impl<'a> CodeGenerator<'a> {
                     }
                 }
             }
+            ResolvedType::Struct(_) | ResolvedType::Optional(_) => {
+                self.print_struct_value(generated_value.value, &generated_value.ty)?;
+                self.builder.build_printf("\n", vec![])?;
+            }
             ResolvedType::List(element_type) => {
                 // For lists, use the appropriate print function
                 let print_func_name = match **element_type {
                     ResolvedType::I32 => "printInt32List",
+                    ResolvedType::I64 => "printInt64List",
+                    ResolvedType::F64 => "printFloat64List",
+                    ResolvedType::Bool => "printBoolList",
                     ResolvedType::String => "printStringList",
                     _ => {
                         return Err(anyhow!(
@@ -675,18 +1229,30 @@ impl<'a> CodeGenerator<'a> {
         Ok(return_value)
     }
 
-    fn generate_break(&mut self) -> Result<GeneratedValue> {
-        let break_target = self
-            .loop_break_stack
-            .last()
-            .copied()
-            .ok_or_else(|| anyhow!("break used outside of loop"))?;
+    /// Jumps out of (`break`) or to the next iteration of (`continue`) the
+    /// innermost loop, or the loop named by `label`.
+    fn generate_loop_jump(
+        &mut self,
+        label: &Option<String>,
+        is_break: bool,
+    ) -> Result<GeneratedValue> {
+        let targets = self
+            .loop_stack
+            .iter()
+            .rev()
+            .find(|targets| label.is_none() || targets.label == *label)
+            .ok_or_else(|| anyhow!("break or continue used outside of loop"))?;
+        let target = if is_break {
+            targets.break_block
+        } else {
+            targets.continue_block
+        };
 
         if !self
             .builder
             .block_has_terminator(self.builder.current_function.block)
         {
-            self.builder.build_br(break_target);
+            self.builder.build_br(target);
         }
 
         let llvm_value = self
@@ -709,10 +1275,20 @@ impl<'a> CodeGenerator<'a> {
         // Generate the value expression
         let generated_value = self.generate_expression(value)?;
 
+        // Lists, maps and strings are handles to heap data, so a binding read from
+        // another variable shares it. Every other value gets its own copy, so
+        // assigning to the new variable leaves the one it was read from alone.
         let ptr = match generated_value.pointer {
-            Some(ptr) => ptr,
-            None => {
-                let llvm_ty = self.resolved_type_to_llvm(&generated_value.ty);
+            Some(ptr)
+                if matches!(
+                    generated_value.ty,
+                    ResolvedType::List(_) | ResolvedType::Map(..) | ResolvedType::String
+                ) =>
+            {
+                ptr
+            }
+            _ => {
+                let llvm_ty = self.resolved_type_to_llvm(&generated_value.ty)?;
                 self.builder
                     .build_alloca_store(generated_value.value, llvm_ty, name)
             }
@@ -744,6 +1320,8 @@ impl<'a> CodeGenerator<'a> {
         // Position at entry and generate condition
         self.builder.position_builder_at_end(if_entry_block);
         let cond_value = self.generate_expression(condition)?;
+        // The condition may have added blocks of its own, e.g. for `&&`
+        let cond_end_block = self.builder.current_function.block;
 
         // Create basic blocks
         let then_block = self.builder.append_basic_block(function, "then_block");
@@ -774,7 +1352,7 @@ impl<'a> CodeGenerator<'a> {
 
         // Now go back to entry block and add the conditional branch
         // This must be done AFTER all other blocks are set up
-        self.builder.set_current_block(if_entry_block);
+        self.builder.set_current_block(cond_end_block);
         let cond_val = if let Some(ptr) = cond_value.pointer {
             self.builder.build_load(ptr, int1_type(), "cond")
         } else {
@@ -796,12 +1374,19 @@ impl<'a> CodeGenerator<'a> {
         &mut self,
         condition: &TypedExpression,
         body: &TypedExpression,
+        step: Option<&TypedExpression>,
+        label: &Option<String>,
     ) -> Result<GeneratedValue> {
         let function = self.builder.current_function.function;
 
             // Create basic blocks
             let loop_cond_block = self.builder.append_basic_block(function, "loop_cond");
             let loop_body_block = self.builder.append_basic_block(function, "loop_body");
+            // A for loop's step gets its own block so `continue` and the end of the body reach it
+            let loop_step_block = match step {
+                Some(_) => self.builder.append_basic_block(function, "loop_step"),
+                None => loop_cond_block,
+            };
             let loop_exit_block = self.builder.append_basic_block(function, "loop_exit");
 
             // Jump to condition block
@@ -809,14 +1394,24 @@ impl<'a> CodeGenerator<'a> {
 
             // Generate loop body
             self.builder.set_current_block(loop_body_block);
-            self.loop_break_stack.push(loop_exit_block);
+            self.loop_stack.push(LoopTargets {
+                label: label.clone(),
+                break_block: loop_exit_block,
+                continue_block: loop_step_block,
+            });
             self.generate_expression(body)?;
-            self.loop_break_stack.pop();
+            self.loop_stack.pop();
             if !self
                 .builder
                 .block_has_terminator(self.builder.current_function.block)
             {
-                self.builder.build_br(loop_cond_block); // Jump back to condition
+                self.builder.build_br(loop_step_block); // Jump back to condition
+            }
+
+            if let Some(step) = step {
+                self.builder.set_current_block(loop_step_block);
+                self.generate_expression(step)?;
+                self.builder.build_br(loop_cond_block);
             }
 
             // Generate loop condition
@@ -886,54 +1481,30 @@ impl<'a> CodeGenerator<'a> {
         elements: &[TypedExpression],
         element_type: &ResolvedType,
     ) -> Result<GeneratedValue> {
-        // Call the appropriate create list function based on element type
-        let (create_func_name, set_func_name) = match element_type {
-            ResolvedType::I32 => ("create_int32_tList", "set_int32_tValue"),
-            ResolvedType::String => ("createStringList", "setStringValue"),
-            _ => {
-                return Err(anyhow!(
-                    "Lists of type {:?} are not yet supported",
-                    element_type
-                ))
-            }
-        };
-
-        // Get the create function from cache
         let create_func = self
             .builder
             .llvm_func_cache
-            .get(create_func_name)
-            .ok_or_else(|| anyhow!("{} function not found in cache", create_func_name))?;
+            .get("createList")
+            .ok_or_else(|| anyhow!("createList function not found in cache"))?;
 
-        // Create list with size
+        // Create list with its length and element size; the runtime owns the buffer
         let size_value = self
             .builder
             .const_int(int32_type(), elements.len() as c_ulonglong, 0);
-        let list_ptr = self
-            .builder
-            .build_call(create_func, vec![size_value], 1, "list_create");
-
-        // Get the set function from cache
-        let set_func = self
-            .builder
-            .llvm_func_cache
-            .get(set_func_name)
-            .ok_or_else(|| anyhow!("{} function not found in cache", set_func_name))?
-            .clone();
+        let element_size = self.builder.build_list_element_size(element_type)?;
+        let list_ptr = self.builder.build_call(
+            create_func,
+            vec![size_value, element_size],
+            2,
+            "list_create",
+        );
 
         // Populate the list with elements
         for (i, elem) in elements.iter().enumerate() {
             let elem_value = self.generate_expression(elem)?;
             let index_value = self.builder.const_int(int32_type(), i as c_ulonglong, 0);
-
-            // Call set function: set_func(list_ptr, value, index) for i32
-            // or set_func(list_ptr, value, index) for string
-            self.builder.build_call(
-                set_func.clone(),
-                vec![list_ptr, elem_value.value, index_value],
-                3,
-                "",
-            );
+            self.builder
+                .build_list_store(list_ptr, index_value, elem_value.value, element_type)?;
         }
 
         Ok(GeneratedValue {
@@ -947,6 +1518,7 @@ impl<'a> CodeGenerator<'a> {
         &mut self,
         list: &TypedExpression,
         index: &TypedExpression,
+        span: Span,
     ) -> Result<GeneratedValue> {
         // Generate the list expression
         let list_value = self.generate_expression(list)?;
@@ -960,43 +1532,16 @@ impl<'a> CodeGenerator<'a> {
         // Generate the index expression
         let index_value = self.generate_expression(index)?;
 
-        // Call the appropriate get function based on element type
-        let get_func_name = match element_type {
-            ResolvedType::I32 => "get_int32_tValue",
-            ResolvedType::String => "getStringValue",
-            _ => {
-                return Err(anyhow!(
-                    "List indexing for type {:?} not yet supported",
-                    element_type
-                ))
-            }
-        };
-
-        let get_func = self
-            .builder
-            .llvm_func_cache
-            .get(get_func_name)
-            .ok_or_else(|| anyhow!("{} function not found in cache", get_func_name))?;
-
-        // Call get function: get_func(list_ptr, index)
-        let result = self.builder.build_call(
-            get_func,
-            vec![list_value.value, index_value.value],
-            2,
-            "list_index",
-        );
+        let length = self.builder.build_list_len(list_value.value);
+        self.builder.build_bounds_check(index_value.value, length, span)?;
 
-        // get_int32_tValue returns the i32 value directly, not a pointer
-        // getStringValue returns a string pointer
-        let (final_value, final_pointer) = match element_type {
-            ResolvedType::I32 => (result, None), // i32 value is returned directly
-            ResolvedType::String => (result, None), // String returns pointer value
-            _ => unreachable!(),
-        };
+        let result =
+            self.builder
+                .build_list_load(list_value.value, index_value.value, &element_type)?;
 
         Ok(GeneratedValue {
-            value: final_value,
-            pointer: final_pointer,
+            value: result,
+            pointer: None,
             ty: element_type,
         })
     }
@@ -1006,6 +1551,7 @@ impl<'a> CodeGenerator<'a> {
         name: &String,
         index: &TypedExpression,
         value: &TypedExpression,
+        span: Span,
     ) -> Result<GeneratedValue> {
         // Look up the list variable and clone what we need
         let (list_ptr, element_type) = {
@@ -1025,32 +1571,10 @@ impl<'a> CodeGenerator<'a> {
         let index_value = self.generate_expression(index)?;
         let new_value = self.generate_expression(value)?;
 
-        // Call the appropriate set function based on element type
-        let set_func_name = match element_type {
-            ResolvedType::I32 => "set_int32_tValue",
-            ResolvedType::String => "setStringValue",
-            _ => {
-                return Err(anyhow!(
-                    "List assignment for type {:?} not yet supported",
-                    element_type
-                ))
-            }
-        };
-
-        let set_func = self
-            .builder
-            .llvm_func_cache
-            .get(set_func_name)
-            .ok_or_else(|| anyhow!("{} function not found in cache", set_func_name))?
-            .clone();
-
-        // Call set function: set_func(list_ptr, value, index)
-        self.builder.build_call(
-            set_func,
-            vec![list_ptr, new_value.value, index_value.value],
-            3,
-            "list_assign",
-        );
+        let length = self.builder.build_list_len(list_ptr);
+        self.builder.build_bounds_check(index_value.value, length, span)?;
+        self.builder
+            .build_list_store(list_ptr, index_value.value, new_value.value, &element_type)?;
 
         Ok(GeneratedValue {
             value: new_value.value,
@@ -1063,39 +1587,982 @@ impl<'a> CodeGenerator<'a> {
         // Generate the list expression
         let list_value = self.generate_expression(value)?;
 
-        // Extract element type from list
-        let element_type = match &list_value.ty {
-            ResolvedType::List(inner) => inner.as_ref().clone(),
-            _ => return Err(anyhow!("len() requires a list argument")),
+        let result = match list_value.ty {
+            // The length lives in the list struct, so this is a single load
+            ResolvedType::List(_) => self.builder.build_list_len(list_value.value),
+            ResolvedType::Map(..) => {
+                self.build_list_runtime_call("mapLength", vec![list_value.value], "length")?
+            }
+            _ => return Err(anyhow!("len() requires a list or map argument")),
         };
 
-        // Call the appropriate len function based on element type
-        let len_func_name = match element_type {
-            ResolvedType::I32 => "lenInt32List",
-            ResolvedType::String => "lenStringList",
-            _ => {
-                return Err(anyhow!(
-                    "len() for type {:?} not yet supported",
-                    element_type
-                ))
+        Ok(GeneratedValue {
+            value: result,
+            pointer: None,
+            ty: ResolvedType::I32, // len() always returns i32
+        })
+    }
+
+    fn generate_list_call(
+        &mut self,
+        op: ListOp,
+        list: &TypedExpression,
+        args: &[TypedExpression],
+        span: Span,
+    ) -> Result<GeneratedValue> {
+        let list_value = self.generate_expression(list)?;
+        let element_type = match &list_value.ty {
+            ResolvedType::List(inner) => inner.as_ref().clone(),
+            _ => return Err(anyhow!("{}() requires a list argument", op.name())),
+        };
+        let list_ptr = list_value.value;
+
+        if let (ListOp::Map | ListOp::Filter, [function]) = (op, args) {
+            let closure = self.generate_expression(function)?;
+            return self.generate_list_transform(op, list_ptr, &element_type, &closure);
+        }
+
+        let mut arg_values = Vec::new();
+        for arg in args {
+            arg_values.push(self.generate_expression(arg)?.value);
+        }
+
+        // The runtime grows and shifts the buffer, codegen loads and stores the
+        // elements themselves since only it knows their type
+        let removed = match (op, arg_values.as_slice()) {
+            (ListOp::Push, [value]) => {
+                let slot = self.build_list_runtime_call("pushList", vec![list_ptr], "slot")?;
+                self.builder
+                    .build_list_slot_store(slot, *value, &element_type)?;
+                None
+            }
+            (ListOp::Insert, [index, value]) => {
+                let location = self.builder.build_source_location(span);
+                let slot = self.build_list_runtime_call(
+                    "insertList",
+                    vec![list_ptr, *index, location],
+                    "slot",
+                )?;
+                self.builder
+                    .build_list_slot_store(slot, *value, &element_type)?;
+                None
+            }
+            (ListOp::Pop, []) => {
+                let length = self.builder.build_list_len(list_ptr);
+                let zero = self.builder.const_int(int32_type(), 0, 0);
+                let is_empty = self.builder.build_int_eq(length, zero, "is_empty");
+                self.builder
+                    .build_panic_if(is_empty, "attempt to pop from an empty list", span)?;
+                let one = self.builder.const_int(int32_type(), 1, 0);
+                let last = self.builder.llvm_build_fn(length, one, "-".to_string());
+                Some(self.build_list_remove(list_ptr, last, &element_type)?)
+            }
+            (ListOp::Remove, [index]) => {
+                let length = self.builder.build_list_len(list_ptr);
+                self.builder.build_bounds_check(*index, length, span)?;
+                Some(self.build_list_remove(list_ptr, *index, &element_type)?)
+            }
+            (ListOp::Clear, []) => {
+                self.build_list_runtime_call("clearList", vec![list_ptr], "")?;
+                None
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Wrong number of arguments supplied to {}()",
+                    op.name()
+                ))
             }
         };
 
-        let len_func = self
+        Ok(match removed {
+            Some(value) => GeneratedValue {
+                value,
+                pointer: None,
+                ty: element_type,
+            },
+            None => GeneratedValue {
+                value: list_ptr,
+                pointer: None,
+                ty: ResolvedType::Void,
+            },
+        })
+    }
+
+    /// Builds the new list map or filter returns by calling `closure` on each
+    /// element in turn.
+    fn generate_list_transform(
+        &mut self,
+        op: ListOp,
+        list_ptr: LLVMValueRef,
+        element_type: &ResolvedType,
+        closure: &GeneratedValue,
+    ) -> Result<GeneratedValue> {
+        let result_type = match (op, &closure.ty) {
+            (ListOp::Map, ResolvedType::Function(_, ret)) => ret.as_ref().clone(),
+            _ => element_type.clone(),
+        };
+        let element_size = self.builder.build_list_element_size(&result_type)?;
+        let zero = self.builder.const_int(int32_type(), 0, 0);
+        let result =
+            self.build_list_runtime_call("createList", vec![zero, element_size], "transformed")?;
+
+        let function = self.builder.current_function.function;
+        let index_ptr = self
+            .builder
+            .build_alloca_store(zero, int32_type(), "transform_index");
+        let cond_block = self.builder.append_basic_block(function, "transform_cond");
+        let body_block = self.builder.append_basic_block(function, "transform_body");
+        let step_block = self.builder.append_basic_block(function, "transform_step");
+        let end_block = self.builder.append_basic_block(function, "transform_end");
+        self.builder.build_br(cond_block);
+
+        self.builder.set_current_block(cond_block);
+        let index = self.builder.build_load(index_ptr, int32_type(), "index");
+        let length = self.builder.build_list_len(list_ptr);
+        let done = self.builder.build_int_eq(index, length, "done");
+        self.builder.build_cond_br(done, end_block, body_block);
+
+        self.builder.set_current_block(body_block);
+        let element = self
+            .builder
+            .build_list_load(list_ptr, index, element_type)?;
+        let value = self.build_closure_call(closure, vec![element])?.value;
+        let pushed = match op {
+            ListOp::Map => value,
+            // filter only keeps the elements the closure returns true for
+            _ => {
+                let keep_block = self.builder.append_basic_block(function, "transform_keep");
+                self.builder.build_cond_br(value, keep_block, step_block);
+                self.builder.set_current_block(keep_block);
+                element
+            }
+        };
+        let slot = self.build_list_runtime_call("pushList", vec![result], "slot")?;
+        self.builder
+            .build_list_slot_store(slot, pushed, &result_type)?;
+        self.builder.build_br(step_block);
+
+        self.builder.set_current_block(step_block);
+        let one = self.builder.const_int(int32_type(), 1, 0);
+        let next = self.builder.llvm_build_fn(index, one, "+".to_string());
+        self.builder.build_store(next, index_ptr);
+        self.builder.build_br(cond_block);
+
+        self.builder.set_current_block(end_block);
+        Ok(GeneratedValue {
+            value: result,
+            pointer: Some(result),
+            ty: ResolvedType::List(Box::new(result_type)),
+        })
+    }
+
+    fn generate_list_slice(
+        &mut self,
+        list: &TypedExpression,
+        start: Option<&TypedExpression>,
+        end: Option<&TypedExpression>,
+        span: Span,
+    ) -> Result<GeneratedValue> {
+        let list_value = self.generate_expression(list)?;
+        if !matches!(list_value.ty, ResolvedType::List(_)) {
+            return Err(anyhow!("Cannot slice non-list type"));
+        }
+
+        // Missing bounds default to the whole list
+        let start = match start {
+            Some(start) => self.generate_expression(start)?.value,
+            None => self.builder.const_int(int32_type(), 0, 0),
+        };
+        let end = match end {
+            Some(end) => self.generate_expression(end)?.value,
+            None => self.builder.build_list_len(list_value.value),
+        };
+
+        let location = self.builder.build_source_location(span);
+        let slice = self.build_list_runtime_call(
+            "sliceList",
+            vec![list_value.value, start, end, location],
+            "list_slice",
+        )?;
+
+        Ok(GeneratedValue {
+            value: slice,
+            pointer: Some(slice),
+            ty: list_value.ty,
+        })
+    }
+
+    /// Loads the element at `index` and closes the gap it leaves. The index
+    /// must already be bounds checked.
+    fn build_list_remove(
+        &mut self,
+        list_ptr: LLVMValueRef,
+        index: LLVMValueRef,
+        element_type: &ResolvedType,
+    ) -> Result<LLVMValueRef> {
+        let value = self
+            .builder
+            .build_list_load(list_ptr, index, element_type)?;
+        self.build_list_runtime_call("removeList", vec![list_ptr, index], "")?;
+        Ok(value)
+    }
+
+    fn build_list_runtime_call(
+        &mut self,
+        func_name: &str,
+        args: Vec<LLVMValueRef>,
+        name: &str,
+    ) -> Result<LLVMValueRef> {
+        let func = self
             .builder
             .llvm_func_cache
-            .get(len_func_name)
-            .ok_or_else(|| anyhow!("{} function not found in cache", len_func_name))?;
+            .get(func_name)
+            .ok_or_else(|| anyhow!("{} function not found in cache", func_name))?;
+        let num_args = args.len() as u32;
+        Ok(self.builder.build_call(func, args, num_args, name))
+    }
+    fn generate_map(
+        &mut self,
+        entries: &[(TypedExpression, TypedExpression)],
+        key_type: &ResolvedType,
+        value_type: &ResolvedType,
+    ) -> Result<GeneratedValue> {
+        // Keys and values are stored by size like list elements; string keys are
+        // hashed and compared by content rather than by pointer
+        let key_size = self.builder.build_list_element_size(key_type)?;
+        let value_size = self.builder.build_list_element_size(value_type)?;
+        let string_keys = self.builder.const_int(
+            int1_type(),
+            (*key_type == ResolvedType::String) as c_ulonglong,
+            0,
+        );
+        let map_ptr = self.build_list_runtime_call(
+            "createMap",
+            vec![key_size, value_size, string_keys],
+            "map_create",
+        )?;
+
+        for (key, value) in entries {
+            let key_value = self.generate_expression(key)?;
+            let slot = self.build_map_runtime_call("mapInsert", map_ptr, &key_value, "slot")?;
+            let value = self.generate_expression(value)?;
+            self.builder
+                .build_list_slot_store(slot, value.value, value_type)?;
+        }
+
+        Ok(GeneratedValue {
+            value: map_ptr,
+            pointer: Some(map_ptr),
+            ty: ResolvedType::Map(Box::new(key_type.clone()), Box::new(value_type.clone())),
+        })
+    }
+
+    fn generate_map_index(
+        &mut self,
+        map: &TypedExpression,
+        key: &TypedExpression,
+        span: Span,
+    ) -> Result<GeneratedValue> {
+        let map_value = self.generate_expression(map)?;
+        let value_type = match &map_value.ty {
+            ResolvedType::Map(_, value) => value.as_ref().clone(),
+            _ => return Err(anyhow!("Cannot index into non-map type")),
+        };
+        let key_value = self.generate_expression(key)?;
+        let value = self.build_map_lookup(map_value.value, &key_value, &value_type, span)?;
+
+        Ok(GeneratedValue {
+            value,
+            pointer: None,
+            ty: value_type,
+        })
+    }
+
+    fn generate_map_assign(
+        &mut self,
+        name: &String,
+        key: &TypedExpression,
+        value: &TypedExpression,
+    ) -> Result<GeneratedValue> {
+        let (map_ptr, value_type) = {
+            let map_var = self
+                .get_variable(name)
+                .ok_or_else(|| anyhow!("Undefined variable: {}", name))?;
+
+            let value_type = match &map_var.ty {
+                ResolvedType::Map(_, value) => value.as_ref().clone(),
+                _ => return Err(anyhow!("Cannot index into non-map type")),
+            };
+
+            (map_var.value, value_type)
+        };
 
-        // Call len function: len_func(list_ptr)
-        let result = self
+        let key_value = self.generate_expression(key)?;
+        let new_value = self.generate_expression(value)?;
+        let slot = self.build_map_runtime_call("mapInsert", map_ptr, &key_value, "slot")?;
+        self.builder
+            .build_list_slot_store(slot, new_value.value, &value_type)?;
+
+        Ok(GeneratedValue {
+            value: new_value.value,
+            pointer: None,
+            ty: ResolvedType::Void,
+        })
+    }
+
+    fn generate_map_call(
+        &mut self,
+        op: MapOp,
+        map: &TypedExpression,
+        args: &[TypedExpression],
+        span: Span,
+    ) -> Result<GeneratedValue> {
+        let map_value = self.generate_expression(map)?;
+        let (key_type, value_type) = match &map_value.ty {
+            ResolvedType::Map(key, value) => (key.as_ref().clone(), value.as_ref().clone()),
+            _ => return Err(anyhow!("{}() requires a map argument", op.name())),
+        };
+        let map_ptr = map_value.value;
+
+        let mut arg_values = Vec::new();
+        for arg in args {
+            arg_values.push(self.generate_expression(arg)?);
+        }
+
+        let (value, ty) = match (op, arg_values.as_slice()) {
+            (MapOp::Get, [key]) => {
+                let slot = self.build_map_runtime_call("mapGet", map_ptr, key, "slot")?;
+                let missing = self.builder.build_is_null(slot, "missing");
+                let value = self.build_map_get(slot, missing, &value_type)?;
+                (value, ResolvedType::Optional(Box::new(value_type)))
+            }
+            (MapOp::Contains, [key]) => {
+                let slot = self.build_map_runtime_call("mapGet", map_ptr, key, "slot")?;
+                let missing = self.builder.build_is_null(slot, "missing");
+                let found = self.builder.build_not(missing);
+                (found, ResolvedType::Bool)
+            }
+            (MapOp::Remove, [key]) => {
+                let value = self.build_map_lookup(map_ptr, key, &value_type, span)?;
+                self.build_map_runtime_call("mapRemove", map_ptr, key, "")?;
+                (value, value_type)
+            }
+            (MapOp::Keys, []) => {
+                let keys = self.build_list_runtime_call("mapKeys", vec![map_ptr], "map_keys")?;
+                (keys, ResolvedType::List(Box::new(key_type)))
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Wrong number of arguments supplied to {}()",
+                    op.name()
+                ))
+            }
+        };
+
+        Ok(GeneratedValue {
+            value,
+            pointer: None,
+            ty,
+        })
+    }
+
+    /// Wraps the value held in `slot` in an optional, which is nil when the
+    /// key is `missing` and the slot is null.
+    fn build_map_get(
+        &mut self,
+        slot: LLVMValueRef,
+        missing: LLVMValueRef,
+        value_type: &ResolvedType,
+    ) -> Result<LLVMValueRef> {
+        let function = self.builder.current_function.function;
+        let lookup_block = self.builder.current_function.block;
+        let found_block = self.builder.append_basic_block(function, "map_get_found");
+        let end_block = self.builder.append_basic_block(function, "map_get_end");
+        let optional_type = self
             .builder
-            .build_call(len_func, vec![list_value.value], 1, "list_len");
+            .optional_type(self.resolved_type_to_llvm(value_type)?);
+        self.builder.build_cond_br(missing, end_block, found_block);
+
+        self.builder.set_current_block(found_block);
+        let value = self.builder.build_list_slot_load(slot, value_type)?;
+        let found = self.builder.build_optional(optional_type, Some(value));
+        self.builder.build_br(end_block);
+
+        self.builder.set_current_block(end_block);
+        let nil = self.builder.build_optional(optional_type, None);
+        Ok(self.builder.build_phi(
+            optional_type,
+            &[(nil, lookup_block), (found, found_block)],
+            "map_get",
+        ))
+    }
+
+    /// Loads the value stored under `key`, panicking when the key is missing.
+    fn build_map_lookup(
+        &mut self,
+        map_ptr: LLVMValueRef,
+        key: &GeneratedValue,
+        value_type: &ResolvedType,
+        span: Span,
+    ) -> Result<LLVMValueRef> {
+        let slot = self.build_map_runtime_call("mapGet", map_ptr, key, "slot")?;
+        let missing = self.builder.build_is_null(slot, "missing");
+        self.builder
+            .build_panic_if(missing, "key not found in map", span)?;
+        self.builder.build_list_slot_load(slot, value_type)
+    }
+
+    /// Calls the `Int` or `String` variant of a map runtime function, e.g.
+    /// `mapGetInt`. Integer keys are widened to i64 so both widths share a table.
+    fn build_map_runtime_call(
+        &mut self,
+        func_prefix: &str,
+        map_ptr: LLVMValueRef,
+        key: &GeneratedValue,
+        name: &str,
+    ) -> Result<LLVMValueRef> {
+        let (suffix, key_value) = match key.ty {
+            ResolvedType::String => ("String", key.value),
+            ResolvedType::I64 => ("Int", key.value),
+            ResolvedType::I32 => (
+                "Int",
+                self.builder.build_numeric_cast(
+                    key.value,
+                    &ResolvedType::I32,
+                    &ResolvedType::I64,
+                )?,
+            ),
+            _ => return Err(anyhow!("Maps cannot be keyed by {:?}", key.ty)),
+        };
+        self.build_list_runtime_call(
+            &format!("{func_prefix}{suffix}"),
+            vec![map_ptr, key_value],
+            name,
+        )
+    }
+
+    fn generate_iter_items(&mut self, value: &TypedExpression) -> Result<GeneratedValue> {
+        let iterable = self.generate_expression(value)?;
+        let (items, item_type) = match &iterable.ty {
+            ResolvedType::List(inner) => (iterable.value, inner.as_ref().clone()),
+            ResolvedType::Map(key, _) => (
+                self.build_list_runtime_call("mapKeys", vec![iterable.value], "map_keys")?,
+                key.as_ref().clone(),
+            ),
+            ResolvedType::String => (
+                self.build_list_runtime_call("stringChars", vec![iterable.value], "chars")?,
+                ResolvedType::String,
+            ),
+            _ => return Err(anyhow!("Cannot iterate over {:?}", iterable.ty)),
+        };
 
         Ok(GeneratedValue {
-            value: result,
+            value: items,
+            pointer: Some(items),
+            ty: ResolvedType::List(Box::new(item_type)),
+        })
+    }
+
+    fn generate_impl(&mut self, methods: &[TypedExpression]) -> Result<GeneratedValue> {
+        for method in methods {
+            self.generate_expression(method)?;
+        }
+        Ok(GeneratedValue {
+            value: ptr::null_mut(),
             pointer: None,
-            ty: ResolvedType::I32, // len() always returns i32
+            ty: ResolvedType::Void,
+        })
+    }
+
+    fn generate_struct_stmt(
+        &mut self,
+        name: &str,
+        fields: &[(String, ResolvedType)],
+    ) -> Result<GeneratedValue> {
+        let mut field_types: Vec<LLVMTypeRef> = fields
+            .iter()
+            .map(|(_, ty)| self.resolved_type_to_llvm(ty))
+            .collect::<Result<_>>()?;
+        self.builder.build_struct_type(name, &mut field_types);
+        self.struct_fields.insert(name.to_string(), fields.to_vec());
+
+        // A declaration produces no code
+        Ok(GeneratedValue {
+            value: ptr::null_mut(),
+            pointer: None,
+            ty: ResolvedType::Void,
+        })
+    }
+
+    fn generate_struct_literal(
+        &mut self,
+        name: &str,
+        fields: &[TypedExpression],
+    ) -> Result<GeneratedValue> {
+        let mut values = Vec::new();
+        for field in fields {
+            values.push(self.generate_expression(field)?.value);
+        }
+        let struct_type = self.builder.struct_type(name)?;
+        let value = self.builder.build_struct_value(struct_type, &values);
+
+        Ok(GeneratedValue {
+            value,
+            pointer: None,
+            ty: ResolvedType::Struct(name.to_string()),
+        })
+    }
+
+    fn generate_field_access(
+        &mut self,
+        value: &TypedExpression,
+        index: u32,
+    ) -> Result<GeneratedValue> {
+        let struct_value = self.generate_expression(value)?;
+        let field_type = self.struct_field_type(&struct_value.ty, index)?;
+        let value = self
+            .builder
+            .build_extract_value(struct_value.value, index, "field");
+
+        Ok(GeneratedValue {
+            value,
+            pointer: None,
+            ty: field_type,
+        })
+    }
+
+    fn generate_field_assign(
+        &mut self,
+        target: &TypedExpression,
+        index: u32,
+        value: &TypedExpression,
+    ) -> Result<GeneratedValue> {
+        let (struct_ptr, struct_ty) = self.generate_place(target)?;
+        let new_value = self.generate_expression(value)?;
+        let field_ptr = self.build_field_ptr(struct_ptr, &struct_ty, index)?;
+        self.builder.build_store(new_value.value, field_ptr);
+
+        Ok(GeneratedValue {
+            value: new_value.value,
+            pointer: None,
+            ty: ResolvedType::Void,
+        })
+    }
+
+    /// Returns a pointer to the memory holding `target` so one of its fields
+    /// can be written in place: a variable, a list element or a field of either.
+    fn generate_place(&mut self, target: &TypedExpression) -> Result<(LLVMValueRef, ResolvedType)> {
+        match target {
+            TypedExpression::Variable { name, .. } => {
+                let var = self
+                    .get_variable(name)
+                    .ok_or_else(|| anyhow!("Undefined variable: {}", name))?;
+                let ptr = var
+                    .pointer
+                    .ok_or_else(|| anyhow!("Variable {} has no pointer", name))?;
+                Ok((ptr, var.ty.clone()))
+            }
+            TypedExpression::FieldAccess { value, index, .. } => {
+                let (struct_ptr, struct_ty) = self.generate_place(value)?;
+                let field_ptr = self.build_field_ptr(struct_ptr, &struct_ty, *index)?;
+                Ok((field_ptr, self.struct_field_type(&struct_ty, *index)?))
+            }
+            TypedExpression::ListIndex { list, index, span } => {
+                let list_value = self.generate_expression(list)?;
+                let element_type = match &list_value.ty {
+                    ResolvedType::List(inner) => inner.as_ref().clone(),
+                    _ => return Err(anyhow!("Cannot index into non-list type")),
+                };
+                let index_value = self.generate_expression(index)?;
+                let length = self.builder.build_list_len(list_value.value);
+                self.builder
+                    .build_bounds_check(index_value.value, length, *span)?;
+                let slot = self.builder.build_list_slot(
+                    list_value.value,
+                    index_value.value,
+                    &element_type,
+                )?;
+                Ok((slot, element_type))
+            }
+            TypedExpression::Grouping { inner, .. } => self.generate_place(inner),
+            _ => Err(anyhow!("Cannot assign to a field of a temporary value")),
+        }
+    }
+
+    fn build_field_ptr(
+        &self,
+        struct_ptr: LLVMValueRef,
+        struct_ty: &ResolvedType,
+        index: u32,
+    ) -> Result<LLVMValueRef> {
+        let ResolvedType::Struct(name) = struct_ty else {
+            return Err(anyhow!("Cannot access a field on {:?}", struct_ty));
+        };
+        let struct_type = self.builder.struct_type(name)?;
+        Ok(self
+            .builder
+            .build_struct_field_ptr(struct_type, struct_ptr, index))
+    }
+
+    fn struct_field_type(&self, struct_ty: &ResolvedType, index: u32) -> Result<ResolvedType> {
+        let ResolvedType::Struct(name) = struct_ty else {
+            return Err(anyhow!("Cannot access a field on {:?}", struct_ty));
+        };
+        self.struct_fields
+            .get(name)
+            .and_then(|fields| fields.get(index as usize))
+            .map(|(_, ty)| ty.clone())
+            .ok_or_else(|| anyhow!("Struct {} has no field {}", name, index))
+    }
+
+    /// Prints a value nested inside a struct, e.g. `Point { x: 1, y: 2 }`,
+    /// without a trailing newline.
+    fn print_struct_value(&mut self, value: LLVMValueRef, ty: &ResolvedType) -> Result<()> {
+        match ty {
+            ResolvedType::I32 => self.builder.build_printf("%d", vec![value]),
+            ResolvedType::I64 => self.builder.build_printf("%lld", vec![value]),
+            ResolvedType::F64 => self.builder.build_printf("%f", vec![value]),
+            ResolvedType::Bool => {
+                let true_str = self.builder.build_global_string("true", "true_str");
+                let false_str = self.builder.build_global_string("false", "false_str");
+                let str_value = self.builder.build_select(value, true_str, false_str);
+                self.builder.build_printf("%s", vec![str_value])
+            }
+            ResolvedType::String => {
+                self.build_list_runtime_call("stringPrintList", vec![value], "")?;
+                Ok(())
+            }
+            ResolvedType::List(element_type) => {
+                let print_func_name = match **element_type {
+                    ResolvedType::I32 => "printInt32List",
+                    ResolvedType::I64 => "printInt64List",
+                    ResolvedType::F64 => "printFloat64List",
+                    ResolvedType::Bool => "printBoolList",
+                    ResolvedType::String => "printStringList",
+                    _ => {
+                        return Err(anyhow!(
+                            "Print not implemented for list of type: {:?}",
+                            element_type
+                        ))
+                    }
+                };
+                self.build_list_runtime_call(print_func_name, vec![value], "")?;
+                Ok(())
+            }
+            ResolvedType::Struct(name) => {
+                let fields = self
+                    .struct_fields
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("Undefined struct: {}", name))?;
+                if fields.is_empty() {
+                    return self.builder.build_printf(&format!("{name} {{}}"), vec![]);
+                }
+                self.builder.build_printf(&format!("{name} {{ "), vec![])?;
+                for (i, (field_name, field_type)) in fields.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ", " };
+                    self.builder
+                        .build_printf(&format!("{separator}{field_name}: "), vec![])?;
+                    let field_value = self.builder.build_extract_value(value, i as u32, "field");
+                    self.print_struct_value(field_value, field_type)?;
+                }
+                self.builder.build_printf(" }", vec![])
+            }
+            ResolvedType::Optional(inner) => {
+                let function = self.builder.current_function.function;
+                let some_block = self.builder.append_basic_block(function, "print_some");
+                let nil_block = self.builder.append_basic_block(function, "print_nil");
+                let end_block = self.builder.append_basic_block(function, "print_end");
+                let present = self.builder.build_extract_value(value, 0, "present");
+                self.builder.build_cond_br(present, some_block, nil_block);
+
+                self.builder.set_current_block(some_block);
+                let inner_value = self.builder.build_extract_value(value, 1, "value");
+                self.print_struct_value(inner_value, inner)?;
+                self.builder.build_br(end_block);
+
+                self.builder.set_current_block(nil_block);
+                self.builder.build_printf("nil", vec![])?;
+                self.builder.build_br(end_block);
+
+                self.builder.set_current_block(end_block);
+                Ok(())
+            }
+            _ => Err(anyhow!("Print not implemented for type: {:?}", ty)),
+        }
+    }
+
+    fn generate_enum_stmt(
+        &mut self,
+        name: &str,
+        variants: &[(String, Vec<ResolvedType>)],
+    ) -> Result<GeneratedValue> {
+        let payloads: Vec<Vec<ResolvedType>> = variants
+            .iter()
+            .map(|(_, payload)| payload.clone())
+            .collect();
+        let payload_slots = payloads
+            .iter()
+            .map(|payload| payload.iter().map(|ty| self.payload_slots(ty)).sum())
+            .max()
+            .unwrap_or(0);
+        self.builder.build_enum_type(name, payload_slots);
+        self.enum_variants.insert(name.to_string(), payloads);
+
+        // A declaration produces no code
+        Ok(GeneratedValue {
+            value: ptr::null_mut(),
+            pointer: None,
+            ty: ResolvedType::Void,
+        })
+    }
+
+    /// Number of 8 byte slots an enum payload needs to hold a value of `ty`.
+    /// Every other value fits in one slot, so a struct needs at most one per field.
+    fn payload_slots(&self, ty: &ResolvedType) -> u64 {
+        match ty {
+            ResolvedType::Struct(name) => self
+                .struct_fields
+                .get(name)
+                .map(|fields| fields.iter().map(|(_, ty)| self.payload_slots(ty)).sum())
+                .unwrap_or(0),
+            // as does the flag of an optional
+            ResolvedType::Optional(inner) => 1 + self.payload_slots(inner),
+            // a closure is a function pointer and an environment pointer
+            ResolvedType::Function(..) => 2,
+            // the tag takes a slot of its own
+            ResolvedType::Enum(name) => {
+                let largest = self.enum_variants.get(name).and_then(|payloads| {
+                    payloads
+                        .iter()
+                        .map(|payload| payload.iter().map(|ty| self.payload_slots(ty)).sum())
+                        .max()
+                });
+                1 + largest.unwrap_or(0)
+            }
+            _ => 1,
+        }
+    }
+
+    fn variant_payload_type(&self, enum_name: &str, tag: u32) -> Result<LLVMTypeRef> {
+        let payload = self
+            .enum_variants
+            .get(enum_name)
+            .and_then(|payloads| payloads.get(tag as usize))
+            .ok_or_else(|| anyhow!("Enum {} has no variant {}", enum_name, tag))?;
+        let mut field_types: Vec<LLVMTypeRef> = payload
+            .iter()
+            .map(|ty| self.resolved_type_to_llvm(ty))
+            .collect::<Result<_>>()?;
+        Ok(self.builder.variant_payload_type(&mut field_types))
+    }
+
+    fn generate_enum_value(
+        &mut self,
+        enum_name: &str,
+        tag: u32,
+        values: &[TypedExpression],
+    ) -> Result<GeneratedValue> {
+        let enum_type = self.builder.struct_type(enum_name)?;
+        let enum_ptr = self.builder.build_alloca(enum_type, "enum_value");
+
+        let tag_ptr = self.builder.build_struct_field_ptr(enum_type, enum_ptr, 0);
+        let tag_value = self.builder.const_int(int32_type(), tag as c_ulonglong, 0);
+        self.builder.build_store(tag_value, tag_ptr);
+
+        if !values.is_empty() {
+            let mut payload_values = Vec::new();
+            for value in values {
+                payload_values.push(self.generate_expression(value)?.value);
+            }
+            let payload_type = self.variant_payload_type(enum_name, tag)?;
+            let payload = self
+                .builder
+                .build_struct_value(payload_type, &payload_values);
+            let payload_ptr = self.builder.build_struct_field_ptr(enum_type, enum_ptr, 1);
+            self.builder.build_store(payload, payload_ptr);
+        }
+
+        let value = self.builder.build_load(enum_ptr, enum_type, "enum_load");
+        Ok(GeneratedValue {
+            value,
+            pointer: None,
+            ty: ResolvedType::Enum(enum_name.to_string()),
+        })
+    }
+
+    /// Lowers a match to a `switch` on the enum's tag, with one block per arm.
+    /// Matches are exhaustive, so without a `_` arm the default is unreachable.
+    fn generate_match(
+        &mut self,
+        value: &TypedExpression,
+        enum_name: &str,
+        arms: &[TypedExpression],
+    ) -> Result<GeneratedValue> {
+        let function = self.builder.current_function.function;
+        let enum_type = self.builder.struct_type(enum_name)?;
+        let scrutinee = self.generate_expression(value)?;
+        let enum_ptr = self
+            .builder
+            .build_alloca_store(scrutinee.value, enum_type, "match_value");
+        let tag = self
+            .builder
+            .build_extract_value(scrutinee.value, 0, "match_tag");
+
+        let merge_block = self.builder.append_basic_block(function, "match_end");
+        let default_block = self.builder.append_basic_block(function, "match_default");
+        let switch = self
+            .builder
+            .build_switch(tag, default_block, arms.len() as u32);
+        let mut has_wildcard = false;
+
+        for arm in arms {
+            let TypedExpression::MatchArm {
+                tag: arm_tag,
+                bindings,
+                body,
+                ..
+            } = arm
+            else {
+                return Err(anyhow!("Expected a match arm"));
+            };
+            let arm_block = match arm_tag {
+                Some(arm_tag) => {
+                    let block = self.builder.append_basic_block(function, "match_arm");
+                    let case = self
+                        .builder
+                        .const_int(int32_type(), *arm_tag as c_ulonglong, 0);
+                    self.builder.add_switch_case(switch, case, block);
+                    block
+                }
+                None => {
+                    has_wildcard = true;
+                    default_block
+                }
+            };
+            self.builder.set_current_block(arm_block);
+
+            self.incr_depth();
+            if let Some(arm_tag) = arm_tag {
+                self.bind_payload(enum_type, enum_ptr, enum_name, *arm_tag, bindings)?;
+            }
+            self.generate_expression(body)?;
+            self.decr_depth();
+
+            if !self
+                .builder
+                .block_has_terminator(self.builder.current_function.block)
+            {
+                self.builder.build_br(merge_block);
+            }
+        }
+
+        if !has_wildcard {
+            self.builder.set_current_block(default_block);
+            self.builder.build_unreachable();
+        }
+        self.builder.set_current_block(merge_block);
+
+        Ok(GeneratedValue {
+            value: ptr::null_mut(),
+            pointer: None,
+            ty: ResolvedType::Void,
+        })
+    }
+
+    // Declares a local for each named value in the matched variant's payload
+    fn bind_payload(
+        &mut self,
+        enum_type: LLVMTypeRef,
+        enum_ptr: LLVMValueRef,
+        enum_name: &str,
+        tag: u32,
+        bindings: &[(String, ResolvedType)],
+    ) -> Result<()> {
+        if bindings.is_empty() {
+            return Ok(());
+        }
+        let payload_type = self.variant_payload_type(enum_name, tag)?;
+        let payload_ptr = self.builder.build_struct_field_ptr(enum_type, enum_ptr, 1);
+        let payload = self
+            .builder
+            .build_load(payload_ptr, payload_type, "payload");
+        for (i, (name, ty)) in bindings.iter().enumerate() {
+            if name == "_" {
+                continue;
+            }
+            let value = self.builder.build_extract_value(payload, i as u32, name);
+            let llvm_type = self.resolved_type_to_llvm(ty)?;
+            let ptr = self.builder.build_alloca_store(value, llvm_type, name);
+            self.set_variable(
+                name,
+                GeneratedValue {
+                    value,
+                    pointer: Some(ptr),
+                    ty: ty.clone(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn generate_optional_value(
+        &mut self,
+        value: Option<&TypedExpression>,
+        value_type: &ResolvedType,
+    ) -> Result<GeneratedValue> {
+        let optional_type = self
+            .builder
+            .optional_type(self.resolved_type_to_llvm(value_type)?);
+        let value = match value {
+            Some(value) => Some(self.generate_expression(value)?.value),
+            None => None,
+        };
+        Ok(GeneratedValue {
+            value: self.builder.build_optional(optional_type, value),
+            pointer: None,
+            ty: ResolvedType::Optional(Box::new(value_type.clone())),
+        })
+    }
+
+    fn generate_nil_check(
+        &mut self,
+        value: &TypedExpression,
+        is_nil: bool,
+    ) -> Result<GeneratedValue> {
+        let optional = self.generate_expression(value)?;
+        let present = self
+            .builder
+            .build_extract_value(optional.value, 0, "present");
+        let value = if is_nil {
+            self.builder.build_not(present)
+        } else {
+            present
+        };
+        Ok(GeneratedValue {
+            value,
+            pointer: None,
+            ty: ResolvedType::Bool,
+        })
+    }
+
+    fn generate_unwrap(&mut self, value: &TypedExpression, span: Span) -> Result<GeneratedValue> {
+        let optional = self.generate_expression(value)?;
+        let ResolvedType::Optional(inner) = optional.ty else {
+            return Err(anyhow!("Cannot unwrap {:?}", optional.ty));
+        };
+        let present = self
+            .builder
+            .build_extract_value(optional.value, 0, "present");
+        let missing = self.builder.build_not(present);
+        self.builder
+            .build_panic_if(missing, "unwrapped a nil value", span)?;
+        Ok(GeneratedValue {
+            value: self
+                .builder
+                .build_extract_value(optional.value, 1, "unwrapped"),
+            pointer: None,
+            ty: *inner,
         })
     }
 }
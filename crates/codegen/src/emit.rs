@@ -0,0 +1,325 @@
+use crate::cstr_from_string;
+use crate::target::Target;
+use anyhow::{anyhow, Result};
+use libc::c_uint;
+use llvm_sys::bit_writer::LLVMWriteBitcodeToFile;
+use llvm_sys::core::{
+    LLVMContextCreate, LLVMContextDispose, LLVMCreateMemoryBufferWithMemoryRangeCopy,
+    LLVMDisposeMemoryBuffer, LLVMDisposeMessage, LLVMDisposeModule, LLVMGetBufferSize,
+    LLVMGetBufferStart, LLVMGetFirstFunction, LLVMGetNextFunction, LLVMPrintModuleToFile,
+    LLVMRemoveStringAttributeAtIndex, LLVMSetTarget,
+};
+use llvm_sys::error::{LLVMDisposeErrorMessage, LLVMErrorRef, LLVMGetErrorMessage};
+use llvm_sys::ir_reader::LLVMParseIRInContext;
+use llvm_sys::prelude::{LLVMMemoryBufferRef, LLVMModuleRef};
+use llvm_sys::target::{
+    LLVMDisposeTargetData, LLVMSetModuleDataLayout, LLVM_InitializeNativeAsmPrinter,
+    LLVM_InitializeNativeTarget,
+};
+use llvm_sys::target_machine::{
+    LLVMCodeGenFileType, LLVMCodeGenOptLevel, LLVMCodeModel, LLVMCreateTargetDataLayout,
+    LLVMCreateTargetMachine, LLVMDisposeTargetMachine, LLVMGetDefaultTargetTriple,
+    LLVMGetHostCPUFeatures, LLVMGetHostCPUName, LLVMGetTargetFromTriple,
+    LLVMGetTargetMachineTriple, LLVMRelocMode, LLVMTargetMachineEmitToFile,
+    LLVMTargetMachineEmitToMemoryBuffer, LLVMTargetMachineRef, LLVMTargetRef,
+};
+use llvm_sys::transforms::pass_builder::{
+    LLVMCreatePassBuilderOptions, LLVMDisposePassBuilderOptions, LLVMRunPasses,
+};
+use llvm_sys::LLVMAttributeFunctionIndex;
+use std::ptr;
+
+/// What `--emit` writes in place of an executable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmitKind {
+    Object,
+    Assembly,
+    Bitcode,
+    LlvmIr,
+}
+
+/// Optimization level set with `-O`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OptLevel {
+    #[default]
+    O0,
+    O1,
+    O2,
+    O3,
+}
+
+impl OptLevel {
+    pub fn from_name(name: &str) -> Option<OptLevel> {
+        match name {
+            "0" => Some(OptLevel::O0),
+            "1" => Some(OptLevel::O1),
+            "2" => Some(OptLevel::O2),
+            "3" => Some(OptLevel::O3),
+            _ => None,
+        }
+    }
+
+    /// Whether integer overflow panics at this level when no flag says
+    /// otherwise. As in a debug build it does at -O0 and -O1, and wraps at
+    /// -O2 and -O3.
+    pub fn checks_arithmetic(&self) -> bool {
+        matches!(self, OptLevel::O0 | OptLevel::O1)
+    }
+
+    fn passes(&self) -> &'static str {
+        match self {
+            OptLevel::O0 => "default<O0>",
+            OptLevel::O1 => "default<O1>",
+            OptLevel::O2 => "default<O2>",
+            OptLevel::O3 => "default<O3>",
+        }
+    }
+}
+
+impl EmitKind {
+    pub fn from_name(name: &str) -> Option<EmitKind> {
+        match name {
+            "obj" => Some(EmitKind::Object),
+            "asm" => Some(EmitKind::Assembly),
+            "bc" => Some(EmitKind::Bitcode),
+            "llvm-ir" => Some(EmitKind::LlvmIr),
+            _ => None,
+        }
+    }
+
+    /// Extension of the file written when no output path is given.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EmitKind::Object => "o",
+            EmitKind::Assembly => "s",
+            EmitKind::Bitcode => "bc",
+            EmitKind::LlvmIr => "ll",
+        }
+    }
+}
+
+/// # Safety
+///
+/// Writes `module` to `path` as `kind`, after retargeting it to `target`, or to
+/// the host when there is none.
+pub unsafe fn write_module(
+    module: LLVMModuleRef,
+    kind: EmitKind,
+    path: &str,
+    target: Option<&Target>,
+) -> Result<()> {
+    let target_machine = create_target_machine(target)?;
+    retarget_module(module, target_machine);
+    let c_path = cstr_from_string(path);
+    let mut error: *mut i8 = ptr::null_mut();
+    let failed = match kind {
+        EmitKind::Object | EmitKind::Assembly => {
+            let file_type = if kind == EmitKind::Object {
+                LLVMCodeGenFileType::LLVMObjectFile
+            } else {
+                LLVMCodeGenFileType::LLVMAssemblyFile
+            };
+            LLVMTargetMachineEmitToFile(
+                target_machine,
+                module,
+                c_path.as_ptr(),
+                file_type,
+                &mut error,
+            )
+        }
+        EmitKind::Bitcode => LLVMWriteBitcodeToFile(module, c_path.as_ptr()),
+        EmitKind::LlvmIr => LLVMPrintModuleToFile(module, c_path.as_ptr(), &mut error),
+    };
+    LLVMDisposeTargetMachine(target_machine);
+    if failed != 0 {
+        return Err(anyhow!(
+            "failed to write {}: {}",
+            path,
+            take_llvm_message(error)
+        ));
+    }
+    Ok(())
+}
+
+/// # Safety
+///
+/// Sets the triple and data layout of `module` from a TargetMachine for
+/// `target`, or for the host when there is none.
+pub unsafe fn set_module_target(module: LLVMModuleRef, target: Option<&Target>) -> Result<()> {
+    let target_machine = create_target_machine(target)?;
+    retarget_module(module, target_machine);
+    LLVMDisposeTargetMachine(target_machine);
+    Ok(())
+}
+
+/// # Safety
+///
+/// Runs the new pass manager's `default<On>` pipeline over `module` in place,
+/// after retargeting it like `write_module`. Does nothing at `-O0`.
+pub unsafe fn optimize_module(
+    module: LLVMModuleRef,
+    opt_level: OptLevel,
+    target: Option<&Target>,
+) -> Result<()> {
+    if opt_level == OptLevel::O0 {
+        return Ok(());
+    }
+    let target_machine = create_target_machine(target)?;
+    retarget_module(module, target_machine);
+    let options = LLVMCreatePassBuilderOptions();
+    let passes = cstr_from_string(opt_level.passes());
+    let error = LLVMRunPasses(module, passes.as_ptr(), target_machine, options);
+    LLVMDisposePassBuilderOptions(options);
+    LLVMDisposeTargetMachine(target_machine);
+    if !error.is_null() {
+        return Err(anyhow!(
+            "failed to optimize module: {}",
+            take_llvm_error(error)
+        ));
+    }
+    Ok(())
+}
+
+/// Compiles textual IR to assembly for the host.
+pub fn ir_to_assembly(ir: &str) -> Result<String> {
+    with_parsed_ir(ir, |module| unsafe {
+        let target_machine = create_target_machine(None)?;
+        retarget_module(module, target_machine);
+        let mut buffer: LLVMMemoryBufferRef = ptr::null_mut();
+        let mut error: *mut i8 = ptr::null_mut();
+        let failed = LLVMTargetMachineEmitToMemoryBuffer(
+            target_machine,
+            module,
+            LLVMCodeGenFileType::LLVMAssemblyFile,
+            &mut error,
+            &mut buffer,
+        );
+        LLVMDisposeTargetMachine(target_machine);
+        if failed != 0 {
+            return Err(anyhow!(
+                "failed to emit assembly: {}",
+                take_llvm_message(error)
+            ));
+        }
+        let bytes = std::slice::from_raw_parts(
+            LLVMGetBufferStart(buffer) as *const u8,
+            LLVMGetBufferSize(buffer),
+        );
+        let asm = String::from_utf8_lossy(bytes).to_string();
+        LLVMDisposeMemoryBuffer(buffer);
+        Ok(asm)
+    })
+}
+
+// Parses `ir` into a module in a fresh context, which is disposed once `f` returns
+fn with_parsed_ir<T>(ir: &str, f: impl FnOnce(LLVMModuleRef) -> Result<T>) -> Result<T> {
+    unsafe {
+        let context = LLVMContextCreate();
+        let buffer = LLVMCreateMemoryBufferWithMemoryRangeCopy(
+            ir.as_ptr() as *const _,
+            ir.len(),
+            cstr_from_string("module").as_ptr(),
+        );
+        let mut module: LLVMModuleRef = ptr::null_mut();
+        let mut error: *mut i8 = ptr::null_mut();
+        // The buffer is owned, and freed, by the parser
+        if LLVMParseIRInContext(context, buffer, &mut module, &mut error) != 0 {
+            LLVMContextDispose(context);
+            return Err(anyhow!("failed to parse IR: {}", take_llvm_message(error)));
+        }
+        let result = f(module);
+        LLVMDisposeModule(module);
+        LLVMContextDispose(context);
+        result
+    }
+}
+
+// Creates a TargetMachine for `target`, or for the host CPU when there is none,
+// which the caller disposes. Cross targets use a generic CPU.
+unsafe fn create_target_machine(target: Option<&Target>) -> Result<LLVMTargetMachineRef> {
+    let (triple, cpu, features) = match target {
+        Some(target) => {
+            target.initialize();
+            (
+                target.get_llvm_target_name(),
+                "generic".to_string(),
+                String::new(),
+            )
+        }
+        None => {
+            LLVM_InitializeNativeTarget();
+            LLVM_InitializeNativeAsmPrinter();
+            (
+                take_llvm_message(LLVMGetDefaultTargetTriple()),
+                take_llvm_message(LLVMGetHostCPUName()),
+                take_llvm_message(LLVMGetHostCPUFeatures()),
+            )
+        }
+    };
+    let c_triple = cstr_from_string(&triple);
+    let mut llvm_target: LLVMTargetRef = ptr::null_mut();
+    let mut error: *mut i8 = ptr::null_mut();
+    if LLVMGetTargetFromTriple(c_triple.as_ptr(), &mut llvm_target, &mut error) != 0 {
+        return Err(anyhow!(
+            "failed to find target {}: {}",
+            triple,
+            take_llvm_message(error)
+        ));
+    }
+    Ok(LLVMCreateTargetMachine(
+        llvm_target,
+        c_triple.as_ptr(),
+        cstr_from_string(&cpu).as_ptr(),
+        cstr_from_string(&features).as_ptr(),
+        LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+        LLVMRelocMode::LLVMRelocPIC,
+        LLVMCodeModel::LLVMCodeModelDefault,
+    ))
+}
+
+// The stdlib bitcode carries the triple and CPU it was built for. Point the
+// module at the target machine instead, dropping the per-function CPU and
+// feature attributes so every function is compiled for its CPU.
+unsafe fn retarget_module(module: LLVMModuleRef, target_machine: LLVMTargetMachineRef) {
+    let triple = LLVMGetTargetMachineTriple(target_machine);
+    LLVMSetTarget(module, triple);
+    LLVMDisposeMessage(triple);
+    let data_layout = LLVMCreateTargetDataLayout(target_machine);
+    LLVMSetModuleDataLayout(module, data_layout);
+    LLVMDisposeTargetData(data_layout);
+
+    let mut function = LLVMGetFirstFunction(module);
+    while !function.is_null() {
+        for key in ["target-cpu", "target-features", "tune-cpu"] {
+            LLVMRemoveStringAttributeAtIndex(
+                function,
+                LLVMAttributeFunctionIndex,
+                key.as_ptr() as *const _,
+                key.len() as c_uint,
+            );
+        }
+        function = LLVMGetNextFunction(function);
+    }
+}
+
+// Copies a message LLVM allocated into a String and frees it
+unsafe fn take_llvm_message(message: *mut i8) -> String {
+    if message.is_null() {
+        return "unknown error".to_string();
+    }
+    let text = std::ffi::CStr::from_ptr(message)
+        .to_string_lossy()
+        .to_string();
+    LLVMDisposeMessage(message);
+    text
+}
+
+// Copies the message of an LLVM error into a String and frees the error
+unsafe fn take_llvm_error(error: LLVMErrorRef) -> String {
+    let message = LLVMGetErrorMessage(error);
+    let text = std::ffi::CStr::from_ptr(message)
+        .to_string_lossy()
+        .to_string();
+    LLVMDisposeErrorMessage(message);
+    text
+}
@@ -8,19 +8,30 @@ use std::ffi::CString;
 
 pub mod builder;
 pub mod code_generator;
+pub mod emit;
 pub mod context;
 pub mod stdlib;
 pub mod target;
 pub mod typed_ast;
 pub mod types;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct CompileOptions {
     pub is_execution_engine: bool,
     pub target: Option<target::Target>,
     pub emit_llvm_ir: bool,
     pub emit_llvm_ir_main_only: bool,
     pub emit_llvm_ir_with_called: bool,
+    /// Trap on integer overflow instead of wrapping
+    pub checked_arithmetic: bool,
+    /// Source the program was parsed from, used for runtime panic locations
+    pub source: Option<parser::SourceFile>,
+    /// Write a native executable to this path instead of running the program
+    pub output: Option<String>,
+    /// Write this to `output` in place of an executable
+    pub emit: Option<emit::EmitKind>,
+    /// Passes run over the module before it is JIT compiled, emitted or linked
+    pub opt_level: emit::OptLevel,
 }
 
 pub fn cstr_from_string(name: &str) -> CString {
@@ -47,6 +58,14 @@ pub fn int64_type() -> LLVMTypeRef {
     unsafe { LLVMInt64Type() }
 }
 
+pub fn double_type() -> LLVMTypeRef {
+    unsafe { LLVMDoubleType() }
+}
+
+pub fn double_ptr_type() -> LLVMTypeRef {
+    unsafe { LLVMPointerType(LLVMDoubleType(), 0) }
+}
+
 pub fn int64_ptr_type() -> LLVMTypeRef {
     unsafe { LLVMPointerType(LLVMInt64Type(), 0) }
 }
@@ -0,0 +1,15 @@
+// The parts of stdio.h the runtime uses, so types.c can be compiled for a
+// target whose C headers aren't installed
+#pragma once
+
+typedef struct FILE FILE;
+
+#ifdef __APPLE__
+extern FILE *__stderrp;
+#define stderr __stderrp
+#else
+extern FILE *stderr;
+#endif
+
+int printf(const char *format, ...);
+int fprintf(FILE *stream, const char *format, ...);
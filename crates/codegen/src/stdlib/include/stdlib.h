@@ -0,0 +1,11 @@
+// The parts of stdlib.h the runtime uses, so types.c can be compiled for a
+// target whose C headers aren't installed
+#pragma once
+
+#include <stddef.h>
+
+void *malloc(size_t size);
+void *calloc(size_t count, size_t size);
+void *realloc(void *ptr, size_t size);
+void free(void *ptr);
+void exit(int status);
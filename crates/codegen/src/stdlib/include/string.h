@@ -0,0 +1,9 @@
+// The parts of string.h the runtime uses, so types.c can be compiled for a
+// target whose C headers aren't installed
+#pragma once
+
+#include <stddef.h>
+
+void *memcpy(void *dest, const void *src, size_t count);
+void *memmove(void *dest, const void *src, size_t count);
+size_t strlen(const char *str);
@@ -0,0 +1,73 @@
+use super::create_and_set_llvm_function;
+use crate::context::LLVMFunctionCache;
+use crate::{int1_type, int32_type, int64_type, int8_ptr_type};
+use llvm_sys::core::LLVMVoidTypeInContext;
+use llvm_sys::prelude::{LLVMContextRef, LLVMModuleRef};
+
+/// # Safety
+///
+/// Load Map Helper funcs
+pub unsafe fn load_map_helper_funcs(
+    context: LLVMContextRef,
+    module: LLVMModuleRef,
+    llvm_func_cache: &mut LLVMFunctionCache,
+) {
+    let void_type = LLVMVoidTypeInContext(context);
+    // Maps are passed around as opaque pointers to the runtime's Map struct
+    let map_ptr_type = int8_ptr_type();
+
+    // createMap
+    let mut create_map_args = vec![int32_type(), int32_type(), int1_type()];
+    create_and_set_llvm_function(
+        module,
+        llvm_func_cache,
+        "createMap",
+        &mut create_map_args,
+        map_ptr_type,
+    );
+
+    // mapLength
+    let mut map_length_args = vec![map_ptr_type];
+    create_and_set_llvm_function(
+        module,
+        llvm_func_cache,
+        "mapLength",
+        &mut map_length_args,
+        int32_type(),
+    );
+
+    // Integer keys are widened to i64, string keys are passed as StringType pointers.
+    // mapInsert* returns the slot the value is stored in, mapGet* returns null when
+    // the key is missing.
+    for (suffix, key_type) in [("Int", int64_type()), ("String", int8_ptr_type())] {
+        for func_name in ["mapInsert", "mapGet"] {
+            let mut args = vec![map_ptr_type, key_type];
+            create_and_set_llvm_function(
+                module,
+                llvm_func_cache,
+                &format!("{func_name}{suffix}"),
+                &mut args,
+                int8_ptr_type(),
+            );
+        }
+
+        let mut remove_args = vec![map_ptr_type, key_type];
+        create_and_set_llvm_function(
+            module,
+            llvm_func_cache,
+            &format!("mapRemove{suffix}"),
+            &mut remove_args,
+            void_type,
+        );
+    }
+
+    // mapKeys
+    let mut map_keys_args = vec![map_ptr_type];
+    create_and_set_llvm_function(
+        module,
+        llvm_func_cache,
+        "mapKeys",
+        &mut map_keys_args,
+        int8_ptr_type(),
+    );
+}
@@ -0,0 +1,37 @@
+use super::create_and_set_llvm_function;
+use crate::context::LLVMFunctionCache;
+use crate::{int32_type, int64_type, int8_ptr_type};
+use llvm_sys::core::LLVMVoidTypeInContext;
+use llvm_sys::prelude::{LLVMContextRef, LLVMModuleRef};
+
+/// # Safety
+///
+/// Load the arithmetic helpers and the runtime panic handler from types.c
+pub unsafe fn load_math_helper_funcs(
+    context: LLVMContextRef,
+    module: LLVMModuleRef,
+    llvm_func_cache: &mut LLVMFunctionCache,
+) {
+    let void_type = LLVMVoidTypeInContext(context);
+
+    // cyclangPanic
+    let mut panic_args = vec![int8_ptr_type(), int8_ptr_type()];
+    create_and_set_llvm_function(
+        module,
+        llvm_func_cache,
+        "cyclangPanic",
+        &mut panic_args,
+        void_type,
+    );
+
+    // powInt32, powInt64 and their checked variants
+    for (func_name, int_type) in [
+        ("powInt32", int32_type()),
+        ("powInt64", int64_type()),
+        ("checkedPowInt32", int32_type()),
+        ("checkedPowInt64", int64_type()),
+    ] {
+        let mut pow_args = vec![int_type, int_type, int8_ptr_type()];
+        create_and_set_llvm_function(module, llvm_func_cache, func_name, &mut pow_args, int_type);
+    }
+}
@@ -1,19 +1,34 @@
 pub mod list;
+pub mod map;
+pub mod math;
 pub mod string;
 
-use crate::context::LLVMFunctionCache;
+use crate::context::{LLVMCallFn, LLVMFunctionCache};
+use crate::target::Target;
 use anyhow::{anyhow, Result};
 use llvm_sys::bit_reader::LLVMParseBitcodeInContext2;
-use llvm_sys::core::LLVMCreateMemoryBufferWithContentsOfFile;
+use llvm_sys::core::{
+    LLVMCreateMemoryBufferWithContentsOfFile, LLVMFunctionType, LLVMGetNamedFunction,
+};
 use llvm_sys::linker::LLVMLinkModules2;
-use llvm_sys::prelude::{LLVMContextRef, LLVMMemoryBufferRef, LLVMModuleRef};
+use llvm_sys::prelude::{LLVMContextRef, LLVMMemoryBufferRef, LLVMModuleRef, LLVMTypeRef};
 use std::ffi::CString;
+use std::fs;
+use std::process::Command;
 use std::ptr;
 
 use std::io::Write;
 use tempfile::NamedTempFile;
 
 const TYPES_BC: &[u8] = include_bytes!("./types.bc");
+const TYPES_C: &str = include_str!("./types.c");
+// Declarations of the libc functions types.c calls, standing in for the
+// headers of targets that aren't installed
+const LIBC_HEADERS: [(&str, &str); 3] = [
+    ("stdio.h", include_str!("./include/stdio.h")),
+    ("stdlib.h", include_str!("./include/stdlib.h")),
+    ("string.h", include_str!("./include/string.h")),
+];
 
 fn get_types_bc_path() -> Result<NamedTempFile, std::io::Error> {
     let mut temp_file = NamedTempFile::new()?;
@@ -21,19 +36,57 @@ fn get_types_bc_path() -> Result<NamedTempFile, std::io::Error> {
     Ok(temp_file)
 }
 
+// types.bc is built for the host, whose pointer and size_t widths a cross
+// target may not share, so the runtime is compiled from types.c for it instead
+fn compile_types_bc(target: &Target) -> Result<NamedTempFile> {
+    let triple = target.get_llvm_target_name();
+    let dir = tempfile::tempdir()?;
+    let include = dir.path().join("include");
+    fs::create_dir(&include)?;
+    for (name, contents) in LIBC_HEADERS {
+        fs::write(include.join(name), contents)?;
+    }
+    let source = dir.path().join("types.c");
+    fs::write(&source, TYPES_C)?;
+
+    let temp_file = NamedTempFile::new()?;
+    let output = Command::new("clang")
+        .arg(format!("--target={}", triple))
+        .args(["-ffreestanding", "-c", "-emit-llvm", "-O0", "-isystem"])
+        .arg(&include)
+        .arg(&source)
+        .arg("-o")
+        .arg(temp_file.path())
+        .output()
+        .map_err(|e| anyhow!("failed to run clang to compile the runtime: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "failed to compile the runtime for {}: {}",
+            triple,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(temp_file)
+}
+
 /// # Safety
 ///
-/// Loads the bitcode file generated from string.c
+/// Loads the bitcode file generated from types.c, compiled for `target` when
+/// cross compiling
 pub unsafe fn load_bitcode_and_set_stdlib_funcs(
     context: LLVMContextRef,
     module: LLVMModuleRef,
     func_cache: LLVMFunctionCache,
+    target: Option<&Target>,
 ) -> Result<LLVMFunctionCache> {
     let mut module_std: LLVMModuleRef = ptr::null_mut();
     let mut buffer: LLVMMemoryBufferRef = ptr::null_mut();
     let mut error: *mut i8 = ptr::null_mut();
 
-    let temp_file = get_types_bc_path()?;
+    let temp_file = match target {
+        Some(target) => compile_types_bc(target)?,
+        None => get_types_bc_path()?,
+    };
     let path = CString::new(temp_file.path().to_str().unwrap())?;
     // let path =
     //     CString::new("./crates/cyclang-backend/src/compiler/codegen/stdlib/types.bc").unwrap();
@@ -86,3 +139,30 @@ pub unsafe fn link_bitcode_file(
     }
     Ok(())
 }
+
+/// # Safety
+///
+/// Looks up a function linked in from types.bc and caches it under its own name.
+pub(crate) unsafe fn create_and_set_llvm_function(
+    module: LLVMModuleRef,
+    llvm_func_cache: &mut LLVMFunctionCache,
+    func_name: &str,
+    func_args: &mut Vec<LLVMTypeRef>,
+    return_type: LLVMTypeRef,
+) {
+    let llvm_function_name = CString::new(func_name).expect("CString::new failed");
+    let llvm_function = LLVMGetNamedFunction(module, llvm_function_name.as_ptr());
+    let llvm_function_type = LLVMFunctionType(
+        return_type,
+        func_args.as_mut_ptr(),
+        func_args.len() as u32,
+        0,
+    );
+    llvm_func_cache.set(
+        func_name,
+        LLVMCallFn {
+            function: llvm_function,
+            func_type: llvm_function_type,
+        },
+    );
+}
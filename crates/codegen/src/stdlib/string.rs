@@ -1,3 +1,4 @@
+use super::create_and_set_llvm_function;
 use crate::context::{LLVMCallFn, LLVMFunctionCache};
 use crate::{int1_type, int8_ptr_type};
 use llvm_sys::core::{
@@ -75,6 +76,19 @@ pub unsafe fn load_string_helper_funcs(
         },
     );
 
+    // Prints the quoted string without a trailing newline, e.g. inside a struct
+    let string_print_list_function_name =
+        CString::new("stringPrintList").expect("CString::new failed");
+    let string_print_list_function =
+        LLVMGetNamedFunction(module, string_print_list_function_name.as_ptr());
+    llvm_func_cache.set(
+        "stringPrintList",
+        LLVMCallFn {
+            function: string_print_list_function,
+            func_type: string_print_func_type,
+        },
+    );
+
     let string_is_equal_function_name = CString::new("isStringEqual").expect("CString::new failed");
     let string_is_equal_function =
         LLVMGetNamedFunction(module, string_is_equal_function_name.as_ptr());
@@ -93,4 +107,14 @@ pub unsafe fn load_string_helper_funcs(
             func_type: string_is_equal_func_type,
         },
     );
+
+    // stringChars splits a string into a list of single character strings
+    let mut string_chars_args = vec![string_ptr_type];
+    create_and_set_llvm_function(
+        module,
+        llvm_func_cache,
+        "stringChars",
+        &mut string_chars_args,
+        int8_ptr_type(),
+    );
 }
@@ -2,31 +2,7 @@
 #include <stdlib.h>
 #include <string.h>
 #include <stdbool.h>
-
-// * MACROS * // 
-#define DEFINE_GET_VALUE_FUNC(type) \
-type get_##type##Value(type* arr, int index) { \
-    return arr[index]; \
-}
-
-#define DEFINE_SET_VALUE_FUNC(type) \
-void set_##type##Value(type* arr, type value, int index) { \
-    arr[index] = value; \
-}
-
-#define DEFINE_CREATE_VALUE_FUNC(type) \
-type* create_##type##List(int size) { \
-    type* arr = (type*)malloc((size + 1) * sizeof(type)); \
-    arr[size] = -1; \
-    return arr; \
-} 
-
-DEFINE_CREATE_VALUE_FUNC(int32_t)
-DEFINE_GET_VALUE_FUNC(int32_t)
-DEFINE_SET_VALUE_FUNC(int32_t)
-DEFINE_CREATE_VALUE_FUNC(int64_t)
-DEFINE_GET_VALUE_FUNC(int64_t)
-DEFINE_SET_VALUE_FUNC(int64_t)
+#include <stdint.h>
 
 // * STRING IMPLEMENTATION * // 
 typedef struct {
@@ -108,129 +84,411 @@ bool isStringEqual(StringType *stringOne, StringType* stringTwo) {
 }
 
 // * LIST IMPLEMENTATION * //
-int32_t lenInt32List(int32_t* arr) {
-    int i = 0;
-    while (arr[i] != -1) {
-        i++;
+// Elements are stored contiguously in data and elementSize is the width of
+// one element in bytes, so the same functions back lists of every element
+// type. Codegen reads data and length directly, keep the field order in sync
+// with list_struct_type in builder.rs.
+typedef struct {
+    char* data;
+    int32_t length;
+    int32_t capacity;
+    int32_t elementSize;
+} List;
+
+List* createList(int32_t length, int32_t elementSize) {
+    List* list = malloc(sizeof(List));
+    // always allocate at least one element so data is never NULL
+    char* data = malloc((size_t)(length > 0 ? length : 1) * elementSize);
+    if (list == NULL || data == NULL) {
+        printf("Memory allocation failed\n");
+        exit(1);
     }
-    return i;
+    list->data = data;
+    list->length = length;
+    list->capacity = length;
+    list->elementSize = elementSize;
+    return list;
+}
+
+List* concatList(List* listOne, List* listTwo) {
+    List* result = createList(listOne->length + listTwo->length, listOne->elementSize);
+    size_t sizeOne = (size_t)listOne->length * listOne->elementSize;
+    size_t sizeTwo = (size_t)listTwo->length * listTwo->elementSize;
+    memcpy(result->data, listOne->data, sizeOne);
+    memcpy(result->data + sizeOne, listTwo->data, sizeTwo);
+    return result;
+}
+
+// * print functions * //
+
+// add individual print functions
+
+void stringPrint(StringType *this) {
+    printf("\"%s\"\n", this->buffer);
+}
+void stringPrintList(StringType *this) {
+    printf("\"%s\"", this->buffer);
+}
+
+#define DEFINE_PRINT_LIST_FUNC(name, type, format, value) \
+void name(List* list) { \
+    printf("["); \
+    for (int32_t i = 0; i < list->length; i++) { \
+        type item = ((type*)list->data)[i]; \
+        if (i != 0) { \
+            printf(","); \
+        } \
+        printf(format, value); \
+    } \
+    printf("]"); \
 }
 
-int32_t* concatInt32List(int32_t* arrOne, int32_t* arrTwo) {
-    int sizeOne = lenInt32List(arrOne);
-    int sizeTwo = lenInt32List(arrTwo);
-    // add a -1 terminator
-    int *result = (int32_t*)malloc((sizeOne + sizeTwo + 1) * sizeof(int32_t));
-    result[sizeOne + sizeTwo] = -1;
-    if (result == NULL) {
+DEFINE_PRINT_LIST_FUNC(printInt32List, int32_t, "%d", item)
+DEFINE_PRINT_LIST_FUNC(printInt64List, int64_t, "%lld", (long long)item)
+DEFINE_PRINT_LIST_FUNC(printFloat64List, double, "%f", item)
+DEFINE_PRINT_LIST_FUNC(printBoolList, bool, "%s", item ? "true" : "false")
+DEFINE_PRINT_LIST_FUNC(printStringList, StringType*, "\"%s\"", item->buffer)
+
+// * RUNTIME ERRORS * //
+// location is the `file:line:column` of the expression that failed
+void cyclangPanic(const char* message, const char* location) {
+    fprintf(stderr, "panic at %s: %s\n", location, message);
+    exit(1);
+}
+
+void cyclangIndexPanic(int32_t index, int32_t length, const char* location) {
+    fprintf(stderr, "panic at %s: index %d out of bounds for list of length %d\n",
+            location, index, length);
+    exit(1);
+}
+
+void cyclangSlicePanic(int32_t start, int32_t end, int32_t length, const char* location) {
+    fprintf(stderr, "panic at %s: slice %d..%d out of range for list of length %d\n",
+            location, start, end, length);
+    exit(1);
+}
+
+// * LIST MUTATION * //
+// Grows the buffer so at least `needed` elements fit. Capacity doubles so a
+// run of pushes costs amortized O(1) each.
+static void reserveList(List* list, int32_t needed) {
+    if (needed <= list->capacity) {
+        return;
+    }
+    int32_t capacity = list->capacity > 0 ? list->capacity * 2 : 4;
+    if (capacity < needed) {
+        capacity = needed;
+    }
+    char* data = realloc(list->data, (size_t)capacity * list->elementSize);
+    if (data == NULL) {
         printf("Memory allocation failed\n");
         exit(1);
     }
+    list->data = data;
+    list->capacity = capacity;
+}
 
-    // Copy over first elements
-    for (int i = 0; i < sizeOne; i++) {
-        result[i] = arrOne[i];
-    }
+// Appends a slot to the end of the list and returns it; codegen stores the
+// element since only it knows the element type
+char* pushList(List* list) {
+    reserveList(list, list->length + 1);
+    char* slot = list->data + (size_t)list->length * list->elementSize;
+    list->length++;
+    return slot;
+}
 
-    // Copy over second elements
-    for (int i = 0; i < sizeTwo; i++) {
-        result[sizeOne + i] = arrTwo[i];
+// Opens a slot at index, shifting later elements up, and returns it
+char* insertList(List* list, int32_t index, const char* location) {
+    if (index < 0 || index > list->length) {
+        cyclangIndexPanic(index, list->length, location);
     }
-    return result;
+    reserveList(list, list->length + 1);
+    char* slot = list->data + (size_t)index * list->elementSize;
+    memmove(slot + list->elementSize, slot, (size_t)(list->length - index) * list->elementSize);
+    list->length++;
+    return slot;
 }
 
-StringType** createStringList(int size) {
-    // set sentinel value of NULL hence size + 1
-    StringType **stringArray = malloc((size + 1) * sizeof(StringType *));
-    stringArray[size] = NULL;
-    return stringArray;
+// Closes the slot at index, codegen bounds checks and loads the element first
+void removeList(List* list, int32_t index) {
+    char* slot = list->data + (size_t)index * list->elementSize;
+    memmove(slot, slot + list->elementSize, (size_t)(list->length - index - 1) * list->elementSize);
+    list->length--;
 }
 
-StringType* getStringValue(StringType** arr, int index) {
-    return arr[index];
+void clearList(List* list) {
+    list->length = 0;
 }
 
-void setStringValue(StringType** arr, StringType* value, int index) {
-    arr[index] = value;
+// Copies the elements in [start, end) into a new list
+List* sliceList(List* list, int32_t start, int32_t end, const char* location) {
+    if (start < 0 || end > list->length || start > end) {
+        cyclangSlicePanic(start, end, list->length, location);
+    }
+    List* result = createList(end - start, list->elementSize);
+    memcpy(result->data, list->data + (size_t)start * list->elementSize,
+           (size_t)(end - start) * list->elementSize);
+    return result;
 }
 
-void printStringList(StringType** arr) {
-    int i = 0;
-    printf("[");
-    while (arr[i] != NULL) {
-        if (i != 0) {
-            printf(",");
+// * STRING ITERATION * //
+// Splits a string into one string per UTF-8 character, which is what a
+// `for (c in s)` loop walks over.
+List* stringChars(StringType* string) {
+    List* chars = createList(0, sizeof(StringType*));
+    int32_t start = 0;
+    while (start < string->length) {
+        // continuation bytes (10xxxxxx) belong to the character before them
+        int32_t end = start + 1;
+        while (end < string->length && (string->buffer[end] & 0xC0) == 0x80) {
+            end++;
         }
-        printf("\"%s\"", arr[i]->buffer);
-        i++;
+        char* buffer = malloc(end - start + 1);
+        memcpy(buffer, string->buffer + start, end - start);
+        buffer[end - start] = '\0';
+        *(StringType**)pushList(chars) = stringInit(buffer);
+        free(buffer);
+        start = end;
     }
-    printf("]");
+    return chars;
 }
 
-int32_t lenStringList(StringType** arr) {
-    int i = 0;
-    while (arr[i] != NULL) {
-        i++;
+// * MAP IMPLEMENTATION * //
+// Insertion ordered hash map. Entries are appended to keys/values and
+// buckets is an open addressing table holding entry index + 1, with 0 for an
+// empty bucket, so walking the entries visits keys in insertion order.
+// Removed entries are marked dead and dropped the next time the map grows.
+typedef union {
+    int64_t integer;
+    StringType* string;
+} MapKey;
+
+typedef struct {
+    MapKey* keys;
+    char* values;
+    bool* live;
+    int32_t* buckets;
+    int32_t length;
+    int32_t entryCount;
+    int32_t entryCapacity;
+    int32_t bucketCount;
+    int32_t keySize;
+    int32_t valueSize;
+    bool stringKeys;
+} Map;
+
+static uint64_t hashMapKey(Map* map, MapKey key) {
+    uint64_t hash;
+    if (map->stringKeys) {
+        // FNV-1a
+        hash = 14695981039346656037ULL;
+        for (int32_t i = 0; i < key.string->length; i++) {
+            hash ^= (unsigned char)key.string->buffer[i];
+            hash *= 1099511628211ULL;
+        }
+    } else {
+        // Mix the bits so sequential integers spread across the buckets
+        hash = (uint64_t)key.integer;
+        hash ^= hash >> 33;
+        hash *= 0xff51afd7ed558ccdULL;
+        hash ^= hash >> 33;
     }
-    return i;
+    return hash;
 }
 
-StringType** concatStringList(StringType** arrOne, StringType** arrTwo) {
-    int sizeOne = lenStringList(arrOne);
-    int sizeTwo = lenStringList(arrTwo);
+// Returns the bucket holding key, or the empty bucket it belongs in
+static int32_t findMapBucket(Map* map, MapKey key) {
+    uint32_t mask = (uint32_t)map->bucketCount - 1;
+    uint32_t bucket = (uint32_t)hashMapKey(map, key) & mask;
+    while (map->buckets[bucket] != 0) {
+        int32_t entry = map->buckets[bucket] - 1;
+        if (map->live[entry]) {
+            MapKey other = map->keys[entry];
+            bool equal = map->stringKeys ? isStringEqual(other.string, key.string)
+                                         : other.integer == key.integer;
+            if (equal) {
+                return (int32_t)bucket;
+            }
+        }
+        bucket = (bucket + 1) & mask;
+    }
+    return (int32_t)bucket;
+}
 
-    // add a NULL terminator
-    StringType** stringArray = malloc((sizeOne + sizeTwo + 1) * sizeof(StringType *));
-    stringArray[sizeOne + sizeTwo] = NULL;
-    if (stringArray == NULL) {
+// Moves the live entries into room for `capacity` entries and rebuilds the
+// buckets, which are kept at least twice as many as the entries
+static void resizeMap(Map* map, int32_t capacity) {
+    int32_t bucketCount = 8;
+    while (bucketCount < capacity * 2) {
+        bucketCount *= 2;
+    }
+    MapKey* keys = malloc(sizeof(MapKey) * capacity);
+    char* values = malloc((size_t)capacity * map->valueSize);
+    bool* live = malloc(sizeof(bool) * capacity);
+    int32_t* buckets = calloc(bucketCount, sizeof(int32_t));
+    if (keys == NULL || values == NULL || live == NULL || buckets == NULL) {
         printf("Memory allocation failed\n");
         exit(1);
     }
-
-    // Copy over first elements
-    for (int i = 0; i < sizeOne; i++) {
-        stringArray[i] = arrOne[i];
+    int32_t count = 0;
+    for (int32_t i = 0; i < map->entryCount; i++) {
+        if (map->live[i]) {
+            keys[count] = map->keys[i];
+            memcpy(values + (size_t)count * map->valueSize,
+                   map->values + (size_t)i * map->valueSize, map->valueSize);
+            live[count] = true;
+            count++;
+        }
     }
-
-    // Copy over second elements
-    for (int i = 0; i < sizeTwo; i++) {
-        stringArray[sizeOne + i] = arrTwo[i];
+    free(map->keys);
+    free(map->values);
+    free(map->live);
+    free(map->buckets);
+    map->keys = keys;
+    map->values = values;
+    map->live = live;
+    map->buckets = buckets;
+    map->entryCount = count;
+    map->entryCapacity = capacity;
+    map->bucketCount = bucketCount;
+    for (int32_t i = 0; i < count; i++) {
+        map->buckets[findMapBucket(map, keys[i])] = i + 1;
     }
-    return stringArray;
 }
 
-// * print functions * //
+Map* createMap(int32_t keySize, int32_t valueSize, bool stringKeys) {
+    Map* map = calloc(1, sizeof(Map));
+    if (map == NULL) {
+        printf("Memory allocation failed\n");
+        exit(1);
+    }
+    map->keySize = keySize;
+    map->valueSize = valueSize;
+    map->stringKeys = stringKeys;
+    resizeMap(map, 8);
+    return map;
+}
 
-// add individual print functions
+int32_t mapLength(Map* map) {
+    return map->length;
+}
 
-void stringPrint(StringType *this) {
-    printf("\"%s\"\n", this->buffer);
+// Returns the value slot for key, adding the key if it is missing; codegen
+// stores the value since only it knows the value type
+static char* mapInsert(Map* map, MapKey key) {
+    int32_t bucket = findMapBucket(map, key);
+    if (map->buckets[bucket] == 0) {
+        if (map->entryCount == map->entryCapacity) {
+            resizeMap(map, map->length * 2 > 8 ? map->length * 2 : 8);
+            bucket = findMapBucket(map, key);
+        }
+        int32_t entry = map->entryCount;
+        map->keys[entry] = key;
+        map->live[entry] = true;
+        map->buckets[bucket] = entry + 1;
+        map->entryCount++;
+        map->length++;
+    }
+    return map->values + (size_t)(map->buckets[bucket] - 1) * map->valueSize;
 }
-void stringPrintList(StringType *this) {
-    printf("\"%s\"", this->buffer);
+
+// Returns the value slot for key, or NULL if the key is missing
+static char* mapGet(Map* map, MapKey key) {
+    int32_t bucket = findMapBucket(map, key);
+    if (map->buckets[bucket] == 0) {
+        return NULL;
+    }
+    return map->values + (size_t)(map->buckets[bucket] - 1) * map->valueSize;
 }
-void printInt32List(int32_t* arr) {
-    int i = 0;
-    printf("[");
-    while (arr[i] != -1) {
-        if (i != 0) {
-            printf(",");
-        }
-        printf("%d", arr[i]);
-        i++;
+
+static void mapRemove(Map* map, MapKey key) {
+    int32_t bucket = findMapBucket(map, key);
+    if (map->buckets[bucket] != 0) {
+        map->live[map->buckets[bucket] - 1] = false;
+        map->length--;
     }
-    printf("]");
 }
 
-void printInt64List(int64_t* arr) {
-    int i = 0;
-    printf("[");
-    while (arr[i] != -1) {
-        if (i != 0) {
-            printf(",");
+#define DEFINE_MAP_KEY_FUNCS(suffix, type, field) \
+char* mapInsert##suffix(Map* map, type key) { \
+    MapKey mapKey; \
+    mapKey.field = key; \
+    return mapInsert(map, mapKey); \
+} \
+char* mapGet##suffix(Map* map, type key) { \
+    MapKey mapKey; \
+    mapKey.field = key; \
+    return mapGet(map, mapKey); \
+} \
+void mapRemove##suffix(Map* map, type key) { \
+    MapKey mapKey; \
+    mapKey.field = key; \
+    mapRemove(map, mapKey); \
+}
+
+// Integer keys of either width are widened to int64_t by codegen
+DEFINE_MAP_KEY_FUNCS(Int, int64_t, integer)
+DEFINE_MAP_KEY_FUNCS(String, StringType*, string)
+
+// Copies the keys into a new list, in insertion order
+List* mapKeys(Map* map) {
+    List* keys = createList(map->length, map->keySize);
+    int32_t count = 0;
+    for (int32_t i = 0; i < map->entryCount; i++) {
+        if (map->live[i]) {
+            char* slot = keys->data + (size_t)count * map->keySize;
+            if (map->keySize == sizeof(int32_t)) {
+                *(int32_t*)slot = (int32_t)map->keys[i].integer;
+            } else {
+                memcpy(slot, &map->keys[i], sizeof(MapKey));
+            }
+            count++;
         }
-        printf("%lld", arr[i]);
-        i++;
     }
-    printf("]");
-}
\ No newline at end of file
+    return keys;
+}
+
+// * ARITHMETIC * //
+// Exponentiation by squaring, wrapping on overflow like the other integer
+// operators
+#define DEFINE_POW_FUNC(type, utype, suffix) \
+type pow##suffix(type base, type exponent, const char* location) { \
+    if (exponent < 0) { \
+        cyclangPanic("attempt to raise to a negative power", location); \
+    } \
+    utype result = 1; \
+    utype factor = (utype)base; \
+    while (exponent > 0) { \
+        if (exponent & 1) { \
+            result *= factor; \
+        } \
+        factor *= factor; \
+        exponent >>= 1; \
+    } \
+    return (type)result; \
+}
+
+// Same as above but panics instead of wrapping, for checked arithmetic
+#define DEFINE_CHECKED_POW_FUNC(type, suffix) \
+type checkedPow##suffix(type base, type exponent, const char* location) { \
+    if (exponent < 0) { \
+        cyclangPanic("attempt to raise to a negative power", location); \
+    } \
+    type result = 1; \
+    type factor = base; \
+    while (exponent > 0) { \
+        if ((exponent & 1) && __builtin_mul_overflow(result, factor, &result)) { \
+            cyclangPanic("attempt to raise to a power with overflow", location); \
+        } \
+        exponent >>= 1; \
+        if (exponent > 0 && __builtin_mul_overflow(factor, factor, &factor)) { \
+            cyclangPanic("attempt to raise to a power with overflow", location); \
+        } \
+    } \
+    return result; \
+}
+
+DEFINE_POW_FUNC(int32_t, uint32_t, Int32)
+DEFINE_POW_FUNC(int64_t, uint64_t, Int64)
+DEFINE_CHECKED_POW_FUNC(int32_t, Int32)
+DEFINE_CHECKED_POW_FUNC(int64_t, Int64)
@@ -1,6 +1,17 @@
-use llvm_sys::target::{LLVMInitializeWebAssemblyAsmPrinter, LLVMInitializeWebAssemblyTarget};
+use llvm_sys::core::LLVMDisposeMessage;
+use llvm_sys::target::{
+    LLVMInitializeAArch64AsmPrinter, LLVMInitializeAArch64Target, LLVMInitializeAArch64TargetInfo,
+    LLVMInitializeAArch64TargetMC, LLVMInitializeARMAsmPrinter, LLVMInitializeARMTarget,
+    LLVMInitializeARMTargetInfo, LLVMInitializeARMTargetMC, LLVMInitializeWebAssemblyAsmPrinter,
+    LLVMInitializeWebAssemblyTarget, LLVMInitializeWebAssemblyTargetInfo,
+    LLVMInitializeWebAssemblyTargetMC, LLVMInitializeX86AsmPrinter, LLVMInitializeX86Target,
+    LLVMInitializeX86TargetInfo, LLVMInitializeX86TargetMC, LLVM_InitializeAllAsmPrinters,
+    LLVM_InitializeAllTargetInfos, LLVM_InitializeAllTargetMCs, LLVM_InitializeAllTargets,
+};
+use llvm_sys::target_machine::LLVMGetDefaultTargetTriple;
+use std::ffi::CStr;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq)]
 #[allow(non_camel_case_types)]
 pub enum Target {
     wasm,
@@ -8,9 +19,12 @@ pub enum Target {
     arm64,
     x86_32,
     x86_64,
+    /// Any other LLVM target triple, e.g. `riscv64-unknown-linux-gnu`
+    triple(String),
 }
 
 impl Target {
+    /// Accepts one of the named targets or a full target triple.
     pub fn from_target_name(s: &str) -> Option<Self> {
         match s {
             "wasm" => Some(Target::wasm),
@@ -18,40 +32,86 @@ impl Target {
             "arm64" => Some(Target::arm64),
             "x86_32" => Some(Target::x86_32),
             "x86_64" => Some(Target::x86_64),
+            _ if s.contains('-') => Some(Target::triple(s.to_string())),
             _ => None,
         }
     }
 
     pub fn get_llvm_target_name(&self) -> String {
         match self {
-            Target::wasm => "wasm32-unknown-unknown-wasm".to_string(),
+            Target::wasm => "wasm32-unknown-unknown".to_string(),
             Target::arm32 => "arm-unknown-linux-gnueabihf".to_string(),
             Target::arm64 => "aarch64-unknown-linux-gnu".to_string(),
-            Target::x86_32 => "i386-unknown-unknown-elf".to_string(),
-            Target::x86_64 => "x86_64-unknown-unknown-elf".to_string(),
+            Target::x86_32 => "i686-unknown-linux-gnu".to_string(),
+            Target::x86_64 => "x86_64-unknown-linux-gnu".to_string(),
+            Target::triple(triple) => triple.clone(),
         }
     }
 
+    /// Whether code built for this target runs on the machine compiling it,
+    /// i.e. the target has the host's architecture and operating system.
+    pub fn is_host(&self) -> bool {
+        triple_arch_and_os(&self.get_llvm_target_name()) == triple_arch_and_os(&host_triple())
+    }
+
     pub fn initialize(&self) {
         unsafe {
             match self {
                 Target::wasm => {
+                    LLVMInitializeWebAssemblyTargetInfo();
                     LLVMInitializeWebAssemblyTarget();
+                    LLVMInitializeWebAssemblyTargetMC();
                     LLVMInitializeWebAssemblyAsmPrinter();
                 }
                 Target::arm32 => {
-                    unimplemented!("arm32 not implemented yet ")
+                    LLVMInitializeARMTargetInfo();
+                    LLVMInitializeARMTarget();
+                    LLVMInitializeARMTargetMC();
+                    LLVMInitializeARMAsmPrinter();
                 }
                 Target::arm64 => {
-                    unimplemented!("arm64 not implemented yet ")
+                    LLVMInitializeAArch64TargetInfo();
+                    LLVMInitializeAArch64Target();
+                    LLVMInitializeAArch64TargetMC();
+                    LLVMInitializeAArch64AsmPrinter();
                 }
-                Target::x86_32 => {
-                    unimplemented!("x86_32 not implemented yet ")
+                Target::x86_32 | Target::x86_64 => {
+                    LLVMInitializeX86TargetInfo();
+                    LLVMInitializeX86Target();
+                    LLVMInitializeX86TargetMC();
+                    LLVMInitializeX86AsmPrinter();
                 }
-                Target::x86_64 => {
-                    unimplemented!("x86_64 not implemented yet ")
+                // The triple could name any backend LLVM was built with
+                Target::triple(_) => {
+                    LLVM_InitializeAllTargetInfos();
+                    LLVM_InitializeAllTargets();
+                    LLVM_InitializeAllTargetMCs();
+                    LLVM_InitializeAllAsmPrinters();
                 }
             }
         }
     }
 }
+
+/// The target triple of the machine compiling, as LLVM reports it.
+pub fn host_triple() -> String {
+    unsafe {
+        let triple = LLVMGetDefaultTargetTriple();
+        let host = CStr::from_ptr(triple).to_string_lossy().to_string();
+        LLVMDisposeMessage(triple);
+        host
+    }
+}
+
+// The architecture and operating system of an `arch-vendor-os[-env]` triple,
+// with Apple's name for aarch64 and any OS version dropped
+fn triple_arch_and_os(triple: &str) -> (String, String) {
+    let mut parts = triple.split('-');
+    let arch = match parts.next().unwrap_or_default() {
+        "arm64" => "aarch64",
+        arch => arch,
+    };
+    let os = parts.nth(1).unwrap_or_default();
+    let os = os.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    (arch.to_string(), os.to_string())
+}
@@ -1,102 +1,566 @@
+use parser::Span;
+use std::fmt;
+
 #[derive(Debug, Clone)]
 pub enum TypedExpression {
     Number32 {
         value: i32,
-        // span: Span,
+        span: Span,
     },
     Number64 {
         value: i64,
+        span: Span,
+    },
+    Float64 {
+        value: f64,
+        span: Span,
     },
     String {
         value: String,
+        span: Span,
     },
     Bool {
         value: bool,
+        span: Span,
     },
     Binary {
         left: Box<TypedExpression>,
         right: Box<TypedExpression>,
         op: String,
+        span: Span,
+    },
+    Unary {
+        op: String,
+        operand: Box<TypedExpression>,
+        span: Span,
     },
     CallStmt {
         callee: Box<TypedExpression>,
         args: Vec<TypedExpression>,
+        span: Span,
     },
+    // Functions of an imported module are named `<module>::<name>`
     FuncStmt {
         name: String,
         args: Vec<(String, ResolvedType)>,
         return_type: ResolvedType,
         body: Box<TypedExpression>,
+        // Set for `pub fn`, which other modules are allowed to call
+        is_pub: bool,
+        span: Span,
     },
     ExternFuncStmt {
         name: String,
         args: Vec<(String, ResolvedType)>,
         return_type: ResolvedType,
+        span: Span,
     },
     ExternModule {
         path: String,
+        span: Span,
     },
     BlockStmt {
         statements: Vec<TypedExpression>,
+        span: Span,
     },
     Variable {
         name: String,
+        span: Span,
     },
     Print {
         value: Box<TypedExpression>,
+        span: Span,
     },
     ReturnStmt {
         value: Box<TypedExpression>,
+        span: Span,
+    },
+    BreakStmt {
+        label: Option<String>,
+        span: Span,
+    },
+    ContinueStmt {
+        label: Option<String>,
+        span: Span,
     },
-    BreakStmt,
     LetStmt {
         name: String,
         var_type: Option<ResolvedType>,
         value: Box<TypedExpression>,
+        span: Span,
     },
     IfStmt {
         condition: Box<TypedExpression>,
         then_branch: Box<TypedExpression>,
         else_branch: Option<Box<TypedExpression>>,
+        span: Span,
     },
     WhileStmt {
         condition: Box<TypedExpression>,
         body: Box<TypedExpression>,
+        // Runs after every iteration of a `for` loop, including ones cut short by `continue`
+        step: Option<Box<TypedExpression>>,
+        // Set for `name: while (...)`, targeted by `break name` and `continue name`
+        label: Option<String>,
+        span: Span,
     },
     AssignStmt {
         name: String,
         value: Box<TypedExpression>,
+        span: Span,
     },
     Grouping {
         inner: Box<TypedExpression>,
+        span: Span,
     },
     List {
         elements: Vec<TypedExpression>,
         element_type: ResolvedType,
+        span: Span,
     },
     ListIndex {
         list: Box<TypedExpression>,
         index: Box<TypedExpression>,
+        span: Span,
     },
     ListAssign {
         name: String,
         index: Box<TypedExpression>,
         value: Box<TypedExpression>,
+        span: Span,
     },
     Len {
         value: Box<TypedExpression>,
+        span: Span,
+    },
+    // List builtin written as a call, e.g. push(xs, 1) or pop(xs)
+    ListCall {
+        op: ListOp,
+        list: Box<TypedExpression>,
+        args: Vec<TypedExpression>,
+        span: Span,
+    },
+    // xs[start:end], a missing bound means the start or end of the list
+    ListSlice {
+        list: Box<TypedExpression>,
+        start: Option<Box<TypedExpression>>,
+        end: Option<Box<TypedExpression>>,
+        span: Span,
+    },
+    Map {
+        entries: Vec<(TypedExpression, TypedExpression)>,
+        key_type: ResolvedType,
+        value_type: ResolvedType,
+        span: Span,
+    },
+    // m[key], panics at runtime when the key is missing
+    MapIndex {
+        map: Box<TypedExpression>,
+        key: Box<TypedExpression>,
+        span: Span,
+    },
+    // m[key] = value, inserting the key if it isn't already present
+    MapAssign {
+        name: String,
+        key: Box<TypedExpression>,
+        value: Box<TypedExpression>,
+        span: Span,
+    },
+    // Map builtin written as a call, e.g. contains(m, "a") or keys(m)
+    MapCall {
+        op: MapOp,
+        map: Box<TypedExpression>,
+        args: Vec<TypedExpression>,
+        span: Span,
+    },
+    // The list a for-each loop walks over: a list itself, the keys of a map
+    // or the characters of a string
+    IterItems {
+        value: Box<TypedExpression>,
+        span: Span,
+    },
+    // struct Point { x: i32, y: i32 }
+    StructStmt {
+        name: String,
+        fields: Vec<(String, ResolvedType)>,
+        span: Span,
+    },
+    // Point { x: 1, y: 2 }, with the field values in declaration order
+    StructLiteral {
+        name: String,
+        fields: Vec<TypedExpression>,
+        span: Span,
+    },
+    // p.x, `index` is the field's position in the struct declaration
+    FieldAccess {
+        value: Box<TypedExpression>,
+        field: String,
+        index: u32,
+        span: Span,
+    },
+    // p.x = value, where `target` is the variable, field or list element holding the struct
+    FieldAssign {
+        target: Box<TypedExpression>,
+        field: String,
+        index: u32,
+        value: Box<TypedExpression>,
+        span: Span,
+    },
+    // enum Shape { Circle(i32), Rect(i32, i32) }, a variant's tag is its position
+    EnumStmt {
+        name: String,
+        variants: Vec<(String, Vec<ResolvedType>)>,
+        span: Span,
+    },
+    // Shape::Circle(3)
+    EnumValue {
+        enum_name: String,
+        tag: u32,
+        values: Vec<TypedExpression>,
+        span: Span,
+    },
+    // match (value) { ... }, `variants` names the enum's variants in tag order
+    MatchStmt {
+        value: Box<TypedExpression>,
+        enum_name: String,
+        variants: Vec<String>,
+        arms: Vec<TypedExpression>,
+        span: Span,
+    },
+    // One arm of a match, `tag` is None for `_`. Bindings line up with the
+    // variant's payload, `_` bindings are skipped.
+    MatchArm {
+        tag: Option<u32>,
+        bindings: Vec<(String, ResolvedType)>,
+        body: Box<TypedExpression>,
+        span: Span,
     },
+    // A value of an optional type, `value` is None for nil
+    OptionalValue {
+        value: Option<Box<TypedExpression>>,
+        value_type: ResolvedType,
+        span: Span,
+    },
+    // x == nil, or x != nil when `is_nil` is false
+    NilCheck {
+        value: Box<TypedExpression>,
+        is_nil: bool,
+        span: Span,
+    },
+    // unwrap(x), panics at runtime when x is nil
+    Unwrap {
+        value: Box<TypedExpression>,
+        span: Span,
+    },
+    // |x| x + 1, `captures` are the enclosing variables copied into its environment
+    Lambda {
+        params: Vec<(String, ResolvedType)>,
+        return_type: ResolvedType,
+        captures: Vec<(String, ResolvedType)>,
+        body: Box<TypedExpression>,
+        span: Span,
+    },
+    // A named function used as a value, e.g. passed to map
+    FunctionRef {
+        name: String,
+        args: Vec<ResolvedType>,
+        return_type: ResolvedType,
+        span: Span,
+    },
+    // impl string { ... }, each method is a FuncStmt named `<type>.<method>`
+    ImplStmt {
+        methods: Vec<TypedExpression>,
+        span: Span,
+    },
+    // Numeric conversion, e.g. f64(x) or i32(y)
+    Cast {
+        value: Box<TypedExpression>,
+        from: ResolvedType,
+        to: ResolvedType,
+        span: Span,
+    },
+}
+
+impl TypedExpression {
+    /// Returns the source span of the expression this node was resolved from.
+    pub fn span(&self) -> Span {
+        match self {
+            TypedExpression::Number32 { span, .. }
+            | TypedExpression::Number64 { span, .. }
+            | TypedExpression::Float64 { span, .. }
+            | TypedExpression::String { span, .. }
+            | TypedExpression::Bool { span, .. }
+            | TypedExpression::Binary { span, .. }
+            | TypedExpression::Unary { span, .. }
+            | TypedExpression::CallStmt { span, .. }
+            | TypedExpression::FuncStmt { span, .. }
+            | TypedExpression::ExternFuncStmt { span, .. }
+            | TypedExpression::ExternModule { span, .. }
+            | TypedExpression::BlockStmt { span, .. }
+            | TypedExpression::Variable { span, .. }
+            | TypedExpression::Print { span, .. }
+            | TypedExpression::ReturnStmt { span, .. }
+            | TypedExpression::BreakStmt { span, .. }
+            | TypedExpression::ContinueStmt { span, .. }
+            | TypedExpression::LetStmt { span, .. }
+            | TypedExpression::IfStmt { span, .. }
+            | TypedExpression::WhileStmt { span, .. }
+            | TypedExpression::AssignStmt { span, .. }
+            | TypedExpression::Grouping { span, .. }
+            | TypedExpression::List { span, .. }
+            | TypedExpression::ListIndex { span, .. }
+            | TypedExpression::ListAssign { span, .. }
+            | TypedExpression::Len { span, .. }
+            | TypedExpression::ListCall { span, .. }
+            | TypedExpression::ListSlice { span, .. }
+            | TypedExpression::Map { span, .. }
+            | TypedExpression::MapIndex { span, .. }
+            | TypedExpression::MapAssign { span, .. }
+            | TypedExpression::MapCall { span, .. }
+            | TypedExpression::IterItems { span, .. }
+            | TypedExpression::StructStmt { span, .. }
+            | TypedExpression::StructLiteral { span, .. }
+            | TypedExpression::FieldAccess { span, .. }
+            | TypedExpression::FieldAssign { span, .. }
+            | TypedExpression::ImplStmt { span, .. }
+            | TypedExpression::EnumStmt { span, .. }
+            | TypedExpression::EnumValue { span, .. }
+            | TypedExpression::MatchStmt { span, .. }
+            | TypedExpression::MatchArm { span, .. }
+            | TypedExpression::OptionalValue { span, .. }
+            | TypedExpression::NilCheck { span, .. }
+            | TypedExpression::Unwrap { span, .. }
+            | TypedExpression::Lambda { span, .. }
+            | TypedExpression::FunctionRef { span, .. }
+            | TypedExpression::Cast { span, .. } => *span,
+        }
+    }
+
+    /// Returns the expressions nested directly inside this one, in source order.
+    pub fn children(&self) -> Vec<&TypedExpression> {
+        match self {
+            TypedExpression::Number32 { .. }
+            | TypedExpression::Number64 { .. }
+            | TypedExpression::Float64 { .. }
+            | TypedExpression::String { .. }
+            | TypedExpression::Bool { .. }
+            | TypedExpression::ExternFuncStmt { .. }
+            | TypedExpression::ExternModule { .. }
+            | TypedExpression::Variable { .. }
+            | TypedExpression::BreakStmt { .. }
+            | TypedExpression::ContinueStmt { .. }
+            | TypedExpression::StructStmt { .. }
+            | TypedExpression::EnumStmt { .. }
+            | TypedExpression::FunctionRef { .. } => vec![],
+            TypedExpression::Binary { left, right, .. } => vec![left, right],
+            TypedExpression::CallStmt { callee, args, .. } => {
+                std::iter::once(callee.as_ref()).chain(args).collect()
+            }
+            TypedExpression::BlockStmt { statements, .. } => statements.iter().collect(),
+            TypedExpression::IfStmt {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => std::iter::once(condition.as_ref())
+                .chain(std::iter::once(then_branch.as_ref()))
+                .chain(else_branch.as_deref())
+                .collect(),
+            TypedExpression::WhileStmt {
+                condition,
+                body,
+                step,
+                ..
+            } => std::iter::once(condition.as_ref())
+                .chain(std::iter::once(body.as_ref()))
+                .chain(step.as_deref())
+                .collect(),
+            TypedExpression::List { elements, .. }
+            | TypedExpression::StructLiteral {
+                fields: elements, ..
+            }
+            | TypedExpression::EnumValue {
+                values: elements, ..
+            }
+            | TypedExpression::ImplStmt {
+                methods: elements, ..
+            } => elements.iter().collect(),
+            TypedExpression::ListIndex { list, index, .. } => vec![list, index],
+            TypedExpression::ListAssign { index, value, .. } => vec![index, value],
+            TypedExpression::ListCall { list, args, .. }
+            | TypedExpression::MapCall {
+                map: list, args, ..
+            } => std::iter::once(list.as_ref()).chain(args).collect(),
+            TypedExpression::ListSlice {
+                list, start, end, ..
+            } => std::iter::once(list.as_ref())
+                .chain(start.as_deref())
+                .chain(end.as_deref())
+                .collect(),
+            TypedExpression::Map { entries, .. } => entries
+                .iter()
+                .flat_map(|(key, value)| [key, value])
+                .collect(),
+            TypedExpression::MapIndex { map, key, .. } => vec![map, key],
+            TypedExpression::MapAssign { key, value, .. } => vec![key, value],
+            TypedExpression::FieldAssign { target, value, .. } => vec![target, value],
+            TypedExpression::MatchStmt { value, arms, .. } => {
+                std::iter::once(value.as_ref()).chain(arms).collect()
+            }
+            TypedExpression::OptionalValue { value, .. } => value.as_deref().into_iter().collect(),
+            TypedExpression::Unary { operand: inner, .. }
+            | TypedExpression::FuncStmt { body: inner, .. }
+            | TypedExpression::Print { value: inner, .. }
+            | TypedExpression::ReturnStmt { value: inner, .. }
+            | TypedExpression::LetStmt { value: inner, .. }
+            | TypedExpression::AssignStmt { value: inner, .. }
+            | TypedExpression::Grouping { inner, .. }
+            | TypedExpression::Len { value: inner, .. }
+            | TypedExpression::IterItems { value: inner, .. }
+            | TypedExpression::FieldAccess { value: inner, .. }
+            | TypedExpression::MatchArm { body: inner, .. }
+            | TypedExpression::NilCheck { value: inner, .. }
+            | TypedExpression::Unwrap { value: inner, .. }
+            | TypedExpression::Lambda { body: inner, .. }
+            | TypedExpression::Cast { value: inner, .. } => vec![inner],
+        }
+    }
+}
+
+/// Builtins that mutate a list in place, or in the case of map and filter
+/// build a new one from it. The list is always the first argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListOp {
+    Push,
+    Pop,
+    Insert,
+    Remove,
+    Clear,
+    Map,
+    Filter,
+}
+
+impl ListOp {
+    pub fn from_name(name: &str) -> Option<ListOp> {
+        match name {
+            "push" => Some(ListOp::Push),
+            "pop" => Some(ListOp::Pop),
+            "insert" => Some(ListOp::Insert),
+            "remove" => Some(ListOp::Remove),
+            "clear" => Some(ListOp::Clear),
+            "map" => Some(ListOp::Map),
+            "filter" => Some(ListOp::Filter),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ListOp::Push => "push",
+            ListOp::Pop => "pop",
+            ListOp::Insert => "insert",
+            ListOp::Remove => "remove",
+            ListOp::Clear => "clear",
+            ListOp::Map => "map",
+            ListOp::Filter => "filter",
+        }
+    }
+}
+
+/// Builtins that query or update a map. The map is always the first argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapOp {
+    Get,
+    Contains,
+    Remove,
+    Keys,
+}
+
+impl MapOp {
+    pub fn from_name(name: &str) -> Option<MapOp> {
+        match name {
+            "get" => Some(MapOp::Get),
+            "contains" => Some(MapOp::Contains),
+            "remove" => Some(MapOp::Remove),
+            "keys" => Some(MapOp::Keys),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapOp::Get => "get",
+            MapOp::Contains => "contains",
+            MapOp::Remove => "remove",
+            MapOp::Keys => "keys",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ResolvedType {
     I32,
     I64,
+    F64,
     String,
     Bool,
     Binary(Box<ResolvedType>, String, Box<ResolvedType>),
     List(Box<ResolvedType>),
+    Map(Box<ResolvedType>, Box<ResolvedType>),
+    // A user-defined struct, referred to by name
+    Struct(String),
+    // A user-defined enum, referred to by name
+    Enum(String),
+    // A value of the inner type or nil
+    Optional(Box<ResolvedType>),
     Function(Vec<ResolvedType>, Box<ResolvedType>),
     CallStmt(Vec<ResolvedType>, Box<ResolvedType>),
     Void,
+    // Stands in for an expression that failed to resolve, so one mistake
+    // doesn't cascade into further diagnostics
+    Error,
+}
+
+impl ResolvedType {
+    pub fn is_error(&self) -> bool {
+        matches!(self, ResolvedType::Error)
+    }
+
+    pub fn is_optional(&self) -> bool {
+        matches!(self, ResolvedType::Optional(_))
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, ResolvedType::I32 | ResolvedType::I64 | ResolvedType::F64)
+    }
+
+    /// Whether a value of type `other` can be used where `self` is expected.
+    /// An unresolved type matches anything since it has already been reported.
+    pub fn is_compatible_with(&self, other: &ResolvedType) -> bool {
+        self.is_error() || other.is_error() || self == other
+    }
+}
+
+// Formats types the way they are written in source, for use in diagnostics.
+impl fmt::Display for ResolvedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolvedType::I32 => write!(f, "i32"),
+            ResolvedType::I64 => write!(f, "i64"),
+            ResolvedType::F64 => write!(f, "f64"),
+            ResolvedType::String => write!(f, "string"),
+            ResolvedType::Bool => write!(f, "bool"),
+            ResolvedType::Binary(left, op, right) => write!(f, "{} {} {}", left, op, right),
+            ResolvedType::List(inner) => write!(f, "List<{}>", inner),
+            ResolvedType::Map(key, value) => write!(f, "Map<{}, {}>", key, value),
+            ResolvedType::Struct(name) | ResolvedType::Enum(name) => write!(f, "{}", name),
+            ResolvedType::Optional(inner) => write!(f, "{}?", inner),
+            ResolvedType::Function(args, ret) | ResolvedType::CallStmt(args, ret) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "fn({}) -> {}", args.join(", "), ret)
+            }
+            ResolvedType::Void => write!(f, "void"),
+            ResolvedType::Error => write!(f, "{{error}}"),
+        }
+    }
 }
@@ -16,7 +16,8 @@ use libc::c_char;
 extern crate llvm_sys;
 use crate::builder::LLVMCodegenBuilder;
 use crate::{
-    int1_ptr_type, int1_type, int32_ptr_type, int32_type, int64_ptr_type, int64_type, int8_ptr_type,
+    double_ptr_type, double_type, int1_ptr_type, int1_type, int32_ptr_type, int32_type,
+    int64_ptr_type, int64_type, int8_ptr_type,
 };
 use anyhow::anyhow;
 use anyhow::Result;
@@ -28,6 +29,7 @@ pub enum BaseTypes {
     String,
     Number,
     Number64,
+    Float64,
     Bool,
     List(Box<BaseTypes>),
     Func,
@@ -87,6 +89,7 @@ pub trait TypeBase: DynClone {
             BaseTypes::Bool => int1_type(),
             BaseTypes::Number => int32_type(),
             BaseTypes::Number64 => int64_type(),
+            BaseTypes::Float64 => double_type(),
             _ => {
                 unreachable!("LLVMType for Type {:?} not found", self.get_type())
             }
@@ -98,6 +101,7 @@ pub trait TypeBase: DynClone {
             BaseTypes::Bool => int1_ptr_type(),
             BaseTypes::Number => int32_ptr_type(),
             BaseTypes::Number64 => int64_ptr_type(),
+            BaseTypes::Float64 => double_ptr_type(),
             _ => {
                 unreachable!("LLVMType for Type {:?} not found", self.get_type())
             }
@@ -22,3 +22,41 @@ impl TypeBase for NumberType {
         BaseTypes::Number
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct Number64Type {
+    pub llvm_value: LLVMValueRef,
+    pub llvm_value_pointer: Option<LLVMValueRef>,
+    pub name: String,
+}
+
+impl TypeBase for Number64Type {
+    fn get_value(&self) -> LLVMValueRef {
+        self.llvm_value
+    }
+    fn get_ptr(&self) -> Option<LLVMValueRef> {
+        self.llvm_value_pointer
+    }
+    fn get_type(&self) -> BaseTypes {
+        BaseTypes::Number64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FloatType {
+    pub llvm_value: LLVMValueRef,
+    pub llvm_value_pointer: Option<LLVMValueRef>,
+    pub name: String,
+}
+
+impl TypeBase for FloatType {
+    fn get_value(&self) -> LLVMValueRef {
+        self.llvm_value
+    }
+    fn get_ptr(&self) -> Option<LLVMValueRef> {
+        self.llvm_value_pointer
+    }
+    fn get_type(&self) -> BaseTypes {
+        BaseTypes::Float64
+    }
+}
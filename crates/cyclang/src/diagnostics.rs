@@ -0,0 +1,141 @@
+use backend::compiler::{Diagnostic, Diagnostics};
+
+/// Renders an error from any compiler stage. Errors carrying a `Diagnostic`
+/// get the source line and caret underline, anything else is printed as is.
+pub fn render_error(error: &anyhow::Error, source: &str, file_name: &str) -> String {
+    if let Some(diagnostics) = error.chain().find_map(|e| e.downcast_ref::<Diagnostics>()) {
+        return render_all(diagnostics, source, file_name);
+    }
+    match error.chain().find_map(|e| e.downcast_ref::<Diagnostic>()) {
+        Some(diagnostic) => render(diagnostic, source, file_name),
+        None => format!("error: {error:#}\n"),
+    }
+}
+
+/// Renders each diagnostic separated by a blank line, followed by a summary
+/// of how many errors stopped the compile. Diagnostics from an imported file
+/// are rendered against that file instead of `source`.
+pub fn render_all(diagnostics: &Diagnostics, source: &str, file_name: &str) -> String {
+    let rendered: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| match diagnostics.source(diagnostic) {
+            Some(imported) => render(diagnostic, imported.contents(), &imported.name),
+            None => render(diagnostic, source, file_name),
+        })
+        .collect();
+    let summary = match diagnostics.len() {
+        1 => "error: aborting due to 1 previous error\n".to_string(),
+        n => format!("error: aborting due to {n} previous errors\n"),
+    };
+    format!("{}\n{summary}", rendered.join("\n"))
+}
+
+/// Renders a diagnostic in the style of rustc:
+///
+/// ```text
+/// error: Undefined variable: x
+///  --> main.cyc:2:11
+///   |
+/// 2 |     print(x);
+///   |           ^ not found in this scope
+/// ```
+pub fn render(diagnostic: &Diagnostic, source: &str, file_name: &str) -> String {
+    let mut out = format!("error: {}\n", diagnostic.message);
+    let Some(span) = diagnostic.span else {
+        out.push_str(&format!(" --> {file_name}\n"));
+        if let Some(label) = &diagnostic.label {
+            out.push_str(&format!("  = note: {label}\n"));
+        }
+        return out;
+    };
+
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let line = source[line_start..line_end].trim_end_matches('\r');
+    let line_number = source[..line_start].matches('\n').count() + 1;
+    let column = source[line_start..start].chars().count() + 1;
+
+    // Spans covering several lines are underlined up to the end of the first one
+    let end = span.end.clamp(start, line_end);
+    let width = source[start..end].chars().count().max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+    let indent: String = line
+        .chars()
+        .take(column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    out.push_str(&format!("{gutter}--> {file_name}:{line_number}:{column}\n"));
+    out.push_str(&format!("{gutter} |\n"));
+    out.push_str(&format!("{line_number} | {line}\n"));
+    out.push_str(&format!("{gutter} | {indent}{}", "^".repeat(width)));
+    if let Some(label) = &diagnostic.label {
+        out.push_str(&format!(" {label}"));
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parser::Span;
+
+    #[test]
+    fn test_render_points_at_span() {
+        let source = "fn main() {\n    print(x);\n}";
+        let diagnostic = Diagnostic::new("Undefined variable: x", Span::new(22, 23))
+            .with_label("not found in this scope");
+        assert_eq!(
+            render(&diagnostic, source, "main.cyc"),
+            "error: Undefined variable: x\n \
+             --> main.cyc:2:11\n  \
+             |\n\
+             2 |     print(x);\n  \
+             |           ^ not found in this scope\n"
+        );
+    }
+
+    #[test]
+    fn test_render_underlines_whole_span() {
+        let source = "let a: i32 = \"hello\";";
+        let diagnostic = Diagnostic::new("Type mismatch", Span::new(13, 20));
+        let rendered = render(&diagnostic, source, "main.cyc");
+        assert!(rendered.ends_with("1 | let a: i32 = \"hello\";\n  |              ^^^^^^^\n"));
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let diagnostic = Diagnostic::without_span("Program must contain a 'main' function");
+        assert_eq!(
+            render(&diagnostic, "", "main.cyc"),
+            "error: Program must contain a 'main' function\n --> main.cyc\n"
+        );
+    }
+
+    #[test]
+    fn test_render_all_reports_each_error() {
+        let source = "let a = x;\nlet b = y;";
+        let diagnostics = Diagnostics::from(vec![
+            Diagnostic::new("Undefined variable: x", Span::new(8, 9)),
+            Diagnostic::new("Undefined variable: y", Span::new(19, 20)),
+        ]);
+        let rendered = render_all(&diagnostics, source, "main.cyc");
+        assert!(rendered.contains(" --> main.cyc:1:9\n"));
+        assert!(rendered.contains(" --> main.cyc:2:9\n"));
+        assert!(rendered.ends_with("\nerror: aborting due to 2 previous errors\n"));
+    }
+
+    #[test]
+    fn test_render_error_without_diagnostic() {
+        let error = anyhow::anyhow!("codegen failed");
+        assert_eq!(
+            render_error(&error, "", "main.cyc"),
+            "error: codegen failed\n"
+        );
+    }
+}
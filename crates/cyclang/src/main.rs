@@ -1,12 +1,15 @@
 use backend::compiler;
-use backend::compiler::CompileOptions;
-use clap::Parser;
+use backend::compiler::{CompileOptions, Diagnostic};
+use clap::{Parser, Subcommand};
+use codegen::emit::{EmitKind, OptLevel};
 use codegen::target::Target;
-use parser::parse_cyclo_program;
+use parser::{parse_cyclo_program, SourceFile, Span};
 use std::fs;
+use std::path::Path;
 use std::process::exit;
 use text_colorizer::Colorize;
 
+mod diagnostics;
 mod repl;
 
 #[derive(Parser, Debug)]
@@ -16,39 +19,146 @@ struct Args {
     #[arg(short, long)]
     repl: bool,
     file: Option<String>,
-    #[arg(short, long)]
-    target: Option<String>,
+    /// wasm, arm32, arm64, x86_32, x86_64 or a target triple
+    #[arg(short, long, value_parser = parse_target)]
+    target: Option<Target>,
     #[arg(short, long)]
     emit_llvm_ir: bool,
+    /// Panic on integer overflow, the default at -O0 and -O1
+    #[arg(long, conflicts_with = "unchecked_arithmetic")]
+    checked_arithmetic: bool,
+    /// Let integer overflow wrap, the default at -O2 and -O3
+    #[arg(long)]
+    unchecked_arithmetic: bool,
+    /// Optimization level, 0 to 3
+    #[arg(short = 'O', default_value = "0", value_parser = parse_opt_level)]
+    opt_level: OptLevel,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn get_target(target: Option<String>) -> Option<Target> {
-    if let Some(target) = target {
-        return Target::from_target_name(&target);
-    }
-    None
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile a file to a native executable without running it
+    Build {
+        file: String,
+        /// Path of the output, defaults to the file name with the extension of what is emitted
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Emit obj, asm, bc or llvm-ir instead of an executable
+        #[arg(long, value_parser = parse_emit_kind)]
+        emit: Option<EmitKind>,
+        /// Cross compile for wasm, arm32, arm64, x86_32, x86_64 or a target triple
+        #[arg(short, long, value_parser = parse_target)]
+        target: Option<Target>,
+        /// Panic on integer overflow, the default at -O0 and -O1
+        #[arg(long, conflicts_with = "unchecked_arithmetic")]
+        checked_arithmetic: bool,
+        /// Let integer overflow wrap, the default at -O2 and -O3
+        #[arg(long)]
+        unchecked_arithmetic: bool,
+        /// Optimization level, 0 to 3
+        #[arg(short = 'O', default_value = "0", value_parser = parse_opt_level)]
+        opt_level: OptLevel,
+    },
 }
 
 fn compile_output_from_string(
     contents: String,
+    file_name: &str,
     is_execution_engine: bool,
     emit_llvm_ir: bool,
-    target: Option<String>,
+    target: Option<Target>,
+    checked_arithmetic: bool,
+    opt_level: OptLevel,
 ) -> String {
-    let compile_options = Some(CompileOptions {
+    let compile_options = CompileOptions {
         is_execution_engine,
         emit_llvm_ir,
         emit_llvm_ir_main_only: true,
         emit_llvm_ir_with_called: false,
-        target: get_target(target),
+        target,
+        checked_arithmetic,
+        source: Some(SourceFile::new(file_name, contents.as_str())),
+        output: None,
+        emit: None,
+        opt_level,
+    };
+    compile_with_options(&contents, file_name, compile_options)
+}
+
+fn parse_emit_kind(name: &str) -> Result<EmitKind, String> {
+    EmitKind::from_name(name).ok_or_else(|| format!("expected obj, asm, bc or llvm-ir, got {name}"))
+}
+
+fn parse_target(name: &str) -> Result<Target, String> {
+    Target::from_target_name(name).ok_or_else(|| {
+        format!("expected wasm, arm32, arm64, x86_32, x86_64 or a target triple, got {name}")
+    })
+}
+
+fn parse_opt_level(name: &str) -> Result<OptLevel, String> {
+    OptLevel::from_name(name).ok_or_else(|| format!("expected 0, 1, 2 or 3, got {name}"))
+}
+
+// Overflow checks follow the optimization level unless a flag turns them on or off
+fn checked_arithmetic(checked: bool, unchecked: bool, opt_level: OptLevel) -> bool {
+    checked || (!unchecked && opt_level.checks_arithmetic())
+}
+
+// Running a program JITs it on this machine, so a target other than the host
+// can only be given when the IR is emitted instead
+fn check_run_target(target: Option<&Target>, emit_llvm_ir: bool) -> Result<(), String> {
+    match target {
+        Some(target) if !emit_llvm_ir && !target.is_host() => Err(format!(
+            "cannot run a program built for {}, use `cyclang build --target` to cross compile",
+            target.get_llvm_target_name()
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn build(
+    file: &str,
+    output: Option<String>,
+    emit: Option<EmitKind>,
+    target: Option<Target>,
+    checked_arithmetic: bool,
+    opt_level: OptLevel,
+) {
+    let contents = fs::read_to_string(file).expect("Failed to read file");
+    let output = output.unwrap_or_else(|| {
+        let stem = Path::new(file).file_stem().unwrap_or_default();
+        match emit {
+            Some(kind) => format!("{}.{}", stem.to_string_lossy(), kind.extension()),
+            None => stem.to_string_lossy().to_string(),
+        }
     });
-    match parse_cyclo_program(&contents) {
-        Ok(exprs) => compiler::compile(exprs, compile_options).unwrap_or_else(|e| {
-            eprintln!("unable to compile contents due to error: {e}");
+    let compile_options = CompileOptions {
+        is_execution_engine: false,
+        emit_llvm_ir: false,
+        emit_llvm_ir_main_only: true,
+        emit_llvm_ir_with_called: false,
+        target,
+        checked_arithmetic,
+        source: Some(SourceFile::new(file, contents.as_str())),
+        output: Some(output),
+        emit,
+        opt_level,
+    };
+    compile_with_options(&contents, file, compile_options);
+}
+
+// Parses and compiles `contents`, printing any errors against the source and exiting
+fn compile_with_options(contents: &str, file_name: &str, options: CompileOptions) -> String {
+    match parse_cyclo_program(contents) {
+        Ok(exprs) => compiler::compile(exprs, Some(options)).unwrap_or_else(|e| {
+            eprint!("{}", diagnostics::render_error(&e, contents, file_name));
             exit(1)
         }),
         Err(e) => {
-            eprintln!("unable to parse contents due to error: {e}");
+            let diagnostic = Diagnostic::new(e.variant.message(), Span::from(e.as_ref()));
+            eprint!("{}", diagnostics::render(&diagnostic, contents, file_name));
             exit(1)
         }
     }
@@ -65,6 +175,26 @@ fn main() {
         repl::run();
         return;
     }
+    if let Some(Command::Build {
+        file,
+        output,
+        emit,
+        target,
+        checked_arithmetic: checked,
+        unchecked_arithmetic: unchecked,
+        opt_level,
+    }) = args.command
+    {
+        build(
+            &file,
+            output,
+            emit,
+            target,
+            checked_arithmetic(checked, unchecked, opt_level),
+            opt_level,
+        );
+        return;
+    }
     let file = match args.file {
         Some(file) => file,
         None => {
@@ -72,12 +202,23 @@ fn main() {
             exit(2);
         }
     };
-    let contents = fs::read_to_string(file).expect("Failed to read file");
+    if let Err(message) = check_run_target(args.target.as_ref(), args.emit_llvm_ir) {
+        eprintln!("{}", message);
+        exit(2);
+    }
+    let contents = fs::read_to_string(&file).expect("Failed to read file");
     let output = compile_output_from_string(
         contents,
+        &file,
         !args.emit_llvm_ir,
         args.emit_llvm_ir,
         args.target,
+        checked_arithmetic(
+            args.checked_arithmetic,
+            args.unchecked_arithmetic,
+            args.opt_level,
+        ),
+        args.opt_level,
     );
 
     // If we're emitting LLVM IR, print it; otherwise it's execution output
@@ -93,7 +234,7 @@ mod test {
     use super::*;
     //Note: Integration tests for parsing and compiling output
     fn compile_output_from_string_test(contents: String) -> String {
-        compile_output_from_string(contents, false, false, None)
+        compile_output_from_string(contents, "test.cyc", false, false, None, true, OptLevel::O0)
     }
 
     fn add_into_main_func(input: &str) -> String {
@@ -114,6 +255,20 @@ mod test {
         assert_eq!(output, "\"hello world\"\n");
     }
 
+    #[test]
+    fn test_compile_add_string_leaves_operands_unchanged() {
+        let input = r#"
+        let a = "foo";
+        let b: string = a + "bar";
+        let c = b + a;
+        print(a);
+        print(b);
+        print(c);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "\"foo\"\n\"foobar\"\n\"foobarfoo\"\n");
+    }
+
     #[test]
     fn test_compile_print_bool_expression() {
         let input = add_into_main_func(r#"print(true);"#);
@@ -122,6 +277,91 @@ mod test {
         assert_eq!(output, "true\n");
     }
 
+    #[test]
+    fn test_compile_print_f64_expression() {
+        let input = add_into_main_func("let a: f64 = 1.5; print((a * 2) + 0.25);");
+        let output = compile_output_from_string_test(input);
+        assert_eq!(output, "3.250000\n");
+    }
+
+    #[test]
+    fn test_compile_f64_comparison() {
+        let input = add_into_main_func("let a = -0.5; print(a < 0); print(2.5 == 2.5);");
+        let output = compile_output_from_string_test(input);
+        assert_eq!(output, "true\ntrue\n");
+    }
+
+    #[test]
+    fn test_compile_f64_function() {
+        let input = r#"
+        fn half(f64 x) -> f64 {
+            return x / 2.0;
+        }
+        fn main() {
+            print(half(5.0));
+        }
+        "#;
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(output, "2.500000\n");
+    }
+
+    #[test]
+    fn test_compile_numeric_conversions() {
+        let input = add_into_main_func(
+            "let a: f64 = 7.9; print(i32(a)); print(0.5 * f64(7)); print(4 + i64(3));",
+        );
+        let output = compile_output_from_string_test(input);
+        assert_eq!(output, "7\n3.500000\n7\n");
+    }
+
+    #[test]
+    fn test_compile_logical_operators_short_circuit() {
+        let input = r#"
+        fn noisy(bool v) -> bool {
+            print(v);
+            return v;
+        }
+        fn main() {
+            if (false && noisy(true)) {
+                print(1);
+            }
+            if (true || noisy(false)) {
+                print(2);
+            }
+            if (true && noisy(true)) {
+                print(3);
+            }
+        }
+        "#;
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(output, "2\ntrue\n3\n");
+    }
+
+    #[test]
+    fn test_compile_logical_operators_in_while() {
+        let input = add_into_main_func(
+            "let i = 0; let done = false; while ((i < 10) && !done) { i = i + 1; if (i == 3) { done = true; } } print(i);",
+        );
+        let output = compile_output_from_string_test(input);
+        assert_eq!(output, "3\n");
+    }
+
+    #[test]
+    fn test_compile_unary_operators() {
+        let input = add_into_main_func("let a = 3; print(-a); print(-(1.5)); print(!(a > 5));");
+        let output = compile_output_from_string_test(input);
+        assert_eq!(output, "-3\n-1.500000\ntrue\n");
+    }
+
+    #[test]
+    fn test_compile_operator_precedence() {
+        let input = add_into_main_func(
+            "print(1 + 2 * 3); print(10 - 4 - 3); print(2 * 3 + 4 * 5); print(1 + 2 < 2 * 2 && true);",
+        );
+        let output = compile_output_from_string_test(input);
+        assert_eq!(output, "7\n3\n26\ntrue\n");
+    }
+
     #[test]
     fn test_compile_extern_module_sleep() {
         let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -242,6 +482,159 @@ fn main() {{
         assert_eq!(output, "5\n");
     }
 
+    #[test]
+    fn test_compile_modulo() {
+        let input = r#"
+        print(17 % 5);
+        print(-7 % 3);
+        print(100 % i64(7));
+        print(2 + 7 % 4 * 2);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "2\n-1\n2\n8\n");
+    }
+
+    #[test]
+    fn test_compile_power() {
+        let input = r#"
+        print(2 ^ 10);
+        print(2 ^ 3 ^ 2);
+        print(-3 ^ 3);
+        print(5 ^ 0);
+        print(2 ^ i64(40));
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "1024\n512\n-27\n1\n1099511627776\n");
+    }
+
+    #[test]
+    fn test_compile_power_negative_exponent_panics() {
+        let input = r#"
+        let exponent = 0 - 2;
+        print(1);
+        print(2 ^ exponent);
+        print(2);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "1\n");
+    }
+
+    #[test]
+    fn test_compile_division_by_zero_panics() {
+        let input = r#"
+        let zero = 0;
+        print(1);
+        print(10 / zero);
+        print(2);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "1\n");
+    }
+
+    #[test]
+    fn test_compile_modulo_by_zero_panics() {
+        let input = r#"
+        let zero = 0;
+        print(1);
+        print(10 % zero);
+        print(2);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "1\n");
+    }
+
+    // Compiles to bin/main, then runs it again to check how it exited
+    fn run_compiled_program(contents: &str) -> std::process::Output {
+        compile_output_from_string_test(contents.to_string());
+        std::process::Command::new("bin/main").output().unwrap()
+    }
+
+    #[test]
+    fn test_compile_overflow_panics_with_location() {
+        let input = "fn main() {\n    let max = 2147483647;\n    print(1);\n    print(max + 1);\n}";
+        let output = run_compiled_program(input);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "panic at test.cyc:4:11: attempt to add with overflow\n"
+        );
+        assert_eq!(output.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_compile_checked_arithmetic_traps() {
+        let cases = [
+            ("let a = 65536; print(a * a);", "attempt to multiply with overflow"),
+            ("let a = 0 - 2147483647; print(a - 2);", "attempt to subtract with overflow"),
+            ("let a = 0 - 2147483647 - 1; print(-a);", "attempt to negate with overflow"),
+            ("let a = 0 - 2147483647 - 1; print(a / (0 - 1));", "attempt to divide with overflow"),
+            ("let a = 0; print(10 / a);", "attempt to divide by zero"),
+            ("print(2 ^ 31);", "attempt to raise to a power with overflow"),
+            ("print(9223372036854775807 + i64(1));", "attempt to add with overflow"),
+        ];
+        for (body, message) in cases {
+            let output = run_compiled_program(&add_into_main_func(body));
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.ends_with(&format!(": {}\n", message)), "{body}: {stderr}");
+            assert!(!output.status.success());
+        }
+    }
+
+    #[test]
+    fn test_compile_unchecked_arithmetic_wraps() {
+        let input = add_into_main_func(
+            "let max = 2147483647; print(max + 1); print(65536 * 65536); print(2 ^ 31);",
+        );
+        let output =
+            compile_output_from_string(input, "test.cyc", false, false, None, false, OptLevel::O0);
+        assert_eq!(output, "-2147483648\n0\n-2147483648\n");
+    }
+
+    #[test]
+    fn test_compile_unchecked_division_overflow_panics() {
+        let cases = [
+            ("print(a / (0 - 1));", "attempt to divide with overflow"),
+            (
+                "print(a % (0 - 1));",
+                "attempt to calculate the remainder with overflow",
+            ),
+        ];
+        for (body, message) in cases {
+            let input = add_into_main_func(&format!("let a = 0 - 2147483647 - 1; {}", body));
+            compile_output_from_string(input, "test.cyc", false, false, None, false, OptLevel::O2);
+            let output = std::process::Command::new("bin/main").output().unwrap();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(
+                stderr.ends_with(&format!(": {}\n", message)),
+                "{body}: {stderr}"
+            );
+            assert!(!output.status.success());
+        }
+    }
+
+    #[test]
+    fn test_checked_arithmetic_follows_opt_level() {
+        assert!(checked_arithmetic(false, false, OptLevel::O0));
+        assert!(checked_arithmetic(false, false, OptLevel::O1));
+        assert!(!checked_arithmetic(false, false, OptLevel::O2));
+        assert!(!checked_arithmetic(false, false, OptLevel::O3));
+        assert!(checked_arithmetic(true, false, OptLevel::O3));
+        assert!(!checked_arithmetic(false, true, OptLevel::O0));
+
+        let input = add_into_main_func("let max = 2147483647; print(max + 1);");
+        let checked = checked_arithmetic(false, false, OptLevel::O2);
+        let output = compile_output_from_string(
+            input,
+            "test.cyc",
+            false,
+            false,
+            None,
+            checked,
+            OptLevel::O2,
+        );
+        assert_eq!(output, "-2147483648\n");
+    }
+
     #[test]
     fn test_compile_eqeq_true_number() {
         let input = r#"
@@ -278,6 +671,43 @@ fn main() {{
         assert_eq!(output, "false\n");
     }
 
+    #[test]
+    fn test_compile_not_equal_string() {
+        let input = r#"
+        print("4" != "5");
+        print("4" != "4");
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "true\nfalse\n");
+    }
+
+    #[test]
+    fn test_compile_compare_i64() {
+        let input = r#"
+        let a = i64(5);
+        let b = 9223372036854775807;
+        print(a < b);
+        print(a == b);
+        print(b >= a);
+        print(a != i64(5));
+        print(a > 3);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "true\nfalse\ntrue\nfalse\ntrue\n");
+    }
+
+    #[test]
+    fn test_compile_compare_i64_and_f64() {
+        let input = r#"
+        let a = i64(5);
+        print(a < 5.5);
+        print(a == 5.0);
+        print(4.5 >= a);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "true\ntrue\nfalse\n");
+    }
+
     #[test]
     fn test_compile_eqeq_bool_false() {
         let input = r#"
@@ -339,148 +769,421 @@ fn main() {{
     }
 
     #[test]
-    fn test_if_stmt_with_let_stmt() {
+    fn test_compile_list_len_with_negative_elements() {
         let input = r#"
-        let is_value = true;
-        if (is_value)
-        {
-            print("hello");
-        }
+        let xs = [1, -1, 3];
+        print(len(xs));
+        print(xs);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "\"hello\"\n");
+        assert_eq!(output, "3\n[1,-1,3]");
     }
 
     #[test]
-    fn test_if_stmt_with_eqeq_stmt_number() {
+    fn test_compile_list_element_types() {
         let input = r#"
-        if (1 == 1)
-        {
-            print("hello");
-        }
+        let floats = [1.5, 2.25];
+        print(floats);
+        let flags = [true, false];
+        print(flags);
+        print(flags[1]);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "\"hello\"\n");
+        assert_eq!(output, "[1.500000,2.250000][true,false]false\n");
     }
 
     #[test]
-    fn test_if_stmt_with_ne_stmt_bool() {
+    fn test_compile_list_i64() {
         let input = r#"
-        if (1 != 1)
-        {
-            print("not hello");
-        } else {
-            print("hello");
-        }
+        let xs = [4294967296, 8589934592];
+        print(len(xs + xs));
+        print(xs + xs);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "\"hello\"\n");
+        assert_eq!(output, "4\n[4294967296,8589934592,4294967296,8589934592]");
     }
 
     #[test]
-    fn test_if_else_stmt() {
+    fn test_compile_list_index_and_assign() {
         let input = r#"
-        let value = false;
-        if (value)
-        {
-            print("not hello");
-        } else {
-            print("hello");
-        }
+        let xs = [10, 20, 30];
+        xs[1] = 25;
+        print(xs[1]);
+        let words = ["a", "b"];
+        print(words[1]);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "\"hello\"\n");
+        assert_eq!(output, "25\n\"b\"\n");
     }
 
     #[test]
-    fn test_nested_if_stmts() {
-        let input = r#"
-        if (true) {
-            if (true) {
-                print("yep");
-            } else {
-                print("nope");
-            }
-        } else {
-            print("don't print this");
-        }
-        "#;
-        let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "\"yep\"\n");
+    fn test_compile_list_index_out_of_bounds_panics() {
+        let input = "fn main() {\n    let xs = [1, 2, 3, 4, 5];\n    print(xs[100]);\n}";
+        let output = run_compiled_program(input);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "panic at test.cyc:3:11: index 100 out of bounds for list of length 5\n"
+        );
+        assert_eq!(output.status.code(), Some(1));
     }
 
     #[test]
-    fn test_nested_if_stmts_with_print_after() {
+    fn test_compile_list_assign_out_of_bounds_panics() {
+        let input = add_into_main_func(
+            r#"let words = ["a", "b"]; print(1); let i = 0 - 1; words[i] = "c"; print(2);"#,
+        );
+        let output = run_compiled_program(&input);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .ends_with(": index -1 out of bounds for list of length 2\n"));
+    }
+
+    #[test]
+    fn test_compile_list_push_pop_insert_remove() {
         let input = r#"
-        if (true) {
-            if (true) {
-                print("yep");
-            } else {
-                print("nope");
-            }
-            print("yep");
-        } else {
-            print("don't print this");
+        let xs = [1, 2];
+        let i = 0;
+        while (i < 20) {
+            push(xs, i);
+            i = i + 1;
         }
+        print(len(xs));
+        let last = pop(xs);
+        print(last);
+        insert(xs, 0, 100);
+        let removed = remove(xs, 1);
+        print(removed);
+        print(xs[0]);
+        print(len(xs));
+        clear(xs);
+        push(xs, 7);
+        print(xs);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "\"yep\"\n\"yep\"\n");
+        assert_eq!(output, "22\n19\n1\n100\n21\n[7]");
     }
 
     #[test]
-    fn test_nested_if_stmts_deeper() {
+    fn test_compile_list_push_strings_and_bools() {
         let input = r#"
-        if (true) {
-            if (true) {
-                print(1);
-                if (false) {
-                    print("error");
-                } else {
-                    print(2);
-                    if (true) {
-                        print(3);
-                    } else {
-                        print("nothing");
-                    }
-                }
-            }
-            print(4);
-        } else {
-            print("don't print this");
-        }
+        let words = ["a"];
+        push(words, "b");
+        insert(words, 0, "z");
+        print(words);
+        let flags = [true];
+        push(flags, false);
+        print(flags);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "1\n2\n3\n4\n");
+        assert_eq!(output, "[\"z\",\"a\",\"b\"][true,false]");
     }
 
     #[test]
-    fn test_nested_if_stmts_with_top_level_var() {
+    fn test_compile_list_slice() {
         let input = r#"
-        let var = 3;
-        if (true) {
-            if (true) {
-                print(1);
-                if (false) {
-                    print("error");
-                } else {
-                    print(2);
-                    if (true) {
-                        print(var);
-                        var = var + 1;
-                        print(var);
-                        var = var + 1;
-                    } else {
-                        print("nope");
-                    }
-                }
-            }
-        } else {
-            print("don't print this");
-        }
-        print(var);
+        let xs = [0, 1, 2, 3, 4];
+        print(xs[1:3]);
+        print(xs[:2]);
+        print(xs[3:]);
+        let n = len(xs[2:2]);
+        print(n);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "1\n2\n3\n4\n5\n");
+        assert_eq!(output, "[1,2][0,1][3,4]0\n");
+    }
+
+    #[test]
+    fn test_compile_list_slice_out_of_range_panics() {
+        let input = "fn main() {\n    let xs = [1, 2, 3];\n    let ys = xs[2:5];\n}";
+        let output = run_compiled_program(input);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "panic at test.cyc:3:14: slice 2..5 out of range for list of length 3\n"
+        );
+        assert_eq!(output.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_compile_list_pop_empty_panics() {
+        let input = add_into_main_func("let xs = [1]; let a = pop(xs); let b = pop(xs);");
+        let output = run_compiled_program(&input);
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .ends_with(": attempt to pop from an empty list\n"));
+        assert_eq!(output.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_compile_list_push_wrong_type() {
+        let input = r#"
+        let xs = [1, 2];
+        push(xs, "three");
+        "#;
+        let messages = compile_error_messages(&add_into_main_func(input));
+        assert_eq!(
+            messages,
+            vec!["Cannot push a value of type string into a list of i32"]
+        );
+    }
+
+    #[test]
+    fn test_compile_empty_list_from_let_annotation() {
+        let input = r#"
+        let xs: List<i32> = [];
+        print(len(xs));
+        push(xs, 3);
+        print(xs);
+        xs = [];
+        print(len(xs));
+        let words: List<string> = [];
+        push(words, "a");
+        print(words);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "0\n[3]0\n[\"a\"]");
+    }
+
+    #[test]
+    fn test_compile_empty_list_from_function_types() {
+        let input = r#"
+        fn empty() -> List<f64> {
+            return [];
+        }
+        fn count(List<bool> flags) -> i32 {
+            let n = len(flags);
+            return n;
+        }
+        fn main() {
+            print(len(empty()));
+            print(count([]));
+        }
+        "#;
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(output, "0\n0\n");
+    }
+
+    #[test]
+    fn test_compile_empty_list_without_annotation() {
+        let input = add_into_main_func("let xs = [];");
+        let messages = compile_error_messages(&input);
+        assert_eq!(messages, vec!["Cannot infer the element type of an empty list"]);
+    }
+
+    #[test]
+    fn test_compile_map_literal_index_and_assign() {
+        let input = r#"
+        let ages = {"alice": 31, "bob": 27};
+        print(ages["bob"]);
+        ages["bob"] = 28;
+        ages["carol"] = 40;
+        print(ages["bob"]);
+        print(len(ages));
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "27\n28\n3\n");
+    }
+
+    #[test]
+    fn test_compile_map_contains_remove_and_keys() {
+        let input = r#"
+        let ages = {"alice": 31, "bob": 27, "carol": 40};
+        print(contains(ages, "bob"));
+        let removed = remove(ages, "bob");
+        print(removed);
+        print(contains(ages, "bob"));
+        ages["bob"] = 1;
+        print(keys(ages));
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "true\n27\nfalse\n[\"alice\",\"carol\",\"bob\"]");
+    }
+
+    #[test]
+    fn test_compile_map_int_keys() {
+        let input = r#"
+        let squares: Map<i32, i64> = {};
+        let i = 0;
+        while (i < 20) {
+            squares[i] = i64(i * i);
+            i = i + 1;
+        }
+        print(squares[19]);
+        print(len(squares));
+        let big = {4294967296: true};
+        print(big[4294967296]);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "361\n20\ntrue\n");
+    }
+
+    #[test]
+    fn test_compile_map_missing_key_panics() {
+        let input = add_into_main_func(r#"let ages = {"alice": 31}; let x = ages["bob"];"#);
+        let output = run_compiled_program(&input);
+        assert!(String::from_utf8_lossy(&output.stderr).ends_with(": key not found in map\n"));
+        assert_eq!(output.status.code(), Some(1));
+    }
+
+    #[test]
+    fn test_compile_map_wrong_key_type() {
+        let input = r#"
+        let ages = {"alice": 31};
+        let x = ages[1];
+        let empty = {};
+        "#;
+        let messages = compile_error_messages(&add_into_main_func(input));
+        assert_eq!(
+            messages,
+            vec![
+                "Map key must be string, got i32",
+                "Cannot infer the key and value types of an empty map"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_if_stmt_with_let_stmt() {
+        let input = r#"
+        let is_value = true;
+        if (is_value)
+        {
+            print("hello");
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "\"hello\"\n");
+    }
+
+    #[test]
+    fn test_if_stmt_with_eqeq_stmt_number() {
+        let input = r#"
+        if (1 == 1)
+        {
+            print("hello");
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "\"hello\"\n");
+    }
+
+    #[test]
+    fn test_if_stmt_with_ne_stmt_bool() {
+        let input = r#"
+        if (1 != 1)
+        {
+            print("not hello");
+        } else {
+            print("hello");
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "\"hello\"\n");
+    }
+
+    #[test]
+    fn test_if_else_stmt() {
+        let input = r#"
+        let value = false;
+        if (value)
+        {
+            print("not hello");
+        } else {
+            print("hello");
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "\"hello\"\n");
+    }
+
+    #[test]
+    fn test_nested_if_stmts() {
+        let input = r#"
+        if (true) {
+            if (true) {
+                print("yep");
+            } else {
+                print("nope");
+            }
+        } else {
+            print("don't print this");
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "\"yep\"\n");
+    }
+
+    #[test]
+    fn test_nested_if_stmts_with_print_after() {
+        let input = r#"
+        if (true) {
+            if (true) {
+                print("yep");
+            } else {
+                print("nope");
+            }
+            print("yep");
+        } else {
+            print("don't print this");
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "\"yep\"\n\"yep\"\n");
+    }
+
+    #[test]
+    fn test_nested_if_stmts_deeper() {
+        let input = r#"
+        if (true) {
+            if (true) {
+                print(1);
+                if (false) {
+                    print("error");
+                } else {
+                    print(2);
+                    if (true) {
+                        print(3);
+                    } else {
+                        print("nothing");
+                    }
+                }
+            }
+            print(4);
+        } else {
+            print("don't print this");
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "1\n2\n3\n4\n");
+    }
+
+    #[test]
+    fn test_nested_if_stmts_with_top_level_var() {
+        let input = r#"
+        let var = 3;
+        if (true) {
+            if (true) {
+                print(1);
+                if (false) {
+                    print("error");
+                } else {
+                    print(2);
+                    if (true) {
+                        print(var);
+                        var = var + 1;
+                        print(var);
+                        var = var + 1;
+                    } else {
+                        print("nope");
+                    }
+                }
+            }
+        } else {
+            print("don't print this");
+        }
+        print(var);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "1\n2\n3\n4\n5\n");
     }
 
     #[test]
@@ -492,134 +1195,1123 @@ fn main() {{
             print(value);
         }
         "#;
-        let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "false\n");
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "false\n");
+    }
+
+    #[test]
+    fn test_compile_while_stmt_increment() {
+        let input = r#"
+        let value = 0;
+        while(value < 10) {
+            value = value + 1;
+            print(value);
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n");
+    }
+
+    #[test]
+    fn test_compile_while_stmt_with_if_true() {
+        let input = r#"
+        let value = true;
+        while(value) {
+            if (value == true) {
+                print(value);
+            }
+            value = false;
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "true\n");
+    }
+
+    #[test]
+    fn test_compile_while_stmt_one_pass_grouping_string() {
+        let input = r#"
+        let value = true;
+        while(value) {
+            value = false;
+            print("here");
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "\"here\"\n");
+    }
+
+    #[test]
+    fn test_compile_while_stmt_one_pass_grouping() {
+        let input = r#"
+        let value = true;
+        while(value) {
+            print(value);
+            value = false;
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "true\n");
+    }
+
+    #[test]
+    fn test_compile_while_stmt_false() {
+        let input = r#"
+        let value = false;
+        while(value) {
+            print(value);
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn test_compile_while_stmt_with_if() {
+        let input = r#"
+            let cond = true;
+            let val = 0;
+            while (cond) {
+                val = val + 1;
+                if (val == 10) {
+                   cond = false;
+                }
+            }
+            print(val);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "10\n");
+    }
+
+    #[test]
+    fn test_compile_while_stmt_break() {
+        let input = r#"
+            let val = 0;
+            while (true) {
+                val = val + 1;
+                break;
+            }
+            print(val);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "1\n");
+    }
+
+    #[test]
+    fn test_compile_continue_in_while_and_for() {
+        let input = r#"
+            let i = 0;
+            let odd = 0;
+            while (i < 10) {
+                i = i + 1;
+                if (i % 2 == 0) {
+                    continue;
+                }
+                odd = odd + i;
+            }
+            print(odd);
+            let even = 0;
+            for (let j = 0; j < 10; j++) {
+                if (j % 2 == 1) {
+                    continue;
+                }
+                even = even + j;
+            }
+            print(even);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "25\n20\n");
+    }
+
+    #[test]
+    fn test_compile_labeled_break_and_continue() {
+        let input = r#"
+            let pairs = 0;
+            outer: for (let i = 0; i < 5; i++) {
+                for (let j = 0; j < 5; j++) {
+                    if (j > i) {
+                        continue outer;
+                    }
+                    if (i == 4) {
+                        break outer;
+                    }
+                    pairs = pairs + 1;
+                }
+            }
+            print(pairs);
+            let found = 0;
+            rows: for (row in [1, 2, 3]) {
+                for (col in [1, 2, 3]) {
+                    if (row * col == 6) {
+                        found = row * 10 + col;
+                        break rows;
+                    }
+                }
+            }
+            print(found);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "10\n23\n");
+    }
+
+    #[test]
+    fn test_compile_misused_break_and_continue() {
+        let input = r#"
+        fn main() {
+            continue;
+            outer: while (true) {
+                break inner;
+                outer: while (true) {
+                    break outer;
+                }
+            }
+        }
+        "#;
+        let messages = compile_error_messages(input);
+        assert_eq!(
+            messages,
+            vec![
+                "continue can only be used inside a loop",
+                "Undefined loop label: inner",
+                "Loop label 'outer' is already in use",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_struct_literal_and_fields() {
+        let input = r#"
+        struct Point { x: i32, y: i32 }
+        struct Line { start: Point, end: Point, name: string, visible: bool }
+        fn main() {
+            let p = Point { x: 1, y: 2 };
+            let q = p;
+            q.x = 10;
+            print(p.x);
+            print(q);
+            let line = Line { name: "diagonal", start: p, end: q, visible: true };
+            line.end.y = line.start.y + 5;
+            print(line.end.y);
+            print(line);
+        }
+        "#;
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(
+            output,
+            "1\nPoint { x: 10, y: 2 }\n7\n\
+             Line { start: Point { x: 1, y: 2 }, end: Point { x: 10, y: 7 }, \
+             name: \"diagonal\", visible: true }\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_structs_in_functions_and_lists() {
+        let input = r#"
+        struct Point { x: i32, y: i32 }
+        fn translate(Point p, i32 dx) -> Point {
+            p.x = p.x + dx;
+            return p;
+        }
+        fn main() {
+            let origin = Point { x: 0, y: 0 };
+            let moved = translate(origin, 3);
+            print(origin.x);
+            print(moved.x);
+            let points: List<Point> = [Point { x: 0, y: 0 }];
+            push(points, moved);
+            push(points, Point { x: 5, y: 6 });
+            points[1].y = 4;
+            print(points[1]);
+            print(points[2].x);
+            let total = 0;
+            for (p in points) {
+                total = total + p.x + p.y;
+            }
+            print(total);
+        }
+        "#;
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(output, "0\n3\nPoint { x: 3, y: 4 }\n5\n18\n");
+    }
+
+    #[test]
+    fn test_compile_struct_errors() {
+        let input = r#"
+        struct Point { x: i32, y: i32 }
+        fn main() {
+            let a = Point { x: 1 };
+            let b = Point { x: 1, y: 2, z: 3 };
+            let c = Point { x: 1, y: 2 };
+            print(c.z);
+            let d: Shape = c;
+        }
+        "#;
+        let messages = compile_error_messages(input);
+        assert_eq!(
+            messages,
+            vec![
+                "Missing fields in struct 'Point': y",
+                "Struct 'Point' has no field 'z'",
+                "No field 'z' on type Point",
+                "Undefined type: Shape",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_impl_blocks_and_method_calls() {
+        let input = r#"
+        struct Point { x: i32, y: i32 }
+        impl string {
+            fn shout(self) -> string {
+                return self + "!";
+            }
+        }
+        impl List<i32> {
+            fn sum(self) -> i32 {
+                let total = 0;
+                for (x in self) {
+                    total = total + x;
+                }
+                return total;
+            }
+            fn scaled(self, i32 factor) -> List<i32> {
+                let out: List<i32> = [];
+                for (x in self) {
+                    out.push(x * factor);
+                }
+                return out;
+            }
+        }
+        impl Point {
+            fn manhattan(self) -> i32 {
+                return self.x + self.y;
+            }
+        }
+        fn main() {
+            let greeting = "hello";
+            print(greeting.shout());
+            print(greeting);
+            let sentence = greeting + " world";
+            print(sentence.shout());
+            let xs = [1, 2, 3];
+            xs.push(4);
+            print(xs.len());
+            print(xs.sum());
+            print(xs.scaled(10).sum());
+            let p = Point { x: 3, y: 4 };
+            print(p.manhattan());
+            let ages = {"ada": 36};
+            print(ages.contains("ada"));
+        }
+        "#;
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(
+            output,
+            "\"hello!\"\n\"hello\"\n\"hello world!\"\n4\n10\n100\n7\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_method_errors() {
+        let input = r#"
+        impl List<i32> {
+            fn len(self) -> i32 {
+                return 0;
+            }
+            fn first(self) -> i32 {
+                return self[0];
+            }
+        }
+        fn main() {
+            let n = 5;
+            n.shout();
+            let xs = [1, 2];
+            xs.first(1);
+        }
+        "#;
+        let messages = compile_error_messages(input);
+        assert_eq!(
+            messages,
+            vec![
+                "Method 'len' is already defined for List<i32>",
+                "No method 'shout' on type i32",
+                "first() takes 0 arguments but 1 were supplied",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_enums_and_match() {
+        let input = r#"
+        enum Shape { Circle(i32), Rect(i32, i32), Empty }
+        struct Point { x: i32, y: i32 }
+        enum Message { Text(string), Move(Point), Quit }
+
+        fn area(Shape s) -> i32 {
+            match (s) {
+                Shape::Circle(r) => { return 3 * r * r; }
+                Shape::Rect(w, h) => { return w * h; }
+                Shape::Empty => { return 0; }
+            }
+            return -1;
+        }
+
+        fn square(i32 side) -> Shape {
+            return Shape::Rect(side, side);
+        }
+
+        fn main() {
+            print(area(Shape::Circle(2)));
+            let sq = square(5);
+            print(area(sq));
+            print(area(Shape::Empty));
+
+            let shapes = [Shape::Rect(2, 3), Shape::Empty];
+            push(shapes, Shape::Circle(1));
+            let total = 0;
+            for (shape in shapes) {
+                total = total + area(shape);
+            }
+            print(total);
+
+            let messages = [Message::Text("hi"), Message::Quit];
+            messages.insert(1, Message::Move(Point { x: 1, y: 2 }));
+            for (message in messages) {
+                match (message) {
+                    Message::Text(text) => { print(text); }
+                    Message::Move(p) => { print(p.x + p.y); }
+                    _ => { print("other"); }
+                }
+            }
+        }
+        "#;
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(output, "12\n25\n0\n9\n\"hi\"\n3\n\"other\"\n");
+    }
+
+    #[test]
+    fn test_compile_enum_errors() {
+        let input = r#"
+        enum Shape { Circle(i32), Rect(i32, i32), Empty }
+        enum Color { Red, Green }
+        fn main() {
+            let s = Shape::Square(1);
+            let c = Shape::Circle(1, 2);
+            let r = Shape::Rect(1, 2);
+            match (r) {
+                Shape::Circle(radius) => { print(radius); }
+                Color::Red => { print(0); }
+            }
+            match (1) {
+                _ => { print(1); }
+            }
+            match (r) {
+                _ => { print(1); }
+                Shape::Empty => { print(2); }
+            }
+            match (r) {
+                Shape::Circle(radius) => { print(radius); }
+            }
+        }
+        "#;
+        let messages = compile_error_messages(input);
+        assert_eq!(
+            messages,
+            vec![
+                "Enum 'Shape' has no variant 'Square'",
+                "Shape::Circle takes 1 value but 2 were supplied",
+                "Expected a variant of Shape, found Color::Red",
+                "Cannot match on i32",
+                "Unreachable match arm",
+                "Non-exhaustive match: Shape::Rect, Shape::Empty not covered",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_non_exhaustive_match_in_lambda() {
+        let input = r#"
+        enum Shape { Circle(i32), Empty }
+        fn main() {
+            let g = |Shape s| {
+                match (s) {
+                    Shape::Circle(r) => { print(r); }
+                }
+            };
+        }
+        "#;
+        assert_eq!(
+            compile_error_messages(input),
+            vec!["Non-exhaustive match: Shape::Empty not covered"]
+        );
+    }
+
+    #[test]
+    fn test_compile_optionals() {
+        let input = r#"
+        struct User { name: string, age: i32? }
+
+        fn find(Map<string, i32> ages, string name) -> i32? {
+            let known = contains(ages, name);
+            if (known) {
+                let age = ages[name];
+                return age;
+            }
+            return nil;
+        }
+
+        fn main() {
+            let ages = {"alice": 31, "bob": 27};
+            let a = ages.get("alice");
+            let c = ages.get("carol");
+            print(a);
+            print(c);
+            if (c == nil) {
+                print("no carol");
+            }
+            if (a != nil) {
+                let next = a.unwrap() + 1;
+                print(next);
+            }
+
+            let x: i32? = nil;
+            print(x);
+            x = 5;
+            let doubled = unwrap(x) * 2;
+            print(doubled);
+
+            let u = User { name: "dan", age: nil };
+            print(u);
+            u.age = 40;
+            print(u.age);
+
+            let found = find(ages, "bob");
+            print(found);
+
+            let maybe: List<i32?> = [];
+            push(maybe, 1);
+            maybe.push(nil);
+            let second = maybe[1];
+            print(maybe[0]);
+            print(second);
+        }
+        "#;
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(
+            output,
+            "31\nnil\n\"no carol\"\n32\nnil\n10\nUser { name: \"dan\", age: nil }\n40\n27\n1\nnil\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_unwrap_nil_panics() {
+        let input = add_into_main_func(r#"let x: i32? = nil; print(unwrap(x));"#);
+        let output = run_compiled_program(&input);
+        assert!(String::from_utf8_lossy(&output.stderr).ends_with(": unwrapped a nil value\n"));
+    }
+
+    #[test]
+    fn test_compile_optional_errors() {
+        let input = r#"
+        fn main() {
+            let a = nil;
+            let b: i32 = nil;
+            let c: i32? = 1;
+            let d = c + 1;
+            let e = 5;
+            if (e != nil) {
+                print(e);
+            }
+            print(unwrap(e));
+            let f: i32? = "five";
+        }
+        "#;
+        let messages = compile_error_messages(input);
+        assert_eq!(
+            messages,
+            vec![
+                "Cannot infer the type of nil",
+                "Cannot assign nil to non-optional type i32",
+                "Operator + is not defined for optional type i32?",
+                "Cannot compare i32 with nil",
+                "unwrap() expects an optional value, got i32",
+                "Type mismatch for variable 'f': declared as i32?, but value is string",
+            ]
+        );
     }
 
     #[test]
-    fn test_compile_while_stmt_increment() {
+    fn test_compile_lambdas_and_closures() {
         let input = r#"
-        let value = 0;
-        while(value < 10) {
-            value = value + 1;
-            print(value);
+        fn double(i32 x) -> i32 {
+            return x * 2;
+        }
+
+        fn apply(fn(i32) -> i32 f, i32 x) -> i32 {
+            return f(x);
+        }
+
+        fn make_adder(i32 n) -> fn(i32) -> i32 {
+            return |x| x + n;
+        }
+
+        fn main() {
+            let inc: fn(i32) -> i32 = |x| x + 1;
+            let a = inc(41);
+            print(a);
+            let twice = apply(double, 5);
+            print(twice);
+            let squared = apply(|x| x * x, 4);
+            print(squared);
+            let add_ten = make_adder(10);
+            let b = add_ten(5);
+            print(b);
+            let base = 100;
+            let offset = |i32 x| -> i32 { return x + base; };
+            base = 0;
+            let c = offset(1);
+            print(c);
+            let xs = [1, 2, 3, 4, 5];
+            let doubled = map(xs, double);
+            print(doubled);
+            let evens = xs.filter(|x| x % 2 == 0);
+            print(evens);
+            let big = xs.map(|x| x > 2);
+            print(big);
         }
         "#;
-        let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n");
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(
+            output,
+            "42\n10\n16\n15\n101\n[2,4,6,8,10][2,4][false,false,true,true,true]"
+        );
     }
 
     #[test]
-    fn test_compile_while_stmt_with_if_true() {
+    fn test_compile_for_each_inside_lambda() {
         let input = r#"
-        let value = true;
-        while(value) {
-            if (value == true) {
-                print(value);
-            }
-            value = false;
+        fn main() {
+            let show = |List<i32> xs| {
+                for (x in xs) {
+                    print(x);
+                }
+            };
+            show([1, 2]);
+            let ns = [3, 4];
+            let doubled = ns.map(|i32 n| -> i32 {
+                let total = 0;
+                for (m in ns) {
+                    total = total + m * n;
+                }
+                return total;
+            });
+            print(doubled);
         }
         "#;
-        let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "true\n");
+        let output = compile_output_from_string_test(input.to_string());
+        assert_eq!(output, "1\n2\n[21,28]");
     }
 
     #[test]
-    fn test_compile_while_stmt_one_pass_grouping_string() {
+    fn test_compile_lambda_errors() {
         let input = r#"
-        let value = true;
-        while(value) {
-            value = false;
-            print("here");
+        fn shout(string s) -> string {
+            return s;
+        }
+
+        fn main() {
+            let f = |x| x + 1;
+            let g: fn(i32) -> i32 = |i32 a, i32 b| a + b;
+            let total = 0;
+            let add = |i32 x| -> i32 {
+                total = total + x;
+                return total;
+            };
+            let h: fn(i32) -> i32 = |x| x * 2;
+            h("a");
+            let xs = [1, 2];
+            let ys = xs.filter(|x| x + 1);
+            let zs = map(xs, shout);
         }
         "#;
-        let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "\"here\"\n");
+        let messages = compile_error_messages(input);
+        assert_eq!(
+            messages,
+            vec![
+                "Cannot infer the type of parameter 'x'",
+                "Expected a function taking 1 argument, but the lambda takes 2",
+                "Type mismatch for variable 'g': declared as fn(i32) -> i32, but value is fn(i32, i32) -> i32",
+                "Cannot assign to captured variable 'total'",
+                "Argument of h() expects i32, got string",
+                "Lambda should return bool, but its body is i32",
+                "map() expects fn(i32) -> T, got fn(string) -> string",
+            ]
+        );
+    }
+
+    // Writes each (path, contents) pair under a fresh directory in the system
+    // temp dir, returning the path of the first one
+    fn write_module_files(dir: &str, files: &[(&str, &str)]) -> String {
+        let dir = std::env::temp_dir().join(dir);
+        let _ = fs::remove_dir_all(&dir);
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir.join(files[0].0).to_str().unwrap().to_string()
+    }
+
+    fn compile_module_err(main: &str) -> anyhow::Error {
+        let contents = fs::read_to_string(main).unwrap();
+        let exprs = parse_cyclo_program(&contents).unwrap();
+        let options = CompileOptions {
+            is_execution_engine: false,
+            emit_llvm_ir: false,
+            emit_llvm_ir_main_only: true,
+            emit_llvm_ir_with_called: false,
+            target: None,
+            checked_arithmetic: true,
+            source: Some(SourceFile::new(main, contents.as_str())),
+            output: None,
+            emit: None,
+            opt_level: OptLevel::O0,
+        };
+        compiler::compile(exprs, Some(options)).unwrap_err()
     }
 
     #[test]
-    fn test_compile_while_stmt_one_pass_grouping() {
+    fn test_compile_module_imports() {
+        let main = write_module_files(
+            "cyclang_test_module_imports",
+            &[
+                (
+                    "main.cyc",
+                    r#"
+                    import "lib/util.cyc";
+                    import "lib/math.cyc";
+                    fn add(i32 a, i32 b) -> i32 {
+                        return a * b;
+                    }
+                    fn main() {
+                        print(util::add(2, 3));
+                        print(add(2, 3));
+                        print(util::square_sum(1, 2));
+                        print(math::square(4));
+                    }
+                    "#,
+                ),
+                (
+                    "lib/util.cyc",
+                    r#"
+                    use math;
+                    pub fn add(i32 a, i32 b) -> i32 {
+                        return a + b;
+                    }
+                    pub fn square_sum(i32 a, i32 b) -> i32 {
+                        let sum = add(a, b);
+                        return math::square(sum);
+                    }
+                    "#,
+                ),
+                (
+                    "lib/math.cyc",
+                    r#"
+                    fn add(i32 a, i32 b) -> i32 {
+                        return a - b;
+                    }
+                    pub fn square(i32 x) -> i32 {
+                        return x * add(x, 0);
+                    }
+                    "#,
+                ),
+            ],
+        );
+        let contents = fs::read_to_string(&main).unwrap();
+        let output =
+            compile_output_from_string(contents, &main, false, false, None, true, OptLevel::O0);
+        assert_eq!(output, "5\n6\n9\n16\n");
+    }
+
+    #[test]
+    fn test_compile_module_errors() {
+        let main = write_module_files(
+            "cyclang_test_module_errors",
+            &[
+                (
+                    "main.cyc",
+                    r#"
+                    use util;
+                    fn main() {
+                        print(util::helper(1));
+                        print(util::missing(1));
+                    }
+                    "#,
+                ),
+                (
+                    "util.cyc",
+                    r#"
+                    fn helper(i32 x) -> i32 {
+                        return x;
+                    }
+                    "#,
+                ),
+            ],
+        );
+        let messages = error_messages(&compile_module_err(&main));
+        assert_eq!(
+            messages,
+            vec![
+                "Module 'util' has no function 'missing'",
+                "Function 'helper' is private to module 'util'",
+            ]
+        );
+
+        let main = write_module_files(
+            "cyclang_test_module_cycle",
+            &[
+                ("main.cyc", "import \"a.cyc\";\nfn main() {}"),
+                ("a.cyc", "import \"b.cyc\";"),
+                ("b.cyc", "import \"a.cyc\";"),
+            ],
+        );
+        let error = compile_module_err(&main);
+        let diagnostic = error.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(diagnostic.message, "Import cycle: a.cyc -> b.cyc -> a.cyc");
+    }
+
+    #[test]
+    fn test_compile_module_error_locations() {
+        let main = write_module_files(
+            "cyclang_test_module_locations",
+            &[
+                (
+                    "main.cyc",
+                    "import \"lib/util.cyc\";\nfn main() {\n    print(util::half(4));\n}",
+                ),
+                (
+                    "lib/util.cyc",
+                    concat!(
+                        "pub fn half(i32 x) -> i32 {\n",
+                        "    let a: i32 = \"hello\";\n",
+                        "    return x / 2;\n",
+                        "}",
+                    ),
+                ),
+            ],
+        );
+        let contents = fs::read_to_string(&main).unwrap();
+        let error = compile_module_err(&main);
+        let rendered = diagnostics::render_error(&error, &contents, &main);
+        assert!(rendered.contains("lib/util.cyc:2:18\n"), "{rendered}");
+        assert!(
+            rendered.contains("2 |     let a: i32 = \"hello\";\n"),
+            "{rendered}"
+        );
+
+        // Runtime panics in imported code point into the imported file too
+        let main = write_module_files(
+            "cyclang_test_module_panics",
+            &[
+                (
+                    "main.cyc",
+                    "import \"lib/util.cyc\";\nfn main() {\n    print(util::div(1, 0));\n}",
+                ),
+                (
+                    "lib/util.cyc",
+                    "pub fn div(i32 x, i32 y) -> i32 {\n    return x / y;\n}",
+                ),
+            ],
+        );
+        let output = Path::new(&main).with_file_name("main");
+        build(
+            &main,
+            Some(output.to_str().unwrap().to_string()),
+            None,
+            None,
+            true,
+            OptLevel::O0,
+        );
+        let run = std::process::Command::new(&output).output().unwrap();
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        assert!(
+            stderr.ends_with("lib/util.cyc:2:12: attempt to divide by zero\n"),
+            "{stderr}"
+        );
+    }
+
+    #[test]
+    fn test_build_executable() {
+        let file = write_module_files(
+            "cyclang_test_build",
+            &[("answer.cyc", "fn main() {\n    print(40 + 2);\n}")],
+        );
+        let output = Path::new(&file).with_file_name("answer");
+        build(
+            &file,
+            Some(output.to_str().unwrap().to_string()),
+            None,
+            None,
+            true,
+            OptLevel::O0,
+        );
+        let run = std::process::Command::new(&output).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "42\n");
+    }
+
+    #[test]
+    fn test_build_float_remainder() {
+        let file = write_module_files(
+            "cyclang_test_build_fmod",
+            &[(
+                "remainder.cyc",
+                "fn main() {\n    let a = 7.5;\n    print(a % 2.0);\n}",
+            )],
+        );
+        let output = Path::new(&file).with_file_name("remainder");
+        build(
+            &file,
+            Some(output.to_str().unwrap().to_string()),
+            None,
+            None,
+            true,
+            OptLevel::O0,
+        );
+        let run = std::process::Command::new(&output).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&run.stdout), "1.500000\n");
+    }
+
+    #[test]
+    fn test_build_emit_kinds() {
+        let file = write_module_files(
+            "cyclang_test_emit",
+            &[("answer.cyc", "fn main() {\n    print(40 + 2);\n}")],
+        );
+        let emitted = |name: &str| {
+            let kind = EmitKind::from_name(name).unwrap();
+            let output = Path::new(&file).with_extension(kind.extension());
+            build(
+                &file,
+                Some(output.to_str().unwrap().to_string()),
+                Some(kind),
+                None,
+                true,
+                OptLevel::O0,
+            );
+            fs::read(output).unwrap()
+        };
+        assert!(!emitted("obj").is_empty());
+        assert!(emitted("bc").starts_with(b"BC\xC0\xDE"));
+        let ir = String::from_utf8(emitted("llvm-ir")).unwrap();
+        assert!(ir.contains("@main("));
+        let asm = String::from_utf8(emitted("asm")).unwrap();
+        assert!(asm.contains("main:"));
+
+        // The REPL's :asm compiles the IR in-process the same way
+        assert!(codegen::emit::ir_to_assembly(&ir)
+            .unwrap()
+            .contains("main:"));
+    }
+
+    #[test]
+    fn test_build_verifies_module() {
+        let input = "fn f() -> i32 {\n    return \"x\";\n}\nfn main() {\n    print(1);\n}";
+        let output = std::env::temp_dir().join("cyclang_test_verify");
+        for emit in [None, Some(EmitKind::Object), Some(EmitKind::LlvmIr)] {
+            let options = CompileOptions {
+                is_execution_engine: false,
+                emit_llvm_ir: false,
+                emit_llvm_ir_main_only: true,
+                emit_llvm_ir_with_called: false,
+                target: None,
+                checked_arithmetic: true,
+                source: Some(SourceFile::new("test.cyc", input)),
+                output: Some(output.to_str().unwrap().to_string()),
+                emit,
+                opt_level: OptLevel::O2,
+            };
+            let exprs = parse_cyclo_program(input).unwrap();
+            let error = compiler::compile(exprs, Some(options)).unwrap_err();
+            let message = error.to_string();
+            assert!(
+                message.starts_with("LLVM module verification failed"),
+                "{message}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_compile_opt_levels() {
         let input = r#"
-        let value = true;
-        while(value) {
-            print(value);
-            value = false;
+        fn fib(i32 n) -> i32 {
+            let a = 0;
+            let b = 1;
+            for (let i = 0; i < n; i++) {
+                let next = a + b;
+                a = b;
+                b = next;
+            }
+            return a;
+        }
+        fn main() {
+            print(fib(10));
         }
         "#;
-        let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "true\n");
+        for opt_level in ["0", "1", "2", "3"] {
+            let opt_level = OptLevel::from_name(opt_level).unwrap();
+            let output = compile_output_from_string(
+                input.to_string(),
+                "test.cyc",
+                false,
+                false,
+                None,
+                true,
+                opt_level,
+            );
+            assert_eq!(output, "55\n");
+        }
+
+        // At -O2 fib is inlined into main and folded down to its result
+        let ir = compile_output_from_string(
+            input.to_string(),
+            "test.cyc",
+            false,
+            true,
+            None,
+            true,
+            OptLevel::O2,
+        );
+        assert!(!ir.contains("@fib("));
+        assert!(ir.contains("i32 55"));
     }
 
     #[test]
-    fn test_compile_while_stmt_false() {
+    fn test_build_cross_targets() {
+        let file = write_module_files(
+            "cyclang_test_targets",
+            &[(
+                "answer.cyc",
+                "fn main() {\n    let xs = [40];\n    push(xs, 2);\n    print(xs);\n}",
+            )],
+        );
+        // ELF class (1 for 32-bit, 2 for 64-bit) and e_machine of each object
+        let targets = [
+            ("arm32", 1, 40),
+            ("arm64", 2, 183),
+            ("x86_32", 1, 3),
+            ("x86_64", 2, 62),
+            ("riscv64-unknown-linux-gnu", 2, 243),
+        ];
+        for (name, class, machine) in targets {
+            let output = Path::new(&file).with_extension(format!("{name}.o"));
+            build(
+                &file,
+                Some(output.to_str().unwrap().to_string()),
+                Some(EmitKind::Object),
+                Target::from_target_name(name),
+                true,
+                OptLevel::O2,
+            );
+            let object = fs::read(output).unwrap();
+            assert_eq!(&object[..4], b"\x7fELF", "{name}");
+            assert_eq!(object[4], class, "{name}");
+            assert_eq!(
+                u16::from_le_bytes([object[18], object[19]]),
+                machine,
+                "{name}"
+            );
+        }
+
+        let output = Path::new(&file).with_extension("wasm.o");
+        build(
+            &file,
+            Some(output.to_str().unwrap().to_string()),
+            Some(EmitKind::Object),
+            Some(Target::wasm),
+            true,
+            OptLevel::O0,
+        );
+        assert!(fs::read(output).unwrap().starts_with(b"\0asm"));
+        assert_eq!(Target::from_target_name("arm65"), None);
+
+        // The runtime is compiled for the target, so a 32-bit one calls it
+        // and libc with 32-bit sizes
+        let output = Path::new(&file).with_extension("x86_32.ll");
+        build(
+            &file,
+            Some(output.to_str().unwrap().to_string()),
+            Some(EmitKind::LlvmIr),
+            Some(Target::x86_32),
+            true,
+            OptLevel::O0,
+        );
+        let ir = fs::read_to_string(output).unwrap();
+        assert!(ir.contains("call ptr @pushList(ptr %list_create)"), "{ir}");
+        assert!(ir.contains("declare ptr @malloc(i32"), "{ir}");
+
+        // Only the IR of a program built for another machine can be printed,
+        // it can't be run
+        assert!(check_run_target(Some(&Target::wasm), false).is_err());
+        assert!(check_run_target(Some(&Target::wasm), true).is_ok());
+        assert!(check_run_target(None, false).is_ok());
+    }
+
+    #[test]
+    fn test_compile_for_loop() {
         let input = r#"
-        let value = false;
-        while(value) {
-            print(value);
+        for (let i = 0; i < 10; i++)
+        {  
+            print(i);
         }
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "");
+        assert_eq!(output, "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n");
     }
 
     #[test]
-    fn test_compile_while_stmt_with_if() {
+    fn test_compile_for_loop_variable_shadows_outer_variable() {
         let input = r#"
-            let cond = true;
-            let val = 0;
-            while (cond) {
-                val = val + 1;
-                if (val == 10) {
-                   cond = false;
-                }
-            }
-            print(val);
+        let i = 100;
+        for (let i = 0; i < 3; i++) {
+            print(i);
+        }
+        print(i);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "10\n");
+        assert_eq!(output, "0\n1\n2\n100\n");
     }
 
     #[test]
-    fn test_compile_while_stmt_break() {
+    fn test_compile_let_copies_scalar_variable() {
         let input = r#"
-            let val = 0;
-            while (true) {
-                val = val + 1;
-                break;
-            }
-            print(val);
+        let start = 5;
+        for (let i = start; i < 8; i++) {
+            print(i);
+        }
+        print(start);
+        let a = 1.5;
+        let b = a;
+        b = 2.5;
+        print(a);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "1\n");
+        assert_eq!(output, "5\n6\n7\n5\n1.500000\n");
     }
 
     #[test]
-    fn test_compile_for_loop() {
+    fn test_compile_for_loop_with_assign() {
         let input = r#"
+        let value = 0;
         for (let i = 0; i < 10; i++)
-        {  
-            print(i);
+        {
+            value = i + value;
         }
+        print(value);
         "#;
         let output = compile_output_from_string_test(add_into_main_func(input));
-        assert_eq!(output, "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n");
+        assert_eq!(output, "45\n");
     }
 
-    //Todo: readd for loop edge case
-    // #[test]
-    // fn test_compile_for_loop_with_assign() {
-    //     let input = r#"
-    //     let value = 0;
-    //     for (let i = 0; i < 10; i++)
-    //     {
-    //         value = i + value;
-    //     }
-    //     print(value);
-    //     "#;
-    //     let output = compile_output_from_string_test(input.to_string());
-    //     assert_eq!(output, "45\n");
-    // }
-
     #[test]
     fn test_compile_block_stmt_bool() {
         let input = r#"
@@ -707,6 +2399,147 @@ fn main() {{
         assert_eq!(output, "10\n19\n27\n34\n40\n45\n49\n52\n54\n55\n");
     }
 
+    #[test]
+    fn test_compile_for_loop_with_expression_bounds_and_step() {
+        let input = r#"
+        let xs = [3, 5, 7, 9, 11];
+        for (let i = 0; i < len(xs); i += 2) {
+            print(xs[i]);
+        }
+        for (let i = len(xs) - 1; i >= 0; i = i - 3) {
+            print(xs[i]);
+        }
+        for (let i = 1; i <= 3; i++) {
+            print(i);
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "3\n7\n11\n11\n5\n1\n2\n3\n");
+    }
+
+    #[test]
+    fn test_compile_for_loop_step_runs_on_every_path() {
+        let input = r#"
+        let total = 0;
+        for (let i = 0; i < 10; i++) {
+            if (i % 2 == 0) {
+                total = total + i;
+            } else {
+                total = total + 100;
+            }
+        }
+        print(total);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "520\n");
+    }
+
+    #[test]
+    fn test_compile_for_loop_condition_must_be_bool() {
+        let input = r#"
+        fn main() {
+            for (let i = 0; i + 1; i++) {
+                print(i);
+            }
+        }
+        "#;
+        let messages = compile_error_messages(input);
+        assert_eq!(messages, vec!["For condition must be boolean, got i32"]);
+    }
+
+    #[test]
+    fn test_compile_for_each_list() {
+        let input = r#"
+        let total = 0;
+        for (x in [1, 2, 3, 4]) {
+            total = total + x;
+        }
+        print(total);
+        let pairs = 0;
+        for (a in [1, 2, 3]) {
+            for (b in [10, 20]) {
+                pairs = pairs + a * b;
+            }
+        }
+        print(pairs);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "10\n180\n");
+    }
+
+    #[test]
+    fn test_compile_for_each_map_keys_and_string_chars() {
+        let input = r#"
+        let ages = {"alice": 31, "bob": 27};
+        for (name in ages) {
+            print(ages[name]);
+        }
+        for (c in "héy") {
+            print(c);
+        }
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "31\n27\n\"h\"\n\"é\"\n\"y\"\n");
+    }
+
+    #[test]
+    fn test_compile_for_each_with_break_over_large_list() {
+        let input = r#"
+        let xs: List<i32> = [];
+        let i = 0;
+        while (i < 1000000) {
+            push(xs, i);
+            i = i + 1;
+        }
+        let total = i64(0);
+        for (x in xs) {
+            if (x == 999999) {
+                break;
+            }
+            total = total + i64(x);
+        }
+        print(total);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "499998500001\n");
+    }
+
+    #[test]
+    fn test_compile_for_each_variable_is_scoped_to_loop() {
+        let input = r#"
+        for (x in [1, 2]) {
+            print(x);
+        }
+        print(x);
+        for (y in 5) {
+            print(y);
+        }
+        "#;
+        let messages = compile_error_messages(&add_into_main_func(input));
+        assert_eq!(
+            messages,
+            vec!["Undefined variable: x", "Cannot iterate over i32"]
+        );
+    }
+
+    #[test]
+    fn test_compile_for_each_variable_shadows_outer_variable() {
+        let input = r#"
+        let x = 7;
+        for (x in [1, 2]) {
+            print(x);
+        }
+        print(x);
+        let c = true;
+        for (c in "ab") {
+            print(c);
+        }
+        print(c);
+        "#;
+        let output = compile_output_from_string_test(add_into_main_func(input));
+        assert_eq!(output, "1\n2\n7\n\"a\"\n\"b\"\ntrue\n");
+    }
+
     #[test]
     fn test_compile_function_return_int() {
         let input = r#"
@@ -901,4 +2734,91 @@ fn main() {{
         let output = compile_output_from_string_test(add_into_main_func(input));
         assert_eq!(output, "true\n");
     }
+
+    // Messages of every diagnostic a compile error carries
+    fn error_messages(error: &anyhow::Error) -> Vec<String> {
+        let diagnostics = error.downcast_ref::<compiler::Diagnostics>().unwrap();
+        diagnostics.iter().map(|d| d.message.clone()).collect()
+    }
+
+    // Compiles a program that must fail, returning the message of each error
+    fn compile_error_messages(input: &str) -> Vec<String> {
+        let exprs = parse_cyclo_program(input).unwrap();
+        error_messages(&compiler::compile(exprs, None).unwrap_err())
+    }
+
+    #[test]
+    fn test_compile_reports_every_error() {
+        let input = r#"
+        fn main() {
+            let a: i32 = "hello";
+            print(b);
+            if (1) {
+                print(c);
+            }
+        }
+        "#;
+        let messages = compile_error_messages(input);
+        assert_eq!(
+            messages,
+            vec![
+                "Type mismatch for variable 'a': declared as i32, but value is string",
+                "Undefined variable: b",
+                "If condition must be boolean, got i32",
+                "Undefined variable: c",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_undefined_variable_does_not_cascade() {
+        let input = add_into_main_func("let a = x + 1; let b: i32 = a; print(b);");
+        assert_eq!(compile_error_messages(&input).len(), 1);
+    }
+
+    #[test]
+    fn test_compile_logical_operators_require_bool() {
+        let input = add_into_main_func("let a = 1 && true; let b = !5;");
+        let messages = compile_error_messages(&input);
+        assert_eq!(
+            messages,
+            vec![
+                "Operator && requires bool operands, got i32",
+                "Cannot apply unary operator ! to i32",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compile_power_requires_integers() {
+        let input = add_into_main_func("let a = 2.0 ^ 2;");
+        let messages = compile_error_messages(&input);
+        assert_eq!(messages, vec!["Operator ^ requires integer operands, got f64"]);
+    }
+
+    #[test]
+    fn test_compile_operators_require_matching_operands() {
+        let input = add_into_main_func(
+            r#"
+            let n = 1;
+            let s = "a";
+            let a = "a" < 1;
+            let b = n == s;
+            let c = s < s;
+            let d = 1 + "a";
+            let e = true - 1;
+            "#,
+        );
+        let messages = compile_error_messages(&input);
+        assert_eq!(
+            messages,
+            vec![
+                "Operator < is not defined for string and i32",
+                "Operator == is not defined for i32 and string",
+                "Operator < is not defined for string and string",
+                "Operator + is not defined for i32 and string",
+                "Operator - is not defined for bool and i32",
+            ]
+        );
+    }
 }
@@ -1,7 +1,8 @@
 use anyhow::{anyhow, Result};
 use backend::compiler::{CompileOptions, desugar_program};
 use backend::compiler;
-use parser::{parse_cyclo_program, Expression};
+use codegen::emit::{self, OptLevel};
+use parser::{parse_cyclo_program, Expression, ExpressionKind};
 use rustyline::completion::Completer;
 use rustyline::error::ReadlineError;
 use rustyline::highlight::Highlighter;
@@ -14,8 +15,6 @@ use rustyline::{Cmd, EventHandler, KeyCode, KeyEvent, Modifiers};
 use std::borrow::Cow;
 use std::collections::HashSet;
 use std::fs;
-use std::io::Write;
-use std::process::Command;
 use text_colorizer::*;
 
 struct CyclangHelper;
@@ -508,13 +507,18 @@ fn parse_and_compile(input: String, persisted: &mut Vec<String>) -> Result<Strin
         emit_llvm_ir_main_only: true,
         emit_llvm_ir_with_called: false,
         target: None,
+        checked_arithmetic: true,
+        source: None,
+        output: None,
+        emit: None,
+        opt_level: OptLevel::O0,
     });
     let output = compiler::compile(exprs.clone(), compile_options)?;
 
     for expr in parse_cyclo_program(&input)? {
-        if let Expression::LetStmt(_, _, _)
-        | Expression::FuncStmt(_, _, _, _)
-        | Expression::ListAssign(_, _, _) = expr
+        if let ExpressionKind::LetStmt(_, _, _)
+        | ExpressionKind::FuncStmt(_, _, _, _)
+        | ExpressionKind::ListAssign(_, _, _) = expr.kind
         {
             persisted.push(input.clone());
         }
@@ -531,6 +535,11 @@ fn parse_and_compile_no_state(input: String) -> Result<String> {
         emit_llvm_ir_main_only: true,
         emit_llvm_ir_with_called: false,
         target: None,
+        checked_arithmetic: true,
+        source: None,
+        output: None,
+        emit: None,
+        opt_level: OptLevel::O0,
     });
     compiler::compile(exprs, compile_options)
 }
@@ -550,6 +559,11 @@ fn parse_and_emit_ir(input: String, persisted: &mut [String]) -> Result<String>
         emit_llvm_ir_main_only: true,
         emit_llvm_ir_with_called: true,
         target: None,
+        checked_arithmetic: true,
+        source: None,
+        output: None,
+        emit: None,
+        opt_level: OptLevel::O0,
     });
     compiler::compile(exprs, compile_options)
 }
@@ -569,9 +583,14 @@ fn parse_and_opt_ir(input: String, persisted: &mut [String]) -> Result<String> {
         emit_llvm_ir_main_only: false,
         emit_llvm_ir_with_called: false,
         target: None,
+        checked_arithmetic: OptLevel::O2.checks_arithmetic(),
+        source: None,
+        output: None,
+        emit: None,
+        opt_level: OptLevel::O2,
     });
-    let module_ir = compiler::compile(exprs, compile_options)?;
-    run_opt_on_ir(&module_ir)
+    let optimized = compiler::compile(exprs, compile_options)?;
+    Ok(extract_main_ir(&optimized).unwrap_or(optimized))
 }
 
 fn parse_and_asm(input: String, persisted: &mut [String]) -> Result<String> {
@@ -589,9 +608,15 @@ fn parse_and_asm(input: String, persisted: &mut [String]) -> Result<String> {
         emit_llvm_ir_main_only: false,
         emit_llvm_ir_with_called: false,
         target: None,
+        checked_arithmetic: true,
+        source: None,
+        output: None,
+        emit: None,
+        opt_level: OptLevel::O0,
     });
     let module_ir = compiler::compile(exprs, compile_options)?;
-    run_llc_on_ir(&module_ir)
+    let asm = emit::ir_to_assembly(&module_ir)?;
+    Ok(extract_main_asm(&asm).unwrap_or(asm))
 }
 
 fn parse_and_ast(input: String, persisted: &mut [String]) -> Result<String> {
@@ -617,58 +642,6 @@ fn parse_and_ast_desugar(input: String, persisted: &mut [String]) -> Result<Stri
     Ok(format_ast(&desugared))
 }
 
-fn run_opt_on_ir(ir: &str) -> Result<String> {
-    let mut child = Command::new("opt")
-        .arg("-O2")
-        .arg("-S")
-        .arg("-")
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| anyhow!("Failed to run opt: {e}"))?;
-
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(ir.as_bytes())?;
-    }
-
-    let output = child.wait_with_output()?;
-    if !output.status.success() {
-        return Err(anyhow!(
-            "opt failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
-    }
-    let optimized = String::from_utf8_lossy(&output.stdout).to_string();
-    Ok(extract_main_ir(&optimized).unwrap_or(optimized))
-}
-
-fn run_llc_on_ir(ir: &str) -> Result<String> {
-    let mut child = Command::new("llc")
-        .arg("-O2")
-        .arg("-filetype=asm")
-        .arg("-o")
-        .arg("-")
-        .arg("-")
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| anyhow!("Failed to run llc: {e}"))?;
-
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(ir.as_bytes())?;
-    }
-
-    let output = child.wait_with_output()?;
-    if !output.status.success() {
-        return Err(anyhow!(
-            "llc failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
-    }
-    let asm = String::from_utf8_lossy(&output.stdout).to_string();
-    Ok(extract_main_asm(&asm).unwrap_or(asm))
-}
-
 fn extract_main_asm(asm: &str) -> Option<String> {
     let lines = asm.lines().peekable();
     let mut buf = String::new();
@@ -830,26 +803,44 @@ fn highlight_ast(input: &str) -> String {
             || clean_label.starts_with("List")
             || clean_label.starts_with("ListIndex")
             || clean_label.starts_with("ListAssign")
+            || clean_label.starts_with("StructStmt")
+            || clean_label.starts_with("StructLiteral")
+            || clean_label.starts_with("EnumStmt")
+            || clean_label.starts_with("EnumValue")
         {
             BLOCK
-        } else if clean_label.starts_with("FuncStmt") || clean_label.starts_with("FuncArg") {
+        } else if clean_label.starts_with("FuncStmt")
+            || clean_label.starts_with("FuncArg")
+            || clean_label.starts_with("ImplStmt")
+            || clean_label.starts_with("Lambda")
+            || clean_label.starts_with("PubStmt")
+        {
             FUNC
         } else if clean_label.starts_with("IfStmt")
             || clean_label.starts_with("WhileStmt")
             || clean_label.starts_with("ForStmt")
             || clean_label.starts_with("ReturnStmt")
             || clean_label.starts_with("BreakStmt")
+            || clean_label.starts_with("ContinueStmt")
+            || clean_label.starts_with("LabeledStmt")
+            || clean_label.starts_with("MatchStmt")
+            || clean_label.starts_with("MatchArm")
         {
             FLOW
         } else if clean_label.starts_with("Binary")
+            || clean_label.starts_with("Unary")
             || clean_label.starts_with("Grouping")
             || clean_label.starts_with("CallStmt")
             || clean_label.starts_with("Print")
             || clean_label.starts_with("Len")
+            || clean_label.starts_with("FieldAccess")
+            || clean_label.starts_with("FieldAssign")
+            || clean_label.starts_with("MethodCall")
         {
             OP
         } else if clean_label.starts_with("Number")
             || clean_label.starts_with("Number64")
+            || clean_label.starts_with("Float")
             || clean_label.starts_with("String")
             || clean_label.starts_with("Bool")
             || clean_label.starts_with("Nil")
@@ -879,18 +870,20 @@ fn highlight_ast(input: &str) -> String {
 }
 
 fn format_expr_tree(expr: &Expression, prefix: &str, is_last: bool, out: &mut String) {
-    use Expression::*;
+    use ExpressionKind::*;
     let branch = if is_last { "└─ " } else { "├─ " };
     let next_prefix = if is_last { "   " } else { "│  " };
 
-    let label = match expr {
+    let label = match &expr.kind {
         Number(n) => format!("Number({n})"),
         Number64(n) => format!("Number64({n})"),
+        Float(n) => format!("Float({n})"),
         String(s) => format!("String({s})"),
         Bool(b) => format!("Bool({b})"),
         Nil => "Nil".to_string(),
         Variable(name) => format!("Variable({name})"),
         Binary(_, op, _) => format!("Binary({op})"),
+        Unary(op, _) => format!("Unary({op})"),
         Grouping(_) => "Grouping".to_string(),
         LetStmt(name, ty, _) => format!("LetStmt({name}: {})", format_type(ty)),
         BlockStmt(_) => "BlockStmt".to_string(),
@@ -900,19 +893,45 @@ fn format_expr_tree(expr: &Expression, prefix: &str, is_last: bool, out: &mut St
             format!("ExternFuncStmt({name} -> {})", format_type(ret_ty))
         }
         ExternModule(path) => format!("ExternModule({path})"),
+        Import(path) => format!("Import({path})"),
+        PubStmt(_) => "PubStmt".to_string(),
         CallStmt(name, _) => format!("CallStmt({name})"),
         IfStmt(_, _, _) => "IfStmt".to_string(),
         WhileStmt(_, _) => "WhileStmt".to_string(),
         ReturnStmt(_) => "ReturnStmt".to_string(),
-        BreakStmt => "BreakStmt".to_string(),
-        ForStmt(name, start, end, step, _) => {
-            format!("ForStmt({name} = {start}..{end} step {step})")
-        }
+        BreakStmt(None) => "BreakStmt".to_string(),
+        BreakStmt(Some(label)) => format!("BreakStmt({label})"),
+        ContinueStmt(None) => "ContinueStmt".to_string(),
+        ContinueStmt(Some(label)) => format!("ContinueStmt({label})"),
+        LabeledStmt(label, _) => format!("LabeledStmt({label})"),
+        ForStmt(_, _, _, _) => "ForStmt".to_string(),
+        ForEachStmt(name, _, _) => format!("ForEachStmt({name})"),
+        IterItems(_) => "IterItems".to_string(),
+        DeclareStmt(name, ty, _) => format!("DeclareStmt({name}: {})", format_type(ty)),
         Print(_) => "Print".to_string(),
         Len(_) => "Len".to_string(),
         List(_) => "List".to_string(),
         ListIndex(_, _) => "ListIndex".to_string(),
         ListAssign(name, _, _) => format!("ListAssign({name})"),
+        ListSlice(_, _, _) => "ListSlice".to_string(),
+        Map(_) => "Map".to_string(),
+        StructStmt(name, _) => format!("StructStmt({name})"),
+        StructLiteral(name, _) => format!("StructLiteral({name})"),
+        FieldAccess(_, field) => format!("FieldAccess({field})"),
+        FieldAssign(_, field, _) => format!("FieldAssign({field})"),
+        MethodCall(_, method, _) => format!("MethodCall({method})"),
+        ImplStmt(ty, _) => format!("ImplStmt({})", format_type(ty)),
+        Lambda(params, _, _) => {
+            let names: Vec<&str> = params.iter().map(|(name, _)| name.as_str()).collect();
+            format!("Lambda({})", names.join(", "))
+        }
+        EnumStmt(name, _) => format!("EnumStmt({name})"),
+        EnumValue(name, variant, _) => format!("EnumValue({name}::{variant})"),
+        MatchStmt(_, _) => "MatchStmt".to_string(),
+        MatchArm(parser::Pattern::Wildcard, _) => "MatchArm(_)".to_string(),
+        MatchArm(parser::Pattern::Variant(name, variant, bindings), _) => {
+            format!("MatchArm({name}::{variant}({}))", bindings.join(", "))
+        }
     };
 
     out.push_str(prefix);
@@ -921,15 +940,15 @@ fn format_expr_tree(expr: &Expression, prefix: &str, is_last: bool, out: &mut St
     out.push('\n');
 
     let child_prefix = format!("{prefix}{next_prefix}");
-    match expr {
+    match &expr.kind {
         Binary(lhs, _, rhs) => {
             format_expr_tree(lhs, &child_prefix, false, out);
             format_expr_tree(rhs, &child_prefix, true, out);
         }
-        Grouping(inner) => {
-            format_expr_tree(inner, &child_prefix, true, out);
+        Unary(_, operand) | Grouping(operand) => {
+            format_expr_tree(operand, &child_prefix, true, out);
         }
-        LetStmt(_, _, value) => {
+        LetStmt(_, _, value) | DeclareStmt(_, _, value) => {
             format_expr_tree(value, &child_prefix, true, out);
         }
         BlockStmt(stmts) => {
@@ -960,7 +979,10 @@ fn format_expr_tree(expr: &Expression, prefix: &str, is_last: bool, out: &mut St
                 format_expr_tree(arg, &args_prefix, last, out);
             }
         }
-        ExternModule(_) => {}
+        ExternModule(_) | Import(_) => {}
+        PubStmt(func) => {
+            format_expr_tree(func, &child_prefix, true, out);
+        }
         CallStmt(_, args) => {
             for (i, arg) in args.iter().enumerate() {
                 let last = i + 1 == args.len();
@@ -1005,11 +1027,21 @@ fn format_expr_tree(expr: &Expression, prefix: &str, is_last: bool, out: &mut St
         ReturnStmt(value) => {
             format_expr_tree(value, &child_prefix, true, out);
         }
-        BreakStmt => {}
-        ForStmt(_, _, _, _, body) => {
+        BreakStmt(_) | ContinueStmt(_) => {}
+        LabeledStmt(_, stmt) => {
+            format_expr_tree(stmt, &child_prefix, true, out);
+        }
+        ForStmt(init, cond, step, body) => {
+            format_expr_tree(init, &child_prefix, false, out);
+            format_expr_tree(cond, &child_prefix, false, out);
+            format_expr_tree(step, &child_prefix, false, out);
+            format_expr_tree(body, &child_prefix, true, out);
+        }
+        ForEachStmt(_, iterable, body) => {
+            format_expr_tree(iterable, &child_prefix, false, out);
             format_expr_tree(body, &child_prefix, true, out);
         }
-        Print(expr) | Len(expr) => {
+        Print(expr) | Len(expr) | IterItems(expr) => {
             format_expr_tree(expr, &child_prefix, true, out);
         }
         List(values) => {
@@ -1026,6 +1058,94 @@ fn format_expr_tree(expr: &Expression, prefix: &str, is_last: bool, out: &mut St
             format_expr_tree(index, &child_prefix, false, out);
             format_expr_tree(value, &child_prefix, true, out);
         }
+        Map(entries) => {
+            for (i, (key, value)) in entries.iter().enumerate() {
+                let last = i + 1 == entries.len();
+                format_expr_tree(key, &child_prefix, false, out);
+                format_expr_tree(value, &child_prefix, last, out);
+            }
+        }
+        StructStmt(_, fields) => {
+            for (i, (name, ty)) in fields.iter().enumerate() {
+                let branch = if i + 1 == fields.len() {
+                    "└─ "
+                } else {
+                    "├─ "
+                };
+                out.push_str(&format!(
+                    "{child_prefix}{branch}{name}: {}\n",
+                    format_type(ty)
+                ));
+            }
+        }
+        StructLiteral(_, fields) => {
+            for (i, (_, value)) in fields.iter().enumerate() {
+                let last = i + 1 == fields.len();
+                format_expr_tree(value, &child_prefix, last, out);
+            }
+        }
+        FieldAccess(value, _) => {
+            format_expr_tree(value, &child_prefix, true, out);
+        }
+        FieldAssign(target, _, value) => {
+            format_expr_tree(target, &child_prefix, false, out);
+            format_expr_tree(value, &child_prefix, true, out);
+        }
+        MethodCall(receiver, _, args) => {
+            format_expr_tree(receiver, &child_prefix, args.is_empty(), out);
+            for (i, arg) in args.iter().enumerate() {
+                let last = i + 1 == args.len();
+                format_expr_tree(arg, &child_prefix, last, out);
+            }
+        }
+        Lambda(_, _, body) => {
+            format_expr_tree(body, &child_prefix, true, out);
+        }
+        ImplStmt(_, methods) => {
+            for (i, method) in methods.iter().enumerate() {
+                let last = i + 1 == methods.len();
+                format_expr_tree(method, &child_prefix, last, out);
+            }
+        }
+        EnumStmt(_, variants) => {
+            for (i, (name, payload)) in variants.iter().enumerate() {
+                let branch = if i + 1 == variants.len() {
+                    "└─ "
+                } else {
+                    "├─ "
+                };
+                let payload: Vec<_> = payload.iter().map(format_type).collect();
+                out.push_str(&format!(
+                    "{child_prefix}{branch}{name}({})\n",
+                    payload.join(", ")
+                ));
+            }
+        }
+        EnumValue(_, _, values) => {
+            for (i, value) in values.iter().enumerate() {
+                let last = i + 1 == values.len();
+                format_expr_tree(value, &child_prefix, last, out);
+            }
+        }
+        MatchStmt(value, arms) => {
+            format_expr_tree(value, &child_prefix, arms.is_empty(), out);
+            for (i, arm) in arms.iter().enumerate() {
+                let last = i + 1 == arms.len();
+                format_expr_tree(arm, &child_prefix, last, out);
+            }
+        }
+        MatchArm(_, body) => {
+            format_expr_tree(body, &child_prefix, true, out);
+        }
+        ListSlice(list, start, end) => {
+            let bounds: Vec<&Expression> =
+                [start, end].into_iter().flatten().map(|b| &**b).collect();
+            format_expr_tree(list, &child_prefix, bounds.is_empty(), out);
+            for (i, bound) in bounds.iter().enumerate() {
+                let last = i + 1 == bounds.len();
+                format_expr_tree(bound, &child_prefix, last, out);
+            }
+        }
         _ => {}
     }
 }
@@ -1035,9 +1155,22 @@ fn format_type(ty: &parser::Type) -> String {
         parser::Type::None => "None".to_string(),
         parser::Type::i32 => "i32".to_string(),
         parser::Type::i64 => "i64".to_string(),
+        parser::Type::f64 => "f64".to_string(),
         parser::Type::String => "string".to_string(),
         parser::Type::Bool => "bool".to_string(),
         parser::Type::List(inner) => format!("List<{}>", format_type(inner)),
+        parser::Type::Map(key, value) => {
+            format!("Map<{}, {}>", format_type(key), format_type(value))
+        }
+        parser::Type::Struct(name) => name.clone(),
+        parser::Type::Optional(inner) => format!("{}?", format_type(inner)),
+        parser::Type::Function(args, ret) => {
+            let args: Vec<String> = args.iter().map(format_type).collect();
+            match ret.as_ref() {
+                parser::Type::None => format!("fn({})", args.join(", ")),
+                ret => format!("fn({}) -> {}", args.join(", "), format_type(ret)),
+            }
+        }
     }
 }
 
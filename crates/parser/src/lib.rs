@@ -2,36 +2,172 @@ extern crate pest;
 #[macro_use]
 extern crate pest_derive;
 
+use pest::iterators::Pairs;
+use pest::pratt_parser::{Assoc, Op, PrattParser};
 use pest::Parser;
-use std::num::ParseIntError;
+use std::num::{ParseFloatError, ParseIntError};
+use std::sync::LazyLock;
 
 #[derive(Parser)]
 #[grammar = "../grammar/cyclo.pest"]
 struct CycloParser;
 
+// Binary operator precedence, from loosest to tightest binding
+static PRATT_PARSER: LazyLock<PrattParser<Rule>> = LazyLock::new(|| {
+    PrattParser::new()
+        .op(Op::infix(Rule::or, Assoc::Left))
+        .op(Op::infix(Rule::and, Assoc::Left))
+        .op(Op::infix(Rule::eq, Assoc::Left)
+            | Op::infix(Rule::ne, Assoc::Left)
+            | Op::infix(Rule::ge, Assoc::Left)
+            | Op::infix(Rule::le, Assoc::Left)
+            | Op::infix(Rule::gt, Assoc::Left)
+            | Op::infix(Rule::lt, Assoc::Left))
+        .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::subtract, Assoc::Left))
+        .op(Op::infix(Rule::multiply, Assoc::Left)
+            | Op::infix(Rule::divide, Assoc::Left)
+            | Op::infix(Rule::modulo, Assoc::Left))
+        .op(Op::infix(Rule::power, Assoc::Right))
+});
+
 #[derive(Debug, Clone, PartialEq)]
 #[allow(non_camel_case_types)]
 pub enum Type {
     None,
     i32,
     i64,
+    f64,
     String,
     Bool,
     List(Box<Type>),
+    Map(Box<Type>, Box<Type>),
+    // A user-defined type named in source, a struct or an enum
+    Struct(String),
+    // i32?, a value of the inner type or nil
+    Optional(Box<Type>),
+    // fn(i32) -> i32, the return type is None when it is left out
+    Function(Vec<Type>, Box<Type>),
 }
 
+/// The left-hand side of a match arm.
 #[derive(Debug, Clone, PartialEq)]
-pub enum Expression {
+pub enum Pattern {
+    // _ matches any value
+    Wildcard,
+    // Shape::Rect(w, h) matches one variant, binding its payload values to names
+    Variant(String, String, Vec<String>),
+}
+
+/// Byte offsets into the source text covered by a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns the smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+/// A named source text, used to turn spans into `file:line:column` locations
+/// once the source itself is no longer at hand, e.g. in runtime panics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceFile {
+    pub name: String,
+    contents: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, contents: impl Into<String>) -> Self {
+        let contents = contents.into();
+        let line_starts = std::iter::once(0)
+            .chain(contents.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            name: name.into(),
+            contents,
+            line_starts,
+        }
+    }
+
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// Returns the 1-based line and column of a byte offset.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.contents.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let column = self.contents[line_start..offset].chars().count() + 1;
+        (line + 1, column)
+    }
+
+    /// Formats the start of `span` as `name:line:column`.
+    pub fn location(&self, span: Span) -> String {
+        let (line, column) = self.line_col(span.start);
+        format!("{}:{}:{}", self.name, line, column)
+    }
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Self {
+        Span::new(span.start(), span.end())
+    }
+}
+
+impl From<&pest::error::Error<Rule>> for Span {
+    fn from(error: &pest::error::Error<Rule>) -> Self {
+        match error.location {
+            pest::error::InputLocation::Pos(pos) => Span::new(pos, pos),
+            pest::error::InputLocation::Span((start, end)) => Span::new(start, end),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionKind {
     Number(i32),
     Number64(i64),
+    Float(f64),
     String(String),
     Bool(bool),
     Nil,
     List(Vec<Expression>),
+    Map(Vec<(Expression, Expression)>),
     ListIndex(Box<Expression>, Box<Expression>),
     ListAssign(String, Box<Expression>, Box<Expression>),
+    ListSlice(Box<Expression>, Option<Box<Expression>>, Option<Box<Expression>>),
+    // struct Point { x: i32, y: i32 }
+    StructStmt(String, Vec<(String, Type)>),
+    // Point { x: 1, y: 2 }, fields in the order they were written
+    StructLiteral(String, Vec<(String, Expression)>),
+    FieldAccess(Box<Expression>, String),
+    FieldAssign(Box<Expression>, String, Box<Expression>),
+    // enum Shape { Circle(i32), Rect(i32, i32) }
+    EnumStmt(String, Vec<(String, Vec<Type>)>),
+    // Shape::Circle(3)
+    EnumValue(String, String, Vec<Expression>),
+    // match (value) { ... }, each arm is a MatchArm
+    MatchStmt(Box<Expression>, Vec<Expression>),
+    MatchArm(Pattern, Box<Expression>),
+    // value.method(args)
+    MethodCall(Box<Expression>, String, Vec<Expression>),
+    // impl string { ... }, each method is a FuncStmt whose first argument is `self`
+    ImplStmt(Type, Vec<Expression>),
+    // |x| x + 1, parameter and return types are None when they are left out
+    Lambda(Vec<(String, Type)>, Type, Box<Expression>),
     Variable(String),
     Binary(Box<Expression>, String, Box<Expression>),
+    Unary(String, Box<Expression>),
     Grouping(Box<Expression>),
     LetStmt(String, Type, Box<Expression>),
     BlockStmt(Vec<Expression>),
@@ -39,88 +175,187 @@ pub enum Expression {
     FuncStmt(String, Vec<Expression>, Type, Box<Expression>),
     ExternFuncStmt(String, Vec<Expression>, Type),
     ExternModule(String),
+    // import "util.cyc"; or use util;, holding the path of the imported file
+    Import(String),
+    // pub fn ..., a function other modules are allowed to call
+    PubStmt(Box<Expression>),
     CallStmt(String, Vec<Expression>),
     IfStmt(Box<Expression>, Box<Expression>, Box<Option<Expression>>),
     WhileStmt(Box<Expression>, Box<Expression>),
     ReturnStmt(Box<Expression>),
-    ForStmt(String, i32, i32, i32, Box<Expression>),
-    BreakStmt,
+    // for (initialization; condition; step) body
+    ForStmt(
+        Box<Expression>,
+        Box<Expression>,
+        Box<Expression>,
+        Box<Expression>,
+    ),
+    ForEachStmt(String, Box<Expression>, Box<Expression>),
+    // The items a for-each loop walks over as a list, only produced by desugaring
+    IterItems(Box<Expression>),
+    // A let that always declares a new variable, shadowing any outer one of the
+    // same name, only produced by desugaring
+    DeclareStmt(String, Type, Box<Expression>),
+    // break and continue optionally name the loop they apply to
+    BreakStmt(Option<String>),
+    ContinueStmt(Option<String>),
+    LabeledStmt(String, Box<Expression>),
     Print(Box<Expression>),
     Len(Box<Expression>),
 }
 
+/// An AST node together with the source span it was parsed from.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+// Spans are ignored when comparing nodes so that ASTs compare by shape.
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
 impl Expression {
-    fn new_number(n: i32) -> Self {
-        Self::Number(n)
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Self { kind, span }
     }
-    fn new_number64(n: i64) -> Self {
-        Self::Number64(n)
+
+    fn new_number(n: i32, span: Span) -> Self {
+        Self::new(ExpressionKind::Number(n), span)
+    }
+    fn new_number64(n: i64, span: Span) -> Self {
+        Self::new(ExpressionKind::Number64(n), span)
+    }
+    fn new_float(n: f64, span: Span) -> Self {
+        Self::new(ExpressionKind::Float(n), span)
+    }
+
+    fn new_string(s: String, span: Span) -> Self {
+        Self::new(ExpressionKind::String(s), span)
     }
 
-    fn new_string(s: String) -> Self {
-        Self::String(s)
+    fn new_binary(left: Expression, op: String, right: Expression, span: Span) -> Self {
+        Self::new(
+            ExpressionKind::Binary(Box::new(left), op, Box::new(right)),
+            span,
+        )
     }
 
-    fn new_binary(left: Expression, op: String, right: Expression) -> Self {
-        Self::Binary(Box::new(left), op, Box::new(right))
+    fn new_bool(b: bool, span: Span) -> Self {
+        Self::new(ExpressionKind::Bool(b), span)
     }
 
-    fn new_bool(b: bool) -> Self {
-        Self::Bool(b)
+    fn new_list(list: Vec<Expression>, span: Span) -> Self {
+        Self::new(ExpressionKind::List(list), span)
     }
 
-    fn new_list(list: Vec<Expression>) -> Self {
-        Self::List(list)
+    fn new_map(entries: Vec<(Expression, Expression)>, span: Span) -> Self {
+        Self::new(ExpressionKind::Map(entries), span)
+    }
+
+    fn new_list_index(list: Expression, index: Expression, span: Span) -> Self {
+        Self::new(
+            ExpressionKind::ListIndex(Box::new(list), Box::new(index)),
+            span,
+        )
+    }
+
+    fn new_list_assign(var: String, index: Expression, value: Expression, span: Span) -> Self {
+        Self::new(
+            ExpressionKind::ListAssign(var, Box::new(index), Box::new(value)),
+            span,
+        )
     }
 
-    fn new_list_index(list: Expression, index: Expression) -> Self {
-        Self::ListIndex(Box::new(list), Box::new(index))
+    fn new_list_slice(
+        list: Expression,
+        start: Option<Expression>,
+        end: Option<Expression>,
+        span: Span,
+    ) -> Self {
+        Self::new(
+            ExpressionKind::ListSlice(Box::new(list), start.map(Box::new), end.map(Box::new)),
+            span,
+        )
     }
 
-    fn new_list_assign(var: String, index: Expression, value: Expression) -> Self {
-        Self::ListAssign(var, Box::new(index), Box::new(value))
+    fn new_nil(span: Span) -> Self {
+        Self::new(ExpressionKind::Nil, span)
     }
 
-    fn new_nil() -> Self {
-        Self::Nil
+    fn new_variable(name: String, span: Span) -> Self {
+        Self::new(ExpressionKind::Variable(name), span)
     }
 
-    fn new_variable(name: String) -> Self {
-        Self::Variable(name)
+    fn new_grouping(inner: Expression, span: Span) -> Self {
+        Self::new(ExpressionKind::Grouping(Box::new(inner)), span)
     }
 
-    fn new_let_stmt(name: String, let_type: Type, value: Expression) -> Self {
-        Self::LetStmt(name, let_type, Box::new(value))
+    fn new_let_stmt(name: String, let_type: Type, value: Expression, span: Span) -> Self {
+        Self::new(
+            ExpressionKind::LetStmt(name, let_type, Box::new(value)),
+            span,
+        )
     }
 
-    fn new_block_stmt(exprs: Vec<Expression>) -> Self {
-        Self::BlockStmt(exprs)
+    fn new_block_stmt(exprs: Vec<Expression>, span: Span) -> Self {
+        Self::new(ExpressionKind::BlockStmt(exprs), span)
     }
 
     fn new_if_stmt(
         condition: Expression,
         if_block_expr: Expression,
         else_block_expr: Option<Expression>,
+        span: Span,
     ) -> Self {
-        Self::IfStmt(
-            Box::new(condition),
-            Box::new(if_block_expr),
-            Box::new(else_block_expr),
+        Self::new(
+            ExpressionKind::IfStmt(
+                Box::new(condition),
+                Box::new(if_block_expr),
+                Box::new(else_block_expr),
+            ),
+            span,
         )
     }
 
-    fn new_while_stmt(condition: Expression, while_block_expr: Expression) -> Self {
-        Self::WhileStmt(Box::new(condition), Box::new(while_block_expr))
+    fn new_while_stmt(condition: Expression, while_block_expr: Expression, span: Span) -> Self {
+        Self::new(
+            ExpressionKind::WhileStmt(Box::new(condition), Box::new(while_block_expr)),
+            span,
+        )
     }
 
-    fn new_for_stmt(
+    fn new_for_each_stmt(
         var_name: String,
-        start: i32,
-        end: i32,
-        step: i32,
+        iterable: Expression,
+        for_block_expr: Expression,
+        span: Span,
+    ) -> Self {
+        Self::new(
+            ExpressionKind::ForEachStmt(var_name, Box::new(iterable), Box::new(for_block_expr)),
+            span,
+        )
+    }
+
+    fn new_for_stmt(
+        init: Expression,
+        condition: Expression,
+        step: Expression,
         for_block_expr: Expression,
+        span: Span,
     ) -> Self {
-        Self::ForStmt(var_name, start, end, step, Box::new(for_block_expr))
+        Self::new(
+            ExpressionKind::ForStmt(
+                Box::new(init),
+                Box::new(condition),
+                Box::new(step),
+                Box::new(for_block_expr),
+            ),
+            span,
+        )
     }
 
     fn new_func_stmt(
@@ -128,62 +363,145 @@ impl Expression {
         args: Vec<Expression>,
         return_type: Type,
         body: Expression,
+        span: Span,
+    ) -> Self {
+        Self::new(
+            ExpressionKind::FuncStmt(name, args, return_type, Box::new(body)),
+            span,
+        )
+    }
+
+    fn new_extern_func_stmt(
+        name: String,
+        args: Vec<Expression>,
+        return_type: Type,
+        span: Span,
     ) -> Self {
-        Self::FuncStmt(name, args, return_type, Box::new(body))
+        Self::new(
+            ExpressionKind::ExternFuncStmt(name, args, return_type),
+            span,
+        )
     }
 
-    fn new_extern_func_stmt(name: String, args: Vec<Expression>, return_type: Type) -> Self {
-        Self::ExternFuncStmt(name, args, return_type)
+    fn new_extern_module(path: String, span: Span) -> Self {
+        Self::new(ExpressionKind::ExternModule(path), span)
     }
 
-    fn new_extern_module(path: String) -> Self {
-        Self::ExternModule(path)
+    fn new_func_arg(name: String, arg_type: Type, span: Span) -> Self {
+        Self::new(ExpressionKind::FuncArg(name, arg_type), span)
     }
 
-    fn new_func_arg(name: String, arg_type: Type) -> Self {
-        Self::FuncArg(name, arg_type)
+    fn new_call_stmt(name: String, args: Vec<Expression>, span: Span) -> Self {
+        Self::new(ExpressionKind::CallStmt(name, args), span)
     }
 
-    fn new_call_stmt(name: String, args: Vec<Expression>) -> Self {
-        Self::CallStmt(name, args)
+    fn new_unary(op: String, operand: Expression, span: Span) -> Self {
+        Self::new(ExpressionKind::Unary(op, Box::new(operand)), span)
     }
 
-    fn new_print_stmt(value: Expression) -> Self {
-        Self::Print(Box::new(value))
+    fn new_print_stmt(value: Expression, span: Span) -> Self {
+        Self::new(ExpressionKind::Print(Box::new(value)), span)
     }
 
-    fn new_len_stmt(value: Expression) -> Self {
-        Self::Len(Box::new(value))
+    fn new_len_stmt(value: Expression, span: Span) -> Self {
+        Self::new(ExpressionKind::Len(Box::new(value)), span)
     }
 
-    fn new_return_stmt(value: Expression) -> Self {
-        Self::ReturnStmt(Box::new(value))
+    fn new_return_stmt(value: Expression, span: Span) -> Self {
+        Self::new(ExpressionKind::ReturnStmt(Box::new(value)), span)
     }
 
-    fn new_break_stmt() -> Self {
-        Self::BreakStmt
+    fn new_break_stmt(label: Option<String>, span: Span) -> Self {
+        Self::new(ExpressionKind::BreakStmt(label), span)
+    }
+
+    fn new_continue_stmt(label: Option<String>, span: Span) -> Self {
+        Self::new(ExpressionKind::ContinueStmt(label), span)
+    }
+
+    fn new_labeled_stmt(label: String, loop_stmt: Expression, span: Span) -> Self {
+        Self::new(
+            ExpressionKind::LabeledStmt(label, Box::new(loop_stmt)),
+            span,
+        )
     }
 }
 
 fn get_type(next: pest::iterators::Pair<Rule>) -> Type {
     let mut inner_pairs = next.into_inner();
-    let next = inner_pairs.next().unwrap();
+    let ty = type_from_pair(inner_pairs.next().unwrap());
+    optional_if_marked(ty, &mut inner_pairs)
+}
+
+// Wraps `ty` in Type::Optional when it is followed by a `?`
+fn optional_if_marked(ty: Type, pairs: &mut Pairs<Rule>) -> Type {
+    if pairs
+        .peek()
+        .is_some_and(|p| p.as_rule() == Rule::optional_marker)
+    {
+        pairs.next();
+        return Type::Optional(Box::new(ty));
+    }
+    ty
+}
+
+fn type_from_pair(next: pest::iterators::Pair<Rule>) -> Type {
     match next.as_rule() {
         Rule::string_type => Type::String,
         Rule::bool_type => Type::Bool,
         Rule::i32_type => Type::i32,
         Rule::i64_type => Type::i64,
+        Rule::f64_type => Type::f64,
+        Rule::struct_type => Type::Struct(next.as_str().to_string()),
         Rule::list_type => {
             let list_inner_type = get_type(next);
             Type::List(Box::new(list_inner_type))
         }
+        Rule::map_type => {
+            let mut inner_pairs = next.into_inner();
+            let key_type = type_from_pair(inner_pairs.next().unwrap());
+            let value_type = type_from_pair(inner_pairs.next().unwrap());
+            let value_type = optional_if_marked(value_type, &mut inner_pairs);
+            Type::Map(Box::new(key_type), Box::new(value_type))
+        }
+        Rule::fn_type => {
+            let mut params = vec![];
+            let mut return_type = Type::None;
+            let mut after_arrow = false;
+            for pair in next.into_inner() {
+                match pair.as_rule() {
+                    Rule::arrow => after_arrow = true,
+                    Rule::type_name if after_arrow => return_type = get_type(pair),
+                    Rule::type_name => params.push(get_type(pair)),
+                    _ => {}
+                }
+            }
+            Type::Function(params, Box::new(return_type))
+        }
         _ => Type::None,
     }
 }
 
+fn parse_binary(pairs: Pairs<Rule>) -> Result<Expression, Box<pest::error::Error<Rule>>> {
+    PRATT_PARSER
+        .map_primary(parse_expression)
+        .map_infix(|left, op, right| {
+            let (left, right) = (left?, right?);
+            let span = left.span.to(right.span);
+            Ok(Expression::new_binary(
+                left,
+                op.as_str().to_string(),
+                right,
+                span,
+            ))
+        })
+        .parse(pairs)
+}
+
 fn parse_expression(
     pair: pest::iterators::Pair<Rule>,
 ) -> Result<Expression, Box<pest::error::Error<Rule>>> {
+    let span = Span::from(pair.as_span());
     match pair.as_rule() {
         Rule::number => {
             let val_str = pair.as_str();
@@ -200,22 +518,33 @@ fn parse_expression(
                             pair.as_span(),
                         )
                     })?;
-                    Ok(Expression::new_number64(n))
+                    Ok(Expression::new_number64(n, span))
                 }
-                Ok(n) => Ok(Expression::new_number(n)),
+                Ok(n) => Ok(Expression::new_number(n, span)),
             }
         }
+        Rule::float => {
+            let n: f64 = pair.as_str().parse().map_err(|e: ParseFloatError| {
+                pest::error::Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: e.to_string(),
+                    },
+                    pair.as_span(),
+                )
+            })?;
+            Ok(Expression::new_float(n, span))
+        }
         Rule::name => {
             let s = pair.as_str().to_string().replace(' ', "");
-            Ok(Expression::new_variable(s))
+            Ok(Expression::new_variable(s, span))
         }
         Rule::string => {
             let s = pair.as_str().to_string();
-            Ok(Expression::new_string(s))
+            Ok(Expression::new_string(s, span))
         }
         Rule::bool => match pair.as_str() {
-            "true" => Ok(Expression::new_bool(true)),
-            "false" => Ok(Expression::new_bool(false)),
+            "true" => Ok(Expression::new_bool(true, span)),
+            "false" => Ok(Expression::new_bool(false, span)),
             _ => Err(Box::new(pest::error::Error::new_from_span(
                 pest::error::ErrorVariant::CustomError {
                     message: "Invalid boolean value".to_string(),
@@ -223,18 +552,17 @@ fn parse_expression(
                 pair.as_span(),
             ))),
         },
-        Rule::nil => Ok(Expression::new_nil()),
-        Rule::binary => {
+        Rule::nil => Ok(Expression::new_nil(span)),
+        Rule::binary => parse_binary(pair.into_inner()),
+        Rule::unary => {
             let mut inner_pairs = pair.into_inner();
-            let next = inner_pairs.next().unwrap();
-            let left = parse_expression(next)?;
             let op = inner_pairs.next().unwrap().as_str().to_string();
-            let right = parse_expression(inner_pairs.next().unwrap())?;
-            Ok(Expression::new_binary(left, op, right))
+            let operand = parse_expression(inner_pairs.next().unwrap())?;
+            Ok(Expression::new_unary(op, operand, span))
         }
         Rule::grouping => {
             let inner_pair = pair.into_inner().next().unwrap();
-            parse_expression(inner_pair).map(|expr| Expression::Grouping(Box::new(expr)))
+            parse_expression(inner_pair).map(|expr| Expression::new_grouping(expr, span))
         }
         Rule::let_stmt => {
             let mut inner_pairs = pair.into_inner();
@@ -252,14 +580,14 @@ fn parse_expression(
                 inner_pairs.next();
             }
             let value = parse_expression(inner_pairs.next().unwrap())?;
-            Ok(Expression::new_let_stmt(name, let_type, value))
+            Ok(Expression::new_let_stmt(name, let_type, value, span))
         }
         Rule::expression => {
             let mut inner_pairs = pair.into_inner();
             let left = parse_expression(inner_pairs.next().unwrap())?;
             let op = inner_pairs.next().unwrap().as_str().to_string();
             let right = parse_expression(inner_pairs.next().unwrap())?;
-            Ok(Expression::new_binary(left, op, right))
+            Ok(Expression::new_binary(left, op, right, span))
         }
         Rule::literal => {
             let inner_pair = pair.into_inner().next().unwrap();
@@ -268,20 +596,33 @@ fn parse_expression(
         Rule::print_stmt => {
             let inner_pair = pair.into_inner().next().unwrap();
             let value = parse_expression(inner_pair)?;
-            Ok(Expression::new_print_stmt(value))
+            Ok(Expression::new_print_stmt(value, span))
         }
         Rule::len_stmt => {
             let inner_pair = pair.into_inner().next().unwrap();
             let value = parse_expression(inner_pair)?;
-            Ok(Expression::new_len_stmt(value))
+            Ok(Expression::new_len_stmt(value, span))
         }
-        Rule::func_stmt => {
+        Rule::func_stmt | Rule::method_stmt => {
             let mut inner_pairs = pair.into_inner();
             let name = inner_pairs.next().unwrap().as_str().to_string();
 
             // Does this handle no args?
             let mut func_args = vec![];
 
+            // The type of `self` is filled in by the enclosing impl block
+            if inner_pairs
+                .peek()
+                .is_some_and(|p| p.as_rule() == Rule::self_arg)
+            {
+                let self_arg = inner_pairs.next().unwrap();
+                func_args.push(Expression::new_func_arg(
+                    "self".to_string(),
+                    Type::None,
+                    Span::from(self_arg.as_span()),
+                ));
+            }
+
             while inner_pairs
                 .peek()
                 .is_some_and(|p| p.as_rule() == Rule::func_arg)
@@ -303,7 +644,7 @@ fn parse_expression(
             }
             let inner = inner_pairs.next().unwrap();
             let body = parse_expression(inner)?;
-            let func = Expression::new_func_stmt(name, func_args, func_type, body);
+            let func = Expression::new_func_stmt(name, func_args, func_type, body, span);
             Ok(func)
         }
         Rule::extern_func_stmt => {
@@ -331,11 +672,34 @@ fn parse_expression(
             }
 
             Ok(Expression::new_extern_func_stmt(
-                name,
-                func_args,
-                func_type,
+                name, func_args, func_type, span,
             ))
         }
+        Rule::pub_func_stmt => {
+            let mut inner_pairs = pair.into_inner();
+            inner_pairs.next(); // consume the pub keyword
+            let func = parse_expression(inner_pairs.next().unwrap())?;
+            Ok(Expression::new(
+                ExpressionKind::PubStmt(Box::new(func)),
+                span,
+            ))
+        }
+        Rule::import_stmt => {
+            let mut inner_pairs = pair.into_inner();
+            let keyword = inner_pairs.next().unwrap();
+            let target = inner_pairs.next().unwrap().as_str();
+            // `use util;` is shorthand for `import "util.cyc";`
+            let path = if keyword.as_rule() == Rule::use_keyword {
+                format!("{}.cyc", target)
+            } else {
+                target.replace('"', "")
+            };
+            Ok(Expression::new(ExpressionKind::Import(path), span))
+        }
+        Rule::extern_module_stmt => {
+            let path = pair.into_inner().next().unwrap().as_str().replace('"', "");
+            Ok(Expression::new_extern_module(path, span))
+        }
         Rule::func_arg => {
             let mut inner_pairs = pair.clone().into_inner();
             while inner_pairs.peek().is_some_and(|p| {
@@ -348,8 +712,11 @@ fn parse_expression(
                     continue;
                 }
                 if next.as_rule() == Rule::type_name {
+                    let type_span = Span::from(next.as_span());
                     let arg_type = get_type(next);
-                    let arg_name = inner_pairs.next().unwrap().as_str().to_string();
+                    let arg_name_pair = inner_pairs.next().unwrap();
+                    let arg_span = type_span.to(Span::from(arg_name_pair.as_span()));
+                    let arg_name = arg_name_pair.as_str().to_string();
                     if arg_type == Type::None {
                         return Err(Box::new(pest::error::Error::new_from_span(
                             pest::error::ErrorVariant::CustomError {
@@ -361,7 +728,7 @@ fn parse_expression(
                             pair.as_span(),
                         )));
                     }
-                    return Ok(Expression::new_func_arg(arg_name, arg_type));
+                    return Ok(Expression::new_func_arg(arg_name, arg_type, arg_span));
                 }
             }
             unreachable!("Unable to parse args {:?}", inner_pairs)
@@ -374,7 +741,10 @@ fn parse_expression(
             while inner_pairs.peek().is_some_and(|p| {
                 p.as_rule() == Rule::comma
                     || p.as_rule() == Rule::binary
+                    || p.as_rule() == Rule::unary
                     || p.as_rule() == Rule::literal
+                    || p.as_rule() == Rule::field_access
+                    || p.as_rule() == Rule::lambda
                     || p.as_rule() == Rule::name
             }) {
                 let next = inner_pairs.next().unwrap();
@@ -383,16 +753,7 @@ fn parse_expression(
                     args.push(arg_expr);
                 }
             }
-            let call = Expression::new_call_stmt(name, args);
-            if let Expression::CallStmt(call_name, call_args) = &call {
-                if call_name == "extern_module" && call_args.len() == 1 {
-                    if let Expression::String(path) = &call_args[0] {
-                        let path = path.replace('"', "");
-                        return Ok(Expression::new_extern_module(path));
-                    }
-                }
-            }
-            Ok(call)
+            Ok(Expression::new_call_stmt(name, args, span))
         }
         Rule::block_stmt => {
             let inner_pairs = pair.into_inner();
@@ -406,7 +767,7 @@ fn parse_expression(
                 expressions.push(parse_expression(inner_pair)?);
             }
 
-            Ok(Expression::new_block_stmt(expressions))
+            Ok(Expression::new_block_stmt(expressions, span))
         }
         Rule::if_stmt => {
             let mut inner_pairs = pair.into_inner();
@@ -417,47 +778,57 @@ fn parse_expression(
             } else {
                 None
             };
-            Ok(Expression::new_if_stmt(cond, if_stmt, else_stmt))
+            Ok(Expression::new_if_stmt(cond, if_stmt, else_stmt, span))
         }
         Rule::for_stmt => {
-            //TODO: improve this logic
             let mut inner_pairs = pair.into_inner();
-            let mut var = inner_pairs.next().unwrap().into_inner();
-            let var_name = var.next().unwrap().as_str().to_string().replace(' ', "");
-            let start = var.next().unwrap().as_str().parse::<i32>().unwrap();
-
-            //TODO: Identify > and < signs
-            let mut cond_stmt = inner_pairs.next().unwrap().into_inner();
-            let _cond_var_name = cond_stmt
-                .next()
-                .unwrap()
-                .as_str()
-                .to_string()
-                .replace(' ', "");
-            let end = cond_stmt.next().unwrap().as_str().parse::<i32>().unwrap();
-
-            let mut step = 1;
-            let step_stmt = inner_pairs.next();
-
-            if step_stmt.unwrap().as_str().to_string().contains("--") {
-                step = -1;
-            }
+            let init_pair = inner_pairs.next().unwrap().into_inner().next().unwrap();
+            let init = parse_expression(init_pair)?;
+            let cond_pair = inner_pairs.next().unwrap().into_inner().next().unwrap();
+            let cond = parse_expression(cond_pair)?;
+            let step = parse_for_step(inner_pairs.next().unwrap())?;
             let block_stmt = parse_expression(inner_pairs.next().unwrap())?;
-            Ok(Expression::new_for_stmt(
-                var_name, start, end, step, block_stmt,
+            Ok(Expression::new_for_stmt(init, cond, step, block_stmt, span))
+        }
+        Rule::for_in_stmt => {
+            let mut inner_pairs = pair.into_inner();
+            let var_name = inner_pairs.next().unwrap().as_str().to_string();
+            inner_pairs.next(); // consume the in keyword
+            let iterable = parse_expression(inner_pairs.next().unwrap())?;
+            let block_stmt = parse_expression(inner_pairs.next().unwrap())?;
+            Ok(Expression::new_for_each_stmt(
+                var_name, iterable, block_stmt, span,
             ))
         }
         Rule::return_stmt => {
             let inner_pairs = pair.into_inner().next().unwrap();
             let expr = parse_expression(inner_pairs)?;
-            Ok(Expression::new_return_stmt(expr))
+            Ok(Expression::new_return_stmt(expr, span))
+        }
+        Rule::break_stmt | Rule::continue_stmt => {
+            let rule = pair.as_rule();
+            let label = pair
+                .into_inner()
+                .find(|p| p.as_rule() == Rule::name)
+                .map(|p| p.as_str().to_string());
+            if rule == Rule::break_stmt {
+                Ok(Expression::new_break_stmt(label, span))
+            } else {
+                Ok(Expression::new_continue_stmt(label, span))
+            }
+        }
+        Rule::labeled_stmt => {
+            let mut inner_pairs = pair.into_inner();
+            let label = inner_pairs.next().unwrap().as_str().to_string();
+            inner_pairs.next(); // consume the colon
+            let loop_stmt = parse_expression(inner_pairs.next().unwrap())?;
+            Ok(Expression::new_labeled_stmt(label, loop_stmt, span))
         }
-        Rule::break_stmt => Ok(Expression::new_break_stmt()),
         Rule::while_stmt => {
             let mut inner_pairs = pair.into_inner();
             let cond = parse_expression(inner_pairs.next().unwrap())?;
             let while_block_expr = parse_expression(inner_pairs.next().unwrap())?;
-            Ok(Expression::new_while_stmt(cond, while_block_expr))
+            Ok(Expression::new_while_stmt(cond, while_block_expr, span))
         }
         Rule::list => {
             let mut inner_pairs = pair.into_inner();
@@ -473,14 +844,233 @@ fn parse_expression(
                     list.push(expr);
                 }
             }
-            Ok(Expression::new_list(list))
+            Ok(Expression::new_list(list, span))
+        }
+        Rule::map => {
+            let mut entries = vec![];
+            for entry in pair.into_inner() {
+                let mut inner_pairs = entry.into_inner();
+                let key = parse_expression(inner_pairs.next().unwrap())?;
+                let value = parse_expression(inner_pairs.next().unwrap())?;
+                entries.push((key, value));
+            }
+            Ok(Expression::new_map(entries, span))
         }
         Rule::list_index => {
             let mut inner_pairs = pair.into_inner();
             let array_expr = parse_expression(inner_pairs.next().unwrap())?;
             inner_pairs.next(); // consume lbracket [
             let index_expr = parse_expression(inner_pairs.next().unwrap())?;
-            Ok(Expression::new_list_index(array_expr, index_expr))
+            Ok(Expression::new_list_index(array_expr, index_expr, span))
+        }
+        Rule::list_slice => {
+            let mut inner_pairs = pair.into_inner();
+            let list_expr = parse_expression(inner_pairs.next().unwrap())?;
+            let (mut start, mut end) = (None, None);
+            for bound in inner_pairs {
+                match bound.as_rule() {
+                    Rule::slice_start => {
+                        start = Some(parse_expression(bound.into_inner().next().unwrap())?)
+                    }
+                    Rule::slice_end => {
+                        end = Some(parse_expression(bound.into_inner().next().unwrap())?)
+                    }
+                    // [, : and ]
+                    _ => {}
+                }
+            }
+            Ok(Expression::new_list_slice(list_expr, start, end, span))
+        }
+        Rule::struct_stmt => {
+            let mut inner_pairs = pair.into_inner();
+            inner_pairs.next(); // consume the struct keyword
+            let name = inner_pairs.next().unwrap().as_str().to_string();
+            let mut fields = vec![];
+            for field in inner_pairs.filter(|p| p.as_rule() == Rule::struct_field) {
+                let mut field_pairs = field.into_inner();
+                let field_name = field_pairs.next().unwrap().as_str().to_string();
+                field_pairs.next(); // consume the colon
+                fields.push((field_name, get_type(field_pairs.next().unwrap())));
+            }
+            Ok(Expression::new(
+                ExpressionKind::StructStmt(name, fields),
+                span,
+            ))
+        }
+        Rule::enum_stmt => {
+            let mut inner_pairs = pair.into_inner();
+            inner_pairs.next(); // consume the enum keyword
+            let name = inner_pairs.next().unwrap().as_str().to_string();
+            let mut variants = vec![];
+            for variant in inner_pairs.filter(|p| p.as_rule() == Rule::enum_variant) {
+                let mut variant_pairs = variant.into_inner();
+                let variant_name = variant_pairs.next().unwrap().as_str().to_string();
+                let payload = variant_pairs
+                    .filter(|p| p.as_rule() == Rule::type_name)
+                    .map(get_type)
+                    .collect();
+                variants.push((variant_name, payload));
+            }
+            Ok(Expression::new(
+                ExpressionKind::EnumStmt(name, variants),
+                span,
+            ))
+        }
+        Rule::enum_value => {
+            let mut inner_pairs = pair.into_inner();
+            let enum_name = inner_pairs.next().unwrap().as_str().to_string();
+            let variant = inner_pairs.next().unwrap().as_str().to_string();
+            let values = inner_pairs
+                .filter(|p| p.as_rule() != Rule::comma)
+                .map(parse_expression)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Expression::new(
+                ExpressionKind::EnumValue(enum_name, variant, values),
+                span,
+            ))
+        }
+        Rule::match_stmt => {
+            let mut inner_pairs = pair.into_inner();
+            inner_pairs.next(); // consume the match keyword
+            let value = parse_expression(inner_pairs.next().unwrap())?;
+            let arms = inner_pairs
+                .filter(|p| p.as_rule() == Rule::match_arm)
+                .map(parse_expression)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Expression::new(
+                ExpressionKind::MatchStmt(Box::new(value), arms),
+                span,
+            ))
+        }
+        Rule::match_arm => {
+            let mut inner_pairs = pair.into_inner();
+            let pattern_pair = inner_pairs.next().unwrap();
+            let pattern = match pattern_pair.as_rule() {
+                Rule::wildcard => Pattern::Wildcard,
+                _ => {
+                    let mut names = pattern_pair
+                        .into_inner()
+                        .filter(|p| p.as_rule() == Rule::name)
+                        .map(|p| p.as_str().to_string());
+                    let enum_name = names.next().unwrap();
+                    let variant = names.next().unwrap();
+                    Pattern::Variant(enum_name, variant, names.collect())
+                }
+            };
+            inner_pairs.next(); // skip =>
+            let body = parse_expression(inner_pairs.next().unwrap())?;
+            Ok(Expression::new(
+                ExpressionKind::MatchArm(pattern, Box::new(body)),
+                span,
+            ))
+        }
+        Rule::impl_stmt => {
+            let mut inner_pairs = pair.into_inner();
+            inner_pairs.next(); // consume the impl keyword
+            let self_type = get_type(inner_pairs.next().unwrap());
+            let mut methods = vec![];
+            for method_pair in inner_pairs {
+                let mut method = parse_expression(method_pair)?;
+                // method_stmt always starts its arguments with `self`
+                if let ExpressionKind::FuncStmt(_, args, _, _) = &mut method.kind {
+                    args[0].kind = ExpressionKind::FuncArg("self".to_string(), self_type.clone());
+                }
+                methods.push(method);
+            }
+            Ok(Expression::new(
+                ExpressionKind::ImplStmt(self_type, methods),
+                span,
+            ))
+        }
+        Rule::lambda => {
+            let mut params = vec![];
+            let mut return_type = Type::None;
+            let mut body = None;
+            for inner in pair.into_inner() {
+                match inner.as_rule() {
+                    Rule::lambda_param => {
+                        let mut param_pairs = inner.into_inner();
+                        let first = param_pairs.next().unwrap();
+                        match param_pairs.next() {
+                            Some(name) => params.push((name.as_str().to_string(), get_type(first))),
+                            None => params.push((first.as_str().to_string(), Type::None)),
+                        }
+                    }
+                    Rule::type_name => return_type = get_type(inner),
+                    Rule::comma | Rule::arrow => {}
+                    _ => body = Some(parse_expression(inner)?),
+                }
+            }
+            Ok(Expression::new(
+                ExpressionKind::Lambda(params, return_type, Box::new(body.unwrap())),
+                span,
+            ))
+        }
+        Rule::struct_literal => {
+            let mut inner_pairs = pair.into_inner();
+            let name = inner_pairs.next().unwrap().as_str().to_string();
+            let mut fields = vec![];
+            for field in inner_pairs.filter(|p| p.as_rule() == Rule::struct_literal_field) {
+                let mut field_pairs = field.into_inner();
+                let field_name = field_pairs.next().unwrap().as_str().to_string();
+                field_pairs.next(); // consume the colon
+                fields.push((field_name, parse_expression(field_pairs.next().unwrap())?));
+            }
+            Ok(Expression::new(
+                ExpressionKind::StructLiteral(name, fields),
+                span,
+            ))
+        }
+        Rule::field_access => {
+            let mut inner_pairs = pair.into_inner();
+            let mut value = parse_expression(inner_pairs.next().unwrap())?;
+            for next in inner_pairs {
+                let next_span = value.span.to(Span::from(next.as_span()));
+                let kind = match next.as_rule() {
+                    Rule::field_index => {
+                        // the index sits between the brackets
+                        let index_pair = next.into_inner().nth(1).unwrap();
+                        let index = parse_expression(index_pair)?;
+                        ExpressionKind::ListIndex(Box::new(value), Box::new(index))
+                    }
+                    // the arguments turn the field just read into a method call
+                    Rule::method_args => {
+                        let ExpressionKind::FieldAccess(receiver, method) = value.kind else {
+                            unreachable!("method arguments always follow a name")
+                        };
+                        let args = next
+                            .into_inner()
+                            .filter(|p| p.as_rule() != Rule::comma)
+                            .map(parse_expression)
+                            .collect::<Result<Vec<_>, _>>()?;
+                        ExpressionKind::MethodCall(receiver, method, args)
+                    }
+                    _ => ExpressionKind::FieldAccess(Box::new(value), next.as_str().to_string()),
+                };
+                value = Expression::new(kind, next_span);
+            }
+            Ok(value)
+        }
+        Rule::field_assign_stmt => {
+            let mut inner_pairs = pair.into_inner();
+            let target_pair = inner_pairs.next().unwrap();
+            let target_span = target_pair.as_span();
+            let target = parse_expression(target_pair)?;
+            inner_pairs.next(); // skip = sign
+            let value = parse_expression(inner_pairs.next().unwrap())?;
+            match target.kind {
+                ExpressionKind::FieldAccess(object, field) => Ok(Expression::new(
+                    ExpressionKind::FieldAssign(object, field, Box::new(value)),
+                    span,
+                )),
+                // e.g. p.points[0] = q
+                _ => Err(Box::new(pest::error::Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: "Only a struct field can be assigned to here".to_string(),
+                    },
+                    target_span,
+                ))),
+            }
         }
         Rule::index_stmt => {
             let mut inner_pairs = pair.into_inner();
@@ -495,6 +1085,7 @@ fn parse_expression(
                 array_var.to_string(),
                 array_index,
                 array_assign,
+                span,
             ))
         }
         _ => Err(Box::new(pest::error::Error::new_from_span(
@@ -509,6 +1100,35 @@ fn parse_expression(
     }
 }
 
+/// Parses the step of a `for` loop into an assignment to the loop variable,
+/// e.g. `i++` becomes `i = i + 1` and `i -= 2` becomes `i = i - 2`.
+fn parse_for_step(
+    pair: pest::iterators::Pair<Rule>,
+) -> Result<Expression, Box<pest::error::Error<Rule>>> {
+    let span = Span::from(pair.as_span());
+    let mut inner_pairs = pair.into_inner();
+    let name = inner_pairs.next().unwrap().as_str().to_string();
+    let update = inner_pairs.next().unwrap();
+    let variable = Expression::new(ExpressionKind::Variable(name.clone()), span);
+    let op = match update.as_rule() {
+        Rule::increment | Rule::add_assign => "+",
+        _ => "-",
+    };
+    let value = match update.as_rule() {
+        Rule::increment | Rule::decrement => {
+            let one = Expression::new(ExpressionKind::Number(1), span);
+            Expression::new_binary(variable, op.to_string(), one, span)
+        }
+        Rule::add_assign | Rule::subtract_assign => {
+            let rhs = parse_expression(update.into_inner().next().unwrap())?;
+            Expression::new_binary(variable, op.to_string(), rhs, span)
+        }
+        // a plain assignment, the `=` has already been consumed
+        _ => parse_expression(inner_pairs.next().unwrap())?,
+    };
+    Ok(Expression::new_let_stmt(name, Type::None, value, span))
+}
+
 fn parse_program(
     pair: pest::iterators::Pair<Rule>,
 ) -> Result<Vec<Expression>, Box<pest::error::Error<Rule>>> {
@@ -528,8 +1148,7 @@ fn parse_program(
 }
 
 pub fn parse_cyclo_program(input: &str) -> Result<Vec<Expression>, Box<pest::error::Error<Rule>>> {
-    let input = preprocess_extern_modules(input);
-    match CycloParser::parse(Rule::expression_list, &input) {
+    match CycloParser::parse(Rule::expression_list, input) {
         Ok(mut pairs) => {
             // TODO: only returns first pair
             // should this iterate through all pairs?
@@ -542,44 +1161,17 @@ pub fn parse_cyclo_program(input: &str) -> Result<Vec<Expression>, Box<pest::err
     unreachable!("parse function program")
 }
 
-fn preprocess_extern_modules(input: &str) -> String {
-    let mut out = String::new();
-    for chunk in input.split_inclusive('\n') {
-        let (line, newline) = if let Some(stripped) = chunk.strip_suffix('\n') {
-            (stripped, "\n")
-        } else {
-            (chunk, "")
-        };
-
-        let trimmed = line.trim_start();
-        if trimmed.starts_with("extern module") {
-            let indent_len = line.len() - trimmed.len();
-            let indent = &line[..indent_len];
-            if let Some(start) = trimmed.find('"') {
-                let rest = &trimmed[start + 1..];
-                if let Some(end) = rest.find('"') {
-                    let path = &rest[..end];
-                    out.push_str(indent);
-                    out.push_str("extern_module(\"");
-                    out.push_str(path);
-                    out.push_str("\");");
-                    out.push_str(newline);
-                    continue;
-                }
-            }
-        }
-
-        out.push_str(line);
-        out.push_str(newline);
-    }
-    out
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::Expression::{FuncArg, Number, Variable};
+    use crate::ExpressionKind::{
+        Binary, BlockStmt, Bool, CallStmt, FuncArg, Number, ReturnStmt, Variable,
+    };
 
+    // builds a node for comparison, spans are ignored by Expression's PartialEq
+    fn expr(kind: ExpressionKind) -> Expression {
+        Expression::new(kind, Span::default())
+    }
     #[test]
     fn test_parse_string_expression() {
         let input = r#""hello";"#;
@@ -643,6 +1235,163 @@ mod test {
         assert!(parse_cyclo_program(input).is_ok());
     }
 
+    #[test]
+    fn test_parse_float_expression() {
+        let input = r#"let a: f64 = -1.5 * 2.25;"#;
+        let res = parse_cyclo_program(input).unwrap();
+        let value = match &res[0].kind {
+            ExpressionKind::LetStmt(_, Type::f64, value) => value,
+            other => panic!("expected f64 let statement, got {:?}", other),
+        };
+        assert_eq!(
+            **value,
+            expr(Binary(
+                Box::new(expr(ExpressionKind::Float(-1.5))),
+                "*".to_string(),
+                Box::new(expr(ExpressionKind::Float(2.25))),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_unary_expressions() {
+        let input = r#"print(!done); print(-x); print(-5);"#;
+        let res = parse_cyclo_program(input).unwrap();
+        let values: Vec<Expression> = res
+            .into_iter()
+            .map(|e| match e.kind {
+                ExpressionKind::Print(value) => *value,
+                other => panic!("expected print statement, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                expr(ExpressionKind::Unary(
+                    "!".to_string(),
+                    Box::new(expr(Variable("done".to_string())))
+                )),
+                expr(ExpressionKind::Unary(
+                    "-".to_string(),
+                    Box::new(expr(Variable("x".to_string())))
+                )),
+                expr(Number(-5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_logical_expression() {
+        let input = r#"if ((a > 0) && !(b > 0)) { print(a); }"#;
+        let res = parse_cyclo_program(input).unwrap();
+        let cond = match &res[0].kind {
+            ExpressionKind::IfStmt(cond, _, _) => cond,
+            other => panic!("expected if statement, got {:?}", other),
+        };
+        let gt = |name: &str| {
+            expr(Binary(
+                Box::new(expr(Variable(name.to_string()))),
+                ">".to_string(),
+                Box::new(expr(Number(0))),
+            ))
+        };
+        assert_eq!(
+            **cond,
+            expr(Binary(
+                Box::new(expr(ExpressionKind::Grouping(Box::new(gt("a"))))),
+                "&&".to_string(),
+                Box::new(expr(ExpressionKind::Unary(
+                    "!".to_string(),
+                    Box::new(expr(ExpressionKind::Grouping(Box::new(gt("b"))))),
+                ))),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_source_file_location() {
+        let source = SourceFile::new("main.cyc", "fn main() {\n    print(1 / 0);\n}");
+        assert_eq!(source.line_col(0), (1, 1));
+        assert_eq!(source.location(Span::new(22, 27)), "main.cyc:2:11");
+        assert_eq!(source.line_col(100), (3, 2));
+    }
+
+    // Renders binary and unary trees as s-expressions so precedence tests stay readable
+    fn sexpr(e: &Expression) -> String {
+        match &e.kind {
+            Binary(left, op, right) => format!("({} {} {})", op, sexpr(left), sexpr(right)),
+            ExpressionKind::Unary(op, operand) => format!("({} {})", op, sexpr(operand)),
+            ExpressionKind::Grouping(inner) => sexpr(inner),
+            Number(n) => n.to_string(),
+            Variable(name) => name.clone(),
+            other => format!("{:?}", other),
+        }
+    }
+
+    fn parse_printed(input: &str) -> String {
+        let res = parse_cyclo_program(&format!("print({});", input)).unwrap();
+        match &res[0].kind {
+            ExpressionKind::Print(value) => sexpr(value),
+            other => panic!("expected print statement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_precedence_multiplicative_over_additive() {
+        assert_eq!(parse_printed("1 + 2 * 3"), "(+ 1 (* 2 3))");
+        assert_eq!(parse_printed("1 * 2 + 3"), "(+ (* 1 2) 3)");
+        assert_eq!(parse_printed("1 - 6 / 2 + 4"), "(+ (- 1 (/ 6 2)) 4)");
+    }
+
+    #[test]
+    fn test_parse_modulo_binds_like_multiplication() {
+        assert_eq!(parse_printed("1 + 7 % 3"), "(+ 1 (% 7 3))");
+        assert_eq!(parse_printed("7 % 3 * 2"), "(* (% 7 3) 2)");
+    }
+
+    #[test]
+    fn test_parse_left_associativity() {
+        assert_eq!(parse_printed("10 - 4 - 3"), "(- (- 10 4) 3)");
+        assert_eq!(parse_printed("8 / 4 / 2"), "(/ (/ 8 4) 2)");
+    }
+
+    #[test]
+    fn test_parse_power_is_right_associative() {
+        assert_eq!(parse_printed("2 ^ 3 ^ 2"), "(^ 2 (^ 3 2))");
+        assert_eq!(parse_printed("2 * 3 ^ 2"), "(* 2 (^ 3 2))");
+    }
+
+    #[test]
+    fn test_parse_precedence_comparison_and_logical() {
+        assert_eq!(
+            parse_printed("a + 1 < b * 2 && c || d"),
+            "(|| (&& (< (+ a 1) (* b 2)) c) d)"
+        );
+        assert_eq!(parse_printed("a || b && c"), "(|| a (&& b c))");
+        assert_eq!(parse_printed("a == b + 1"), "(== a (+ b 1))");
+    }
+
+    #[test]
+    fn test_parse_grouping_and_unary_bind_tightest() {
+        assert_eq!(parse_printed("(1 + 2) * 3"), "(* (+ 1 2) 3)");
+        assert_eq!(parse_printed("-a * b"), "(* (- a) b)");
+        assert_eq!(parse_printed("!a && b"), "(&& (! a) b)");
+    }
+
+    #[test]
+    fn test_parse_binary_spans_cover_operands() {
+        let input = "print(1 + 2 * 3);";
+        let res = parse_cyclo_program(input).unwrap();
+        let ExpressionKind::Print(value) = &res[0].kind else {
+            panic!("expected print statement");
+        };
+        assert_eq!(value.span, Span::new(6, 15));
+        let ExpressionKind::Binary(_, _, right) = &value.kind else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(&input[right.span.start..right.span.end], "2 * 3");
+    }
+
     #[test]
     fn test_parse_number_expression_err() {
         let input = r#"555""#;
@@ -703,6 +1452,27 @@ mod test {
         assert!(parse_cyclo_program(input).is_ok());
     }
 
+    #[test]
+    fn test_parse_labeled_break_and_continue() {
+        let input = r#"outer: while (true) { continue outer; break; }"#;
+        let output = parse_cyclo_program(input).unwrap();
+        let body = expr(ExpressionKind::BlockStmt(vec![
+            expr(ExpressionKind::ContinueStmt(Some("outer".to_string()))),
+            expr(ExpressionKind::BreakStmt(None)),
+        ]));
+        let while_stmt = expr(ExpressionKind::WhileStmt(
+            Box::new(expr(ExpressionKind::Bool(true))),
+            Box::new(body),
+        ));
+        assert_eq!(
+            output,
+            vec![expr(ExpressionKind::LabeledStmt(
+                "outer".to_string(),
+                Box::new(while_stmt),
+            ))]
+        );
+    }
+
     #[test]
     fn test_parse_number_equals_digit() {
         let input = r#"5 == 5;"#;
@@ -832,20 +1602,35 @@ mod test {
         assert!(parse_cyclo_program(input).is_ok());
     }
 
+    #[test]
+    fn test_parse_let_stmt_empty_list() {
+        let input = r#"let value: List<i32> = [ ];"#;
+        let output = parse_cyclo_program(input).unwrap();
+        let let_stmt_expr = expr(ExpressionKind::LetStmt(
+            "value".to_string(),
+            Type::List(Box::new(Type::i32)),
+            Box::new(expr(ExpressionKind::List(vec![]))),
+        ));
+        assert_eq!(output, vec![let_stmt_expr]);
+    }
+
     #[test]
     fn test_parse_let_stmt_list_string() {
         let input = r#"let value: List<string> = ["1", "2", "3", "4"];"#;
         let output: Result<Vec<Expression>, Box<pest::error::Error<Rule>>> =
             parse_cyclo_program(input);
-        let list_expr = Expression::List(vec![
-            Expression::String("\"1\"".to_string()),
-            Expression::String("\"2\"".to_string()),
-            Expression::String("\"3\"".to_string()),
-            Expression::String("\"4\"".to_string()),
-        ]);
+        let list_expr = expr(ExpressionKind::List(vec![
+            expr(ExpressionKind::String("\"1\"".to_string())),
+            expr(ExpressionKind::String("\"2\"".to_string())),
+            expr(ExpressionKind::String("\"3\"".to_string())),
+            expr(ExpressionKind::String("\"4\"".to_string())),
+        ]));
         let list_type = Type::List(Box::new(Type::String));
-        let let_stmt_expr =
-            Expression::LetStmt("value".to_string(), list_type, Box::new(list_expr));
+        let let_stmt_expr = expr(ExpressionKind::LetStmt(
+            "value".to_string(),
+            list_type,
+            Box::new(list_expr),
+        ));
         assert!(output.is_ok());
         assert!(output.unwrap().contains(&let_stmt_expr))
     }
@@ -855,15 +1640,18 @@ mod test {
         let input = r#"let value: List<List<bool>> = [[true,false],[true,false]];"#;
         let output: Result<Vec<Expression>, Box<pest::error::Error<Rule>>> =
             parse_cyclo_program(input);
-        let list_expr = Expression::List(vec![Expression::Bool(true), Expression::Bool(false)]);
-        let list_of_list_expr = Expression::List(vec![list_expr.clone(), list_expr]);
+        let list_expr = expr(ExpressionKind::List(vec![
+            expr(ExpressionKind::Bool(true)),
+            expr(ExpressionKind::Bool(false)),
+        ]));
+        let list_of_list_expr = expr(ExpressionKind::List(vec![list_expr.clone(), list_expr]));
         let list_type = Type::List(Box::new(Type::Bool));
         let list_of_list_type = Type::List(Box::new(list_type));
-        let let_stmt_expr = Expression::LetStmt(
+        let let_stmt_expr = expr(ExpressionKind::LetStmt(
             "value".to_string(),
             list_of_list_type,
             Box::new(list_of_list_expr),
-        );
+        ));
         assert!(output.is_ok());
         assert!(output.unwrap().contains(&let_stmt_expr))
     }
@@ -873,15 +1661,15 @@ mod test {
         let input = r#"let value: List<List<i32>> = [[1,2],[1,2]];"#;
         let output: Result<Vec<Expression>, Box<pest::error::Error<Rule>>> =
             parse_cyclo_program(input);
-        let list_expr = Expression::List(vec![Number(1), Number(2)]);
-        let list_of_list_expr = Expression::List(vec![list_expr.clone(), list_expr]);
+        let list_expr = expr(ExpressionKind::List(vec![expr(Number(1)), expr(Number(2))]));
+        let list_of_list_expr = expr(ExpressionKind::List(vec![list_expr.clone(), list_expr]));
         let list_type = Type::List(Box::new(Type::i32));
         let list_of_list_type = Type::List(Box::new(list_type));
-        let let_stmt_expr = Expression::LetStmt(
+        let let_stmt_expr = expr(ExpressionKind::LetStmt(
             "value".to_string(),
             list_of_list_type,
             Box::new(list_of_list_expr),
-        );
+        ));
         assert!(output.is_ok());
         assert!(output.unwrap().contains(&let_stmt_expr))
     }
@@ -941,9 +1729,52 @@ fn main() {
 }
 "#;
         let res = parse_cyclo_program(input).unwrap();
-        assert!(matches!(res.get(0), Some(Expression::ExternModule(_))));
-        assert!(matches!(res.get(1), Some(Expression::ExternFuncStmt(_, _, _))));
-        assert!(matches!(res.get(2), Some(Expression::FuncStmt(_, _, _, _))));
+        assert!(matches!(
+            res.first().map(|e| &e.kind),
+            Some(ExpressionKind::ExternModule(_))
+        ));
+        assert!(matches!(
+            res.get(1).map(|e| &e.kind),
+            Some(ExpressionKind::ExternFuncStmt(_, _, _))
+        ));
+        assert!(matches!(
+            res.get(2).map(|e| &e.kind),
+            Some(ExpressionKind::FuncStmt(_, _, _, _))
+        ));
+    }
+
+    #[test]
+    fn test_parse_spans() {
+        let input = "let value = other + 1;";
+        let res = parse_cyclo_program(input).unwrap();
+        assert_eq!(
+            &input[res[0].span.start..res[0].span.end],
+            "let value = other + 1"
+        );
+        let ExpressionKind::LetStmt(_, _, value) = &res[0].kind else {
+            panic!("expected let statement, got {:?}", res[0]);
+        };
+        let ExpressionKind::Binary(left, _, right) = &value.kind else {
+            panic!("expected binary expression, got {:?}", value);
+        };
+        assert_eq!(left.span, Span::new(12, 17));
+        assert_eq!(right.span, Span::new(20, 21));
+    }
+
+    #[test]
+    fn test_parse_spans_after_extern_module() {
+        let input = "extern module \"sleep.c\";\nfn main() { print(x); }";
+        let res = parse_cyclo_program(input).unwrap();
+        let ExpressionKind::FuncStmt(_, _, _, body) = &res[1].kind else {
+            panic!("expected function, got {:?}", res[1]);
+        };
+        let ExpressionKind::BlockStmt(stmts) = &body.kind else {
+            panic!("expected block, got {:?}", body);
+        };
+        let ExpressionKind::Print(value) = &stmts[0].kind else {
+            panic!("expected print, got {:?}", stmts[0]);
+        };
+        assert_eq!(&input[value.span.start..value.span.end], "x");
     }
 
     // todo: fix
@@ -1038,7 +1869,7 @@ fn main() {
             "get_ten".into(),
             [].to_vec(),
             Type::i32,
-            vec![Expression::ReturnStmt(Box::new(Expression::Number(10)))],
+            vec![expr(ReturnStmt(Box::new(expr(Number(10)))))],
         );
         assert!(output.is_ok());
         assert!(output.unwrap().contains(&func_expr))
@@ -1055,11 +1886,9 @@ fn main() {
             parse_cyclo_program(input);
         let func_expr = build_basic_func_ast(
             "get_value".into(),
-            [FuncArg("value".into(), Type::i32)].to_vec(),
+            [expr(FuncArg("value".into(), Type::i32))].to_vec(),
             Type::i32,
-            vec![Expression::ReturnStmt(Box::new(Expression::Variable(
-                "value".into(),
-            )))],
+            vec![expr(ReturnStmt(Box::new(expr(Variable("value".into())))))],
         );
         assert!(output.is_ok());
         assert!(output.unwrap().contains(&func_expr))
@@ -1076,11 +1905,9 @@ fn main() {
             parse_cyclo_program(input);
         let func_expr = build_basic_func_ast(
             "get_value".into(),
-            [FuncArg("value".into(), Type::String)].to_vec(),
+            [expr(FuncArg("value".into(), Type::String))].to_vec(),
             Type::String,
-            vec![Expression::ReturnStmt(Box::new(Expression::Variable(
-                "value".into(),
-            )))],
+            vec![expr(ReturnStmt(Box::new(expr(Variable("value".into())))))],
         );
         assert!(output.is_ok());
         // Return stmt not returning correct ast
@@ -1101,16 +1928,16 @@ fn main() {
         let func_expr = build_basic_func_ast(
             "add".into(),
             [
-                FuncArg("x".into(), Type::i32),
-                FuncArg("y".into(), Type::i32),
+                expr(FuncArg("x".into(), Type::i32)),
+                expr(FuncArg("y".into(), Type::i32)),
             ]
             .to_vec(),
             Type::i32,
-            vec![Expression::ReturnStmt(Box::new(Expression::Binary(
-                Box::new(Variable("x".into())),
+            vec![expr(ReturnStmt(Box::new(expr(Binary(
+                Box::new(expr(Variable("x".into()))),
                 "+".into(),
-                Box::new(Variable("y".into())),
-            )))],
+                Box::new(expr(Variable("y".into()))),
+            )))))],
         );
         assert!(output.is_ok());
         assert!(output.unwrap().contains(&func_expr))
@@ -1123,8 +1950,8 @@ fn main() {
         return_type: Type,
         block_stmt: Vec<Expression>,
     ) -> Expression {
-        let body = Expression::BlockStmt(block_stmt);
-        Expression::new_func_stmt(name, args, return_type, body)
+        let body = expr(BlockStmt(block_stmt));
+        Expression::new_func_stmt(name, args, return_type, body, Span::default())
     }
 
     #[test]
@@ -1141,9 +1968,9 @@ fn main() {
             "hello_world".into(),
             [].to_vec(),
             Type::String,
-            vec![Expression::ReturnStmt(Box::new(Expression::String(
+            vec![expr(ReturnStmt(Box::new(expr(ExpressionKind::String(
                 "\"hello world\"".into(),
-            )))],
+            )))))],
         );
         assert!(output.is_ok());
         assert!(output.unwrap().contains(&func_expr))
@@ -1163,7 +1990,7 @@ fn main() {
             "hello_bool".into(),
             [].to_vec(),
             Type::Bool,
-            vec![Expression::ReturnStmt(Box::new(Expression::Bool(true)))],
+            vec![expr(ReturnStmt(Box::new(expr(Bool(true)))))],
         );
         assert!(output.is_ok());
         assert!(output.unwrap().contains(&func_expr))
@@ -1182,22 +2009,22 @@ fn main() {
         let func_expr = build_basic_func_ast(
             "sum_square".into(),
             [
-                FuncArg("x".into(), Type::i32),
-                FuncArg("y".into(), Type::i32),
+                expr(FuncArg("x".into(), Type::i32)),
+                expr(FuncArg("y".into(), Type::i32)),
             ]
             .to_vec(),
             Type::i32,
-            vec![Expression::ReturnStmt(Box::new(Expression::Binary(
-                Box::new(Expression::CallStmt(
+            vec![expr(ReturnStmt(Box::new(expr(Binary(
+                Box::new(expr(CallStmt(
                     "square".into(),
-                    vec![Variable("x".into())],
-                )),
+                    vec![expr(Variable("x".into()))],
+                ))),
                 "+".into(),
-                Box::new(Expression::CallStmt(
+                Box::new(expr(CallStmt(
                     "square".into(),
-                    vec![Variable("y".into())],
-                )),
-            )))],
+                    vec![expr(Variable("y".into()))],
+                ))),
+            )))))],
         );
         assert!(output.is_ok());
         assert!(output.unwrap().contains(&func_expr));
@@ -1310,6 +2137,75 @@ fn main() {
         assert!(parse_cyclo_program(input).is_ok());
     }
 
+    #[test]
+    fn test_for_loop_stmt_with_expression_bounds() {
+        let input = r#"
+        for (let i = len(xs) - 1; i >= 0; i -= 2)
+        {
+            print(i);
+        }
+        "#;
+        let output = parse_cyclo_program(input).unwrap();
+        let i = || Box::new(expr(ExpressionKind::Variable("i".to_string())));
+        let xs = Box::new(expr(ExpressionKind::Variable("xs".to_string())));
+        let init = expr(ExpressionKind::LetStmt(
+            "i".to_string(),
+            Type::None,
+            Box::new(expr(ExpressionKind::Binary(
+                Box::new(expr(ExpressionKind::Len(xs))),
+                "-".to_string(),
+                Box::new(expr(ExpressionKind::Number(1))),
+            ))),
+        ));
+        let cond = expr(ExpressionKind::Binary(
+            i(),
+            ">=".to_string(),
+            Box::new(expr(ExpressionKind::Number(0))),
+        ));
+        let step = expr(ExpressionKind::LetStmt(
+            "i".to_string(),
+            Type::None,
+            Box::new(expr(ExpressionKind::Binary(
+                i(),
+                "-".to_string(),
+                Box::new(expr(ExpressionKind::Number(2))),
+            ))),
+        ));
+        let print_i = expr(ExpressionKind::Print(i()));
+        let body = expr(ExpressionKind::BlockStmt(vec![print_i]));
+        assert_eq!(
+            output,
+            vec![expr(ExpressionKind::ForStmt(
+                Box::new(init),
+                Box::new(cond),
+                Box::new(step),
+                Box::new(body),
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_for_each_loop_stmt() {
+        let input = r#"
+        for (item in items)
+        {
+            print(item);
+        }
+        "#;
+        let output = parse_cyclo_program(input).unwrap();
+        let print_item = expr(ExpressionKind::Print(Box::new(expr(
+            ExpressionKind::Variable("item".to_string()),
+        ))));
+        assert_eq!(
+            output,
+            vec![expr(ExpressionKind::ForEachStmt(
+                "item".to_string(),
+                Box::new(expr(ExpressionKind::Variable("items".to_string()))),
+                Box::new(expr(ExpressionKind::BlockStmt(vec![print_item]))),
+            ))]
+        );
+    }
+
     #[test]
     fn test_access_and_set_value_in_list() {
         let input = r#"
@@ -1318,4 +2214,322 @@ fn main() {
         "#;
         assert!(parse_cyclo_program(input).is_ok());
     }
+
+    #[test]
+    fn test_parse_list_slice() {
+        let input = r#"let a = xs[1:len]; let b = xs[:2]; let c = xs[1:];"#;
+        let output = parse_cyclo_program(input).unwrap();
+        let xs = || Box::new(expr(ExpressionKind::Variable("xs".to_string())));
+        let let_stmt = |name: &str, start: Option<Expression>, end: Option<Expression>| {
+            expr(ExpressionKind::LetStmt(
+                name.to_string(),
+                Type::None,
+                Box::new(expr(ExpressionKind::ListSlice(
+                    xs(),
+                    start.map(Box::new),
+                    end.map(Box::new),
+                ))),
+            ))
+        };
+        let len = expr(ExpressionKind::Variable("len".to_string()));
+        assert_eq!(
+            output,
+            vec![
+                let_stmt("a", Some(expr(Number(1))), Some(len)),
+                let_stmt("b", None, Some(expr(Number(2)))),
+                let_stmt("c", Some(expr(Number(1))), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_let_stmt_map() {
+        let input =
+            r#"let ages: Map<string, List<i32>> = {"a": [1], "b": [2, 3]}; let empty = { };"#;
+        let output = parse_cyclo_program(input).unwrap();
+        let string = |s: &str| expr(ExpressionKind::String(format!("\"{s}\"")));
+        let map_expr = expr(ExpressionKind::Map(vec![
+            (
+                string("a"),
+                expr(ExpressionKind::List(vec![expr(Number(1))])),
+            ),
+            (
+                string("b"),
+                expr(ExpressionKind::List(vec![expr(Number(2)), expr(Number(3))])),
+            ),
+        ]));
+        let map_type = Type::Map(
+            Box::new(Type::String),
+            Box::new(Type::List(Box::new(Type::i32))),
+        );
+        assert_eq!(
+            output,
+            vec![
+                expr(ExpressionKind::LetStmt(
+                    "ages".to_string(),
+                    map_type,
+                    Box::new(map_expr)
+                )),
+                expr(ExpressionKind::LetStmt(
+                    "empty".to_string(),
+                    Type::None,
+                    Box::new(expr(ExpressionKind::Map(vec![])))
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_struct_decl_literal_and_fields() {
+        let input = r#"
+        struct Point { x: i32, y: List<Point> }
+        let p: Point = Point { x: 1, y: [] };
+        p.x = p.y[0].x + 1;
+        "#;
+        let output = parse_cyclo_program(input).unwrap();
+        let var = |name: &str| Box::new(expr(ExpressionKind::Variable(name.to_string())));
+        let field = |value, name: &str| expr(ExpressionKind::FieldAccess(value, name.to_string()));
+        let decl = expr(ExpressionKind::StructStmt(
+            "Point".to_string(),
+            vec![
+                ("x".to_string(), Type::i32),
+                (
+                    "y".to_string(),
+                    Type::List(Box::new(Type::Struct("Point".to_string()))),
+                ),
+            ],
+        ));
+        let literal = expr(ExpressionKind::StructLiteral(
+            "Point".to_string(),
+            vec![
+                ("x".to_string(), expr(Number(1))),
+                ("y".to_string(), expr(ExpressionKind::List(vec![]))),
+            ],
+        ));
+        let first = expr(ExpressionKind::ListIndex(
+            Box::new(field(var("p"), "y")),
+            Box::new(expr(Number(0))),
+        ));
+        let assign = expr(ExpressionKind::FieldAssign(
+            var("p"),
+            "x".to_string(),
+            Box::new(expr(ExpressionKind::Binary(
+                Box::new(field(Box::new(first), "x")),
+                "+".to_string(),
+                Box::new(expr(Number(1))),
+            ))),
+        ));
+        assert_eq!(
+            output,
+            vec![
+                decl,
+                expr(ExpressionKind::LetStmt(
+                    "p".to_string(),
+                    Type::Struct("Point".to_string()),
+                    Box::new(literal)
+                )),
+                assign,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_impl_block_and_method_calls() {
+        let input = r#"
+        impl List<i32> {
+            fn offset(self, i32 start) -> i32 {
+                return start;
+            }
+        }
+        xs.offset(1).len();
+        "#;
+        let output = parse_cyclo_program(input).unwrap();
+        let list_type = Type::List(Box::new(Type::i32));
+        let method = expr(ExpressionKind::FuncStmt(
+            "offset".to_string(),
+            vec![
+                expr(ExpressionKind::FuncArg(
+                    "self".to_string(),
+                    list_type.clone(),
+                )),
+                expr(ExpressionKind::FuncArg("start".to_string(), Type::i32)),
+            ],
+            Type::i32,
+            Box::new(expr(ExpressionKind::BlockStmt(vec![expr(
+                ExpressionKind::ReturnStmt(Box::new(expr(ExpressionKind::Variable(
+                    "start".to_string(),
+                )))),
+            )]))),
+        ));
+        let offset = expr(ExpressionKind::MethodCall(
+            Box::new(expr(ExpressionKind::Variable("xs".to_string()))),
+            "offset".to_string(),
+            vec![expr(Number(1))],
+        ));
+        assert_eq!(
+            output,
+            vec![
+                expr(ExpressionKind::ImplStmt(list_type, vec![method])),
+                expr(ExpressionKind::MethodCall(
+                    Box::new(offset),
+                    "len".to_string(),
+                    vec![]
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_enum_and_match() {
+        let input = r#"
+        enum Shape { Circle(i32), Rect(i32, i32), Empty }
+        let s = Shape::Rect(2, 3);
+        match (s) {
+            Shape::Rect(w, h) => { print(w); }
+            _ => { print(0); }
+        }
+        "#;
+        let output = parse_cyclo_program(input).unwrap();
+        let print = |value| {
+            expr(ExpressionKind::BlockStmt(vec![expr(
+                ExpressionKind::Print(Box::new(value)),
+            )]))
+        };
+        let decl = expr(ExpressionKind::EnumStmt(
+            "Shape".to_string(),
+            vec![
+                ("Circle".to_string(), vec![Type::i32]),
+                ("Rect".to_string(), vec![Type::i32, Type::i32]),
+                ("Empty".to_string(), vec![]),
+            ],
+        ));
+        let value = expr(ExpressionKind::EnumValue(
+            "Shape".to_string(),
+            "Rect".to_string(),
+            vec![expr(Number(2)), expr(Number(3))],
+        ));
+        let rect_arm = expr(ExpressionKind::MatchArm(
+            Pattern::Variant(
+                "Shape".to_string(),
+                "Rect".to_string(),
+                vec!["w".to_string(), "h".to_string()],
+            ),
+            Box::new(print(expr(ExpressionKind::Variable("w".to_string())))),
+        ));
+        let wildcard_arm = expr(ExpressionKind::MatchArm(
+            Pattern::Wildcard,
+            Box::new(print(expr(Number(0)))),
+        ));
+        assert_eq!(
+            output,
+            vec![
+                decl,
+                expr(ExpressionKind::LetStmt(
+                    "s".to_string(),
+                    Type::None,
+                    Box::new(value)
+                )),
+                expr(ExpressionKind::MatchStmt(
+                    Box::new(expr(ExpressionKind::Variable("s".to_string()))),
+                    vec![rect_arm, wildcard_arm],
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_optional_types() {
+        let input = r#"
+        let x: i32? = nil;
+        let m: Map<string, List<i32?>?> = {};
+        "#;
+        let output = parse_cyclo_program(input).unwrap();
+        let optional = |ty| Type::Optional(Box::new(ty));
+        let map_type = Type::Map(
+            Box::new(Type::String),
+            Box::new(optional(Type::List(Box::new(optional(Type::i32))))),
+        );
+        assert_eq!(
+            output,
+            vec![
+                expr(ExpressionKind::LetStmt(
+                    "x".to_string(),
+                    optional(Type::i32),
+                    Box::new(expr(ExpressionKind::Nil))
+                )),
+                expr(ExpressionKind::LetStmt(
+                    "m".to_string(),
+                    map_type,
+                    Box::new(expr(ExpressionKind::Map(vec![])))
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_lambda() {
+        let input = r#"
+        let add: fn(i32, i32) -> i32 = |a, i32 b| a + b;
+        "#;
+        let output = parse_cyclo_program(input).unwrap();
+        let var = |name: &str| Box::new(expr(ExpressionKind::Variable(name.to_string())));
+        assert_eq!(
+            output,
+            vec![expr(ExpressionKind::LetStmt(
+                "add".to_string(),
+                Type::Function(vec![Type::i32, Type::i32], Box::new(Type::i32)),
+                Box::new(expr(ExpressionKind::Lambda(
+                    vec![("a".to_string(), Type::None), ("b".to_string(), Type::i32)],
+                    Type::None,
+                    Box::new(expr(ExpressionKind::Binary(
+                        var("a"),
+                        "+".to_string(),
+                        var("b")
+                    )))
+                )))
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_parse_imports() {
+        let input = r#"
+        import "lib/util.cyc";
+        use math;
+        pub fn double(i32 x) -> i32 {
+            return x * 2;
+        }
+        let y = math::now();
+        "#;
+        let output = parse_cyclo_program(input).unwrap();
+        let double = expr(ExpressionKind::FuncStmt(
+            "double".to_string(),
+            vec![expr(FuncArg("x".to_string(), Type::i32))],
+            Type::i32,
+            Box::new(expr(BlockStmt(vec![expr(ReturnStmt(Box::new(expr(
+                Binary(
+                    Box::new(expr(Variable("x".to_string()))),
+                    "*".to_string(),
+                    Box::new(expr(Number(2))),
+                ),
+            ))))]))),
+        ));
+        assert_eq!(
+            output,
+            vec![
+                expr(ExpressionKind::Import("lib/util.cyc".to_string())),
+                expr(ExpressionKind::Import("math.cyc".to_string())),
+                expr(ExpressionKind::PubStmt(Box::new(double))),
+                expr(ExpressionKind::LetStmt(
+                    "y".to_string(),
+                    Type::None,
+                    Box::new(expr(ExpressionKind::EnumValue(
+                        "math".to_string(),
+                        "now".to_string(),
+                        vec![]
+                    )))
+                )),
+            ]
+        );
+    }
 }